@@ -0,0 +1,133 @@
+use crate::explain::Plan;
+use crate::llm::clean_json;
+use crate::schema::{IndexInfo, SchemaModel};
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+pub static PLAN_PROMPT: &str = r#"
+You are a Postgres performance expert.
+You get a query, its execution plan, the schema of the tables it reads and their existing indexes.
+Respond with valid JSON only that matches this schema:
+{
+  "explanation": "string",
+  "suggestions": [
+    { "kind": "index" | "rewrite", "sql": "string", "rationale": "string" }
+  ]
+}
+Rules
+
+* Output only JSON (no markdown fences, no text outside).
+* "explanation" describes in plain language what the plan does and where the time goes.
+* An "index" suggestion is exactly one CREATE INDEX statement.
+* A "rewrite" suggestion is the complete rewritten query returning the same rows.
+* Never suggest an index that already exists. Use only tables and columns from the schema.
+* Return an empty "suggestions" list when the plan is already good.
+"#;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SuggestionKind {
+  Index,
+  Rewrite,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Suggestion {
+  pub kind: SuggestionKind,
+  pub sql: String,
+  #[serde(default)]
+  pub rationale: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct AdviceReply {
+  #[serde(default)]
+  pub explanation: String,
+  #[serde(default)]
+  pub suggestions: Vec<Suggestion>,
+}
+
+/// A suggestion whose re-planned cost beat the original plan.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerifiedSuggestion {
+  pub suggestion: Suggestion,
+  pub cost_before: f64,
+  pub cost_after: f64,
+}
+
+impl VerifiedSuggestion {
+  /// Estimated speed-up factor, e.g. `4.0` when the cost drops to a quarter.
+  pub fn speedup(&self) -> f64 {
+    self.cost_before / self.cost_after.max(0.01)
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PlanAdvice {
+  pub explanation: String,
+  pub suggestions: Vec<VerifiedSuggestion>,
+  /// Suggestions that failed verification, with the reason
+  pub rejected: Vec<(Suggestion, String)>,
+  /// Index suggestions that could not be re-planned because hypopg is not installed
+  pub unverified: Vec<Suggestion>,
+}
+
+/// A suggestion only counts when re-planning lowers the estimated cost by at least this much.
+pub const MIN_COST_GAIN: f64 = 0.1;
+
+pub fn parse_advice(raw: &str) -> anyhow::Result<AdviceReply> {
+  serde_json::from_str(clean_json(raw)).context("LLM reply is not valid advice JSON")
+}
+
+/// User message with everything the LLM needs to review a plan.
+pub fn plan_context(sql: &str, plan: &Plan, schema: &SchemaModel, indexes: &[IndexInfo]) -> String {
+  let mut context = format!("Query:\n{sql}\n\nPlan:\n{}\n", plan.to_text());
+  if let Some(time) = plan.execution_time {
+    context.push_str(&format!("Execution time: {time:.3} ms\n"));
+  }
+
+  context.push_str("\nSchema:\n");
+  for relation in plan.relations() {
+    if let Some(table) = schema.table(&relation) {
+      let columns: Vec<String> =
+        table.columns.iter().map(|c| format!("{} {}", c.name, c.data_type)).collect();
+      context.push_str(&format!("{}({})\n", table.qualified_name(), columns.join(", ")));
+    }
+  }
+
+  context.push_str("\nExisting indexes:\n");
+  if indexes.is_empty() {
+    context.push_str("(none)\n");
+  }
+  for index in indexes {
+    context.push_str(&format!("{}\n", index.definition));
+  }
+  context
+}
+
+#[test]
+fn test_parse_advice_and_context() {
+  let raw = r#"```json
+  { "explanation": "Sequential scan over orders",
+    "suggestions": [{ "kind": "index", "sql": "CREATE INDEX ON orders (user_id)", "rationale": "filter" }] }
+  ```"#;
+  let advice = parse_advice(raw).unwrap();
+  assert_eq!(advice.suggestions[0].kind, SuggestionKind::Index);
+  assert!(parse_advice("not json").is_err());
+
+  let plan = Plan::parse(
+    r#"[{"Plan": {"Node Type": "Seq Scan", "Relation Name": "orders", "Alias": "orders",
+        "Startup Cost": 0.0, "Total Cost": 35.5, "Plan Rows": 10}}]"#,
+  )
+  .unwrap();
+  let schema: SchemaModel = serde_json::from_str(
+    r#"{"tables": [{"schema": "public", "name": "orders",
+        "columns": [{"name": "user_id", "data_type": "integer"}]}],
+        "foreign_keys": [], "functions": []}"#,
+  )
+  .unwrap();
+  let context = plan_context("SELECT * FROM orders WHERE user_id = 1", &plan, &schema, &[]);
+  assert!(context.contains("-> Seq Scan on orders (cost=0.00..35.50 rows=10)"));
+  assert!(context.contains("orders(user_id integer)"));
+  assert!(context.contains("(none)"));
+}
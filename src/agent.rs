@@ -1,14 +1,26 @@
-use crate::config::LlmConfig;
+use crate::advisor::{
+  MIN_COST_GAIN, PLAN_PROMPT, PlanAdvice, Suggestion, SuggestionKind, VerifiedSuggestion,
+  parse_advice, plan_context,
+};
+use crate::config::{LlmConfig, PostgresConfig};
+use crate::conversation::ChatMessage;
+use crate::database::Database;
 use crate::db_client::DbClient;
-use crate::llm::send_request;
-use anyhow::anyhow;
-use freya::prelude::{Readable, Signal, Writable};
+use crate::explain::Plan;
+use crate::glossary::{self, Glossary};
+use crate::history::HistoryKind;
+use crate::llm::{complete, estimate_tokens, send_request};
+use crate::summary::{SUMMARY_PROMPT, sample_result, summary_request};
+use crate::trace::TraceEvent;
+use anyhow::{Context, anyhow};
 use once_cell::sync::OnceCell;
 use tokio::sync::RwLock;
 use tracing::{debug, error};
 pub static AGENT: OnceCell<Agent> = OnceCell::new();
-use crate::conversation::{Conversation, LlmResponse};
+use crate::conversation::{Conversation, ConversationObserver, FOLLOW_UP_HEADER, LlmResponse};
 use std::collections::HashSet;
+use std::ops::{Deref, DerefMut};
+use std::time::{Duration, Instant};
 
 static SYSTEM_PROMPT: &str = r#"
 You are a Postgres SQL assistant.
@@ -105,10 +117,15 @@ Rules
 
 "#;
 
+/// The text-to-SQL agent. The database is generic so tests can use
+/// [`crate::database::FakeDatabase`]; plan advice needs a real [`DbClient`].
 #[derive(Debug)]
-pub struct Agent {
-  pub db_client: DbClient,
+pub struct Agent<D = DbClient> {
+  pub db_client: D,
   pub llm_client: RwLock<Option<LlmConfig>>,
+  pub glossary: RwLock<Glossary>,
+  /// Name of the active entry in `Setting::postgres_profiles`
+  pub active_profile: RwLock<Option<String>>,
 }
 /// Simple helper: extract table name from clarifications like
 /// "What are the columns in 'branch_heads'?"
@@ -179,21 +196,99 @@ fn extract_tables_from_sql(sql: &str) -> HashSet<String> {
   tables
 }
 
-impl Agent {
+/// The conversation the agent is working on; every write is reported to the observer.
+struct Observed<'a, O: ConversationObserver> {
+  conversation: &'a mut Conversation,
+  observer: &'a mut O,
+}
+
+impl<'a, O: ConversationObserver> Observed<'a, O> {
+  fn read(&self) -> &Conversation {
+    self.conversation
+  }
+
+  fn write(&mut self) -> ObservedWrite<'_, 'a, O> {
+    ObservedWrite { inner: self }
+  }
+}
+
+/// Mutable access to an observed conversation that notifies the observer when dropped.
+struct ObservedWrite<'w, 'a, O: ConversationObserver> {
+  inner: &'w mut Observed<'a, O>,
+}
+
+impl<O: ConversationObserver> Deref for ObservedWrite<'_, '_, O> {
+  type Target = Conversation;
+
+  fn deref(&self) -> &Conversation {
+    self.inner.conversation
+  }
+}
+
+impl<O: ConversationObserver> DerefMut for ObservedWrite<'_, '_, O> {
+  fn deref_mut(&mut self) -> &mut Conversation {
+    self.inner.conversation
+  }
+}
+
+impl<O: ConversationObserver> Drop for ObservedWrite<'_, '_, O> {
+  fn drop(&mut self) {
+    self.inner.observer.updated(self.inner.conversation);
+  }
+}
+
+impl<D: Database> Agent<D> {
+  /// Turns `query` into SQL, asking the LLM and answering its schema questions until it returns
+  /// SQL over known tables. `conversation` is updated in place and `observer` sees every change.
   pub async fn text_to_sql(
     &self,
     query: &str,
-    mut conversation: Signal<Conversation>,
+    conversation: &mut Conversation,
+    observer: &mut impl ConversationObserver,
   ) -> anyhow::Result<String> {
-    if self.db_client.config.lock().await.is_none() {
+    let mut conversation = Observed { conversation, observer };
+    if !self.db_client.is_configured().await {
       return Err(anyhow!("PG client is not configured"));
     }
+    let llm_config =
+      self.llm_client.read().await.clone().ok_or_else(|| anyhow!("LLM is not configured"))?;
+
+    conversation.write().start_trace(query);
+    let result = self.generate_sql(query, &llm_config, &mut conversation).await;
+    if let Err(e) = &result {
+      conversation.write().trace(
+        TraceEvent::Failed { reason: format!("{e:#}") },
+        Duration::ZERO,
+        0,
+      );
+    }
+    result
+  }
 
+  /// The LLM loop of `text_to_sql`, after the trace of `query` was started.
+  async fn generate_sql(
+    &self,
+    query: &str,
+    llm_config: &LlmConfig,
+    conversation: &mut Observed<'_, impl ConversationObserver>,
+  ) -> anyhow::Result<String> {
     let client = reqwest::Client::new();
-    //let mut conv = conversation.write();
-    conversation.write().add_system(SYSTEM_PROMPT);
+    // the instructions are sent once per conversation, not once per question
+    if !conversation.read().has_message("system", SYSTEM_PROMPT) {
+      conversation.write().add_system(SYSTEM_PROMPT);
+    }
+    // only the prompts for this question are kept, not one per earlier question
+    let follow_up = conversation.read().follow_up_prompt();
+    conversation.write().set_pinned_system(FOLLOW_UP_HEADER, follow_up.as_deref());
+    let glossary_prompt = Glossary::prompt_for(&self.glossary.read().await.matching(query));
+    if glossary_prompt.is_some() {
+      debug!("Injecting glossary entries into conversation");
+    }
+    conversation.write().set_pinned_system(glossary::PROMPT_HEADER, glossary_prompt.as_deref());
     conversation.write().add_user(query);
 
+    let budget = llm_config.prompt_budget();
+
     let mut attempts = 0usize;
     let max_attempts = 12usize;
 
@@ -205,17 +300,28 @@ impl Agent {
 
       let conv_snapshot = {
         let conv = conversation.read();
-        conv.clone()
+        conv.within_budget(budget)
       };
 
       // call LLM
-      let reply: LlmResponse = match send_request(&client, &conv_snapshot).await {
+      conversation.write().trace(
+        TraceEvent::LlmRequest { attempt: attempts, messages: conv_snapshot.llm_messages().len() },
+        Duration::ZERO,
+        conv_snapshot.estimated_tokens(),
+      );
+      let started = Instant::now();
+      let reply: LlmResponse = match send_request(&client, llm_config, &conv_snapshot).await {
         Ok(r) => {
           debug!(?r, "llm reply");
           r
         }
         Err(e) => {
           error!(?e, "failed request to LLM");
+          conversation.write().trace(
+            TraceEvent::LlmError { attempt: attempts, error: format!("{e}") },
+            started.elapsed(),
+            0,
+          );
           // gentle backoff / retry
           conversation.write().add_user(&format!("encounted error: {e}"));
           continue;
@@ -224,6 +330,11 @@ impl Agent {
 
       // record assistant reply (serialized JSON) so conversation has LLM output
       let serialized = serde_json::to_string(&reply)?;
+      conversation.write().trace(
+        TraceEvent::LlmResponse { attempt: attempts, reply: serialized.clone() },
+        started.elapsed(),
+        estimate_tokens(&serialized),
+      );
       conversation.write().add_assistant(&serialized);
 
       let sql_trim = reply.sql.trim();
@@ -233,6 +344,11 @@ impl Agent {
       // --- Case: both empty -> try parsing explanation for column list (LLM sometimes puts schema there)
       if sql_trim.is_empty() && clar_trim.is_empty() {
         // Nothing usable -> tell LLM what options are valid
+        conversation.write().trace(
+          TraceEvent::ValidationFailure { reason: "Both clarification and sql are empty".into() },
+          Duration::ZERO,
+          0,
+        );
         conversation.write().add_user(
           "Both clarification and sql are empty — NOT allowed. \
            If you need schema details, ask 'list all available tables' or \
@@ -249,9 +365,17 @@ impl Agent {
 
         // "list all available tables"
         if clar.eq_ignore_ascii_case("list all available tables") {
-          match self.db_client.fetch_info(clar).await {
+          match self.introspect(clar, conversation).await {
             Ok(data) => {
               debug!("DB client response for '{}': {}", clar, data);
+              conversation.write().trace(
+                TraceEvent::ClarificationResolved {
+                  clarification: clar.into(),
+                  answer: data.clone(),
+                },
+                Duration::ZERO,
+                0,
+              );
               // send a clearly formatted reply containing the available tables and instruct LLM what to do next
               conversation.write().add_user(&format!(
                 "Available tables: {data}. Based on these, ask for columns of the tables you need with \
@@ -271,7 +395,7 @@ impl Agent {
 
         // "What are the columns in 'X'?" -> fetch and remember columns, then instruct the LLM to retry SQL
         if let Some(table) = extract_table_from_clarification(clar) {
-          match self.db_client.fetch_info(clar).await {
+          match self.introspect(clar, conversation).await {
             Ok(data) => {
               debug!("DB client response for '{}': {}", clar, data);
               let cols = parse_columns_from_db_response(&data);
@@ -279,6 +403,14 @@ impl Agent {
                 let table_lc = table.to_lowercase();
                 conversation.write().remember_table_columns(&table_lc, cols.clone());
                 debug!("Remembered {} cols for table {}", cols.len(), table_lc);
+                conversation.write().trace(
+                  TraceEvent::ClarificationResolved {
+                    clarification: clar.into(),
+                    answer: format!("{table}: {}", cols.join(", ")),
+                  },
+                  Duration::ZERO,
+                  0,
+                );
                 conversation.write().add_user(&format!(
                   "Table '{}' has columns: [{}]. Now that you have the schema, please retry and produce the SQL.",
                   table, cols.join(", ")
@@ -311,9 +443,17 @@ impl Agent {
         }
 
         // fallback: unknown clarification text — forward to DB client and pass the response back
-        match self.db_client.fetch_info(clar).await {
+        match self.introspect(clar, conversation).await {
           Ok(data) => {
             debug!("DB client generic response for '{}': {}", clar, data);
+            conversation.write().trace(
+              TraceEvent::ClarificationResolved {
+                clarification: clar.into(),
+                answer: data.clone(),
+              },
+              Duration::ZERO,
+              0,
+            );
             conversation.write().add_user(&format!("DB response: {data}. Now please continue."));
           }
           Err(e) => {
@@ -331,7 +471,7 @@ impl Agent {
       let referenced_tables = extract_tables_from_sql(&reply.sql);
       let mut missing_tables: Vec<String> = Vec::new();
       for t in referenced_tables.iter() {
-        if !conversation.write().known_tables.contains_key(&t.to_lowercase()) {
+        if !conversation.read().known_tables.contains_key(&t.to_lowercase()) {
           missing_tables.push(t.clone());
         }
       }
@@ -340,13 +480,269 @@ impl Agent {
         // Ask for columns for the first missing table (sequential approach).
         // IMPORTANT: we explicitly phrase the user message to follow your allowed syntax.
         let ask_table = &missing_tables[0];
+        conversation.write().trace(
+          TraceEvent::ValidationFailure {
+            reason: format!("SQL uses '{ask_table}' whose columns are not known yet"),
+          },
+          Duration::ZERO,
+          0,
+        );
         conversation.write().add_user(&format!("What are the columns in '{ask_table}'?"));
         continue;
       }
 
       // All referenced tables have schema known — accept SQL
       debug!("Final SQL accepted: {}", reply.sql);
+      conversation.write().trace(
+        TraceEvent::FinalSql { sql: reply.sql.clone() },
+        Duration::ZERO,
+        0,
+      );
+      conversation.write().set_current_query(query, &reply.sql);
+      conversation.write().record_query(HistoryKind::Generated, &reply.sql);
       return Ok(reply.sql);
     }
   }
+
+  /// Runs a schema lookup for a clarification and records it in the trace.
+  async fn introspect(
+    &self,
+    request: &str,
+    conversation: &mut Observed<'_, impl ConversationObserver>,
+  ) -> anyhow::Result<String> {
+    let started = Instant::now();
+    let result = self.db_client.fetch_info(request).await;
+    let (response, failed) = match &result {
+      Ok(data) => (data.clone(), false),
+      Err(e) => (format!("{e}"), true),
+    };
+    conversation.write().trace(
+      TraceEvent::Introspection { request: request.into(), response, failed },
+      started.elapsed(),
+      0,
+    );
+    result
+  }
+
+  /// Answers `question` in a few sentences from a sample of the result of `sql`, capped by the
+  /// row and token limits of the LLM profile.
+  pub async fn summarize_result(
+    &self,
+    question: &str,
+    sql: &str,
+    headers: &[String],
+    rows: &[Vec<String>],
+  ) -> anyhow::Result<String> {
+    let config =
+      self.llm_client.read().await.clone().ok_or_else(|| anyhow!("LLM is not configured"))?;
+    let sample = sample_result(headers, rows, config.summary_rows, config.summary_tokens);
+    let messages = vec![
+      ChatMessage { role: "system".into(), content: SUMMARY_PROMPT.into() },
+      ChatMessage { role: "user".into(), content: summary_request(question, sql, &sample) },
+    ];
+    let answer = complete(&reqwest::Client::new(), &config, &messages).await?;
+    Ok(answer.trim().to_string())
+  }
+}
+
+impl Agent {
+  /// Connects to the Postgres profile `name` and loads its glossary, replacing the current
+  /// connection. The previous connection and glossary stay in use when either fails.
+  pub async fn switch_postgres_profile(
+    &self,
+    name: &str,
+    config: PostgresConfig,
+  ) -> anyhow::Result<()> {
+    let glossary = Glossary::load(Some(name)).context("Unable to load the glossary")?;
+    self.db_client.setup_db_client(Some(config)).await?;
+    *self.glossary.write().await = glossary;
+    *self.active_profile.write().await = Some(name.to_string());
+    Ok(())
+  }
+
+  /// Loads the glossary of the Postgres profile `name` without connecting. When it fails to
+  /// load the glossary is left empty and read-only.
+  pub async fn load_glossary(&self, name: &str) -> anyhow::Result<()> {
+    let loaded = Glossary::load(Some(name));
+    let mut glossary = self.glossary.write().await;
+    match loaded {
+      Ok(loaded) => *glossary = loaded,
+      Err(e) => {
+        *glossary = Glossary::default();
+        return Err(e);
+      }
+    }
+    Ok(())
+  }
+
+  /// Asks the LLM to explain `plan` and suggest indexes or rewrites. Every suggestion is
+  /// re-planned against the database and only kept when it lowers the estimated cost; index
+  /// suggestions stay unverified without hypopg.
+  pub async fn explain_plan(&self, sql: &str, plan: &Plan) -> anyhow::Result<PlanAdvice> {
+    let config =
+      self.llm_client.read().await.clone().ok_or_else(|| anyhow!("LLM is not configured"))?;
+    let schema = self.db_client.schema_model().await?;
+    let indexes = self.db_client.list_indexes(&plan.relations()).await?;
+
+    let messages = vec![
+      ChatMessage { role: "system".into(), content: PLAN_PROMPT.into() },
+      ChatMessage { role: "user".into(), content: plan_context(sql, plan, &schema, &indexes) },
+    ];
+    let raw = complete(&reqwest::Client::new(), &config, &messages).await?;
+    debug!(?raw, "plan advice reply");
+    let reply = parse_advice(&raw)?;
+
+    // compare estimates with estimates: an analyzed plan carries the same planner costs
+    let cost_before = self.db_client.explain(sql, false).await?.root.total_cost;
+    let mut advice = PlanAdvice { explanation: reply.explanation, ..Default::default() };
+    let hypopg = self.db_client.has_hypopg().await?;
+    for suggestion in reply.suggestions {
+      if suggestion.kind == SuggestionKind::Index && !hypopg {
+        advice.unverified.push(suggestion);
+        continue;
+      }
+      match self.verify_suggestion(sql, &suggestion).await {
+        Ok(cost_after) if cost_after <= cost_before * (1.0 - MIN_COST_GAIN) => {
+          advice.suggestions.push(VerifiedSuggestion { suggestion, cost_before, cost_after })
+        }
+        Ok(cost_after) => advice
+          .rejected
+          .push((suggestion, format!("No cost improvement ({cost_before:.2} -> {cost_after:.2})"))),
+        Err(e) => {
+          debug!(?e, "suggestion failed verification");
+          advice.rejected.push((suggestion, format!("{e}")))
+        }
+      }
+    }
+    Ok(advice)
+  }
+
+  /// Estimated cost of the query with the suggestion applied.
+  async fn verify_suggestion(&self, sql: &str, suggestion: &Suggestion) -> anyhow::Result<f64> {
+    let plan = match suggestion.kind {
+      SuggestionKind::Index => self.db_client.replan_with_index(sql, &suggestion.sql).await?,
+      SuggestionKind::Rewrite => {
+        if crate::sql::splitter::split_statements(&suggestion.sql).len() != 1 {
+          return Err(anyhow!("A rewrite must be a single statement"));
+        }
+        self.db_client.explain(&suggestion.sql, false).await?
+      }
+    };
+    Ok(plan.root.total_cost)
+  }
+}
+
+#[tokio::test]
+async fn test_text_to_sql_without_ui() {
+  struct Counter(usize);
+  impl ConversationObserver for Counter {
+    fn updated(&mut self, _conversation: &Conversation) {
+      self.0 += 1;
+    }
+  }
+
+  let mut conversation = Conversation::new();
+  let mut counter = Counter(0);
+  let mut observed = Observed { conversation: &mut conversation, observer: &mut counter };
+  observed.write().add_user("first");
+  observed.write().add_user("second");
+  assert_eq!(observed.read().messages.len(), 2);
+  assert_eq!(counter.0, 2);
+
+  let agent = Agent {
+    db_client: DbClient::new(),
+    llm_client: RwLock::new(None),
+    glossary: RwLock::new(Glossary::default()),
+    active_profile: RwLock::new(None),
+  };
+  let err = agent.text_to_sql("all users", &mut conversation, &mut ()).await.unwrap_err();
+  assert_eq!(err.to_string(), "PG client is not configured");
+  assert_eq!(conversation.messages.len(), 2);
+}
+
+#[tokio::test]
+async fn test_agent_loop_with_replayed_llm() {
+  use crate::database::FakeDatabase;
+  use crate::fixtures::Fixture;
+
+  let replay = async |name: &str| {
+    let path =
+      std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/llm").join(name);
+    let server = Fixture::load(&path).unwrap().serve().await;
+    let agent = Agent {
+      db_client: FakeDatabase::new()
+        .table(
+          "users",
+          &[("id", "integer"), ("email", "character varying"), ("full_name", "character varying")],
+        )
+        .table("orders", &[("id", "integer"), ("user_id", "integer")]),
+      llm_client: RwLock::new(Some(LlmConfig::new(&server.url("/v1"), "replay"))),
+      glossary: RwLock::new(Glossary::default()),
+      active_profile: RwLock::new(None),
+    };
+    (server, agent)
+  };
+
+  // table listing, then a column clarification, then SQL
+  let (_server, agent) = replay("list_tables.json").await;
+  let mut conversation = Conversation::new();
+  let sql = agent.text_to_sql("Who are our users?", &mut conversation, &mut ()).await.unwrap();
+  assert_eq!(sql, "SELECT id, full_name, email FROM users;");
+  let tables = conversation.messages.iter().find(|m| m.content.starts_with("Available tables:"));
+  assert!(tables.unwrap().content.contains("\"users\""));
+  // columns are remembered as `name:type`, as the DB reports them
+  assert!(conversation.known_tables["users"].contains("full_name:character varying"));
+  assert_eq!(conversation.current_query.unwrap().sql, sql);
+
+  // SQL over a table whose columns were never asked for
+  let (_server, agent) = replay("missing_table.json").await;
+  let mut conversation = Conversation::new();
+  let sql = agent.text_to_sql("Names of all users", &mut conversation, &mut ()).await.unwrap();
+  assert_eq!(sql, "SELECT full_name FROM users;");
+  assert!(conversation.has_message("user", "What are the columns in 'users'?"));
+  let trace = conversation.traces.last().unwrap();
+  assert!(trace.steps.iter().any(|s| matches!(&s.event,
+    TraceEvent::ValidationFailure { reason } if reason.contains("'users'"))));
+
+  // never a usable reply
+  let (_server, agent) = replay("no_convergence.json").await;
+  let mut conversation = Conversation::new();
+  let err = agent.text_to_sql("Show me the thing", &mut conversation, &mut ()).await.unwrap_err();
+  assert_eq!(err.to_string(), "LLM did not converge after 12 attempts");
+  let requests = conversation.traces[0]
+    .steps
+    .iter()
+    .filter(|s| matches!(s.event, TraceEvent::LlmRequest { .. }))
+    .count();
+  assert_eq!(requests, 12);
+  let last = &conversation.traces[0].steps.last().unwrap().event;
+  assert_eq!(last, &TraceEvent::Failed { reason: "LLM did not converge after 12 attempts".into() });
+}
+
+#[tokio::test]
+async fn test_agent_with_fake_database() {
+  use crate::database::FakeDatabase;
+  use crate::fixtures::Fixture;
+
+  let path =
+    std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/llm/list_tables.json");
+  let server = Fixture::load(&path).unwrap().serve().await;
+  let agent = Agent {
+    db_client: FakeDatabase::new()
+      .table("users", &[("id", "integer"), ("full_name", "text"), ("email", "text")])
+      .table("orders", &[("id", "integer"), ("user_id", "integer")]),
+    llm_client: RwLock::new(Some(LlmConfig::new(&server.url("/v1"), "replay"))),
+    glossary: RwLock::new(Glossary::default()),
+    active_profile: RwLock::new(None),
+  };
+  let mut conversation = Conversation::new();
+  let sql = agent.text_to_sql("Who are our users?", &mut conversation, &mut ()).await.unwrap();
+  assert_eq!(sql, "SELECT id, full_name, email FROM users;");
+  let tables = conversation.messages.iter().find(|m| m.content.starts_with("Available tables:"));
+  assert!(tables.unwrap().content.contains(r#"["orders", "users"]"#));
+  assert_eq!(conversation.known_tables["users"].len(), 3);
+
+  let agent = Agent { db_client: FakeDatabase::unconfigured(), ..agent };
+  let err = agent.text_to_sql("all users", &mut Conversation::new(), &mut ()).await.unwrap_err();
+  assert_eq!(err.to_string(), "PG client is not configured");
 }
@@ -0,0 +1,319 @@
+use crate::agent::Agent;
+use crate::config::Setting;
+use crate::conversation::Conversation;
+use crate::db_client::{DbClient, ResultSet};
+use crate::eval::{evaluate, load_cases};
+use crate::glossary::Glossary;
+use crate::sql::splitter::split_statements;
+use anyhow::{Context, anyhow};
+use clap::{Parser, Subcommand, ValueEnum};
+use std::path::PathBuf;
+use tokio::sync::RwLock;
+
+/// Without a subcommand the desktop app is launched.
+#[derive(Debug, Parser)]
+#[command(name = "pg-admin", version, about = "Postgres admin assistant powered by LLMs")]
+pub struct Cli {
+  /// Postgres profile from config.toml, instead of `active_postgres`
+  #[arg(long, global = true)]
+  pub profile: Option<String>,
+  /// LLM profile from config.toml, instead of `active_llm`
+  #[arg(long, global = true)]
+  pub llm: Option<String>,
+  #[command(subcommand)]
+  pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+  /// Turn a question into SQL and print it
+  Ask {
+    question: String,
+    /// Run the generated SQL and print its result instead
+    #[arg(long)]
+    run: bool,
+    #[arg(long, value_enum, default_value_t = OutputFormat::Table)]
+    format: OutputFormat,
+  },
+  /// Run the statements of a SQL file
+  Exec {
+    file: PathBuf,
+    #[arg(long, value_enum, default_value_t = OutputFormat::Table)]
+    format: OutputFormat,
+  },
+  /// List the tables of the public schema
+  Tables,
+  /// Score text to SQL against a TOML file of questions with gold SQL
+  Eval {
+    cases: PathBuf,
+    /// LLM profiles to compare, comma separated; defaults to `--llm` or the active one
+    #[arg(long, value_delimiter = ',')]
+    llm_profiles: Vec<String>,
+  },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum OutputFormat {
+  Table,
+  Csv,
+  Json,
+}
+
+/// Makes the profiles given with `--profile` and `--llm` the active ones of `setting`, for this
+/// run only.
+pub fn select_profiles(cli: &Cli, setting: &mut Setting) -> anyhow::Result<()> {
+  if let Some(profile) = &cli.profile {
+    if !setting.postgres_profiles.contains_key(profile) {
+      return Err(anyhow!("No Postgres profile named '{profile}'"));
+    }
+    setting.active_postgres = profile.clone();
+  }
+  if let Some(profile) = &cli.llm {
+    if !setting.llm_profiles.contains_key(profile) {
+      return Err(anyhow!("No LLM profile named '{profile}'"));
+    }
+    setting.active_llm = profile.clone();
+  }
+  Ok(())
+}
+
+/// Runs a subcommand against the selected profiles. Results go to stdout, everything else to
+/// stderr. The API key is only resolved for the subcommands that use the LLM.
+pub async fn run(cli: Cli) -> anyhow::Result<()> {
+  let mut setting = Setting::try_load().context("loading config.toml")?;
+  select_profiles(&cli, &mut setting)?;
+  let Some(command) = cli.command else {
+    return Err(anyhow!("No command given"));
+  };
+  let profile = setting.active_postgres.clone();
+  let pg_config = setting
+    .postgres_profiles
+    .get(&profile)
+    .cloned()
+    .ok_or_else(|| anyhow!("No Postgres profile named '{profile}'"))?;
+  let llm_profile = setting.active_llm.clone();
+
+  let agent = Agent {
+    db_client: DbClient::new(),
+    llm_client: RwLock::new(None),
+    glossary: RwLock::new(Glossary::load(Some(&profile)).unwrap_or_default()),
+    active_profile: RwLock::new(Some(profile)),
+  };
+  agent.db_client.setup_db_client(Some(pg_config)).await?;
+
+  match command {
+    Command::Ask { question, run, format } => {
+      let config = setting
+        .llm_profiles
+        .get(&llm_profile)
+        .ok_or_else(|| anyhow!("No LLM profile named '{llm_profile}'"))?
+        .resolve_secrets()?;
+      *agent.llm_client.write().await = Some(config);
+      let sql = agent.text_to_sql(&question, &mut Conversation::new(), &mut ()).await?;
+      if run {
+        eprintln!("{sql}");
+        exec_statements(&agent.db_client, &sql, format, setting.execution.stop_on_error).await
+      } else {
+        println!("{sql}");
+        Ok(())
+      }
+    }
+    Command::Exec { file, format } => {
+      let sql =
+        std::fs::read_to_string(&file).with_context(|| format!("reading {}", file.display()))?;
+      exec_statements(&agent.db_client, &sql, format, setting.execution.stop_on_error).await
+    }
+    Command::Eval { cases, llm_profiles } => {
+      let cases = load_cases(&cases)?;
+      let profiles = if llm_profiles.is_empty() { vec![llm_profile] } else { llm_profiles };
+      for profile in profiles {
+        let config = setting
+          .llm_profiles
+          .get(&profile)
+          .ok_or_else(|| anyhow!("No LLM profile named '{profile}'"))?
+          .resolve_secrets()?;
+        *agent.llm_client.write().await = Some(config);
+        print!("{}", evaluate(&agent, &profile, &cases).await.to_text());
+      }
+      Ok(())
+    }
+    Command::Tables => {
+      let mut tables = agent.db_client.list_tables().await?;
+      tables.sort();
+      for table in tables {
+        println!("{table}");
+      }
+      Ok(())
+    }
+  }
+}
+
+/// Runs each statement on its own, printing results and server notices as they come.
+async fn exec_statements(
+  db_client: &DbClient,
+  sql: &str,
+  format: OutputFormat,
+  stop_on_error: bool,
+) -> anyhow::Result<()> {
+  let mut failed = 0;
+  for (i, statement) in split_statements(sql).iter().enumerate() {
+    match db_client.execute(&statement.text).await {
+      Ok(execution) => {
+        for notice in execution.notices {
+          eprintln!("{notice}");
+        }
+        for set in execution.result_sets {
+          if set.returns_rows() {
+            print!("{}", format_result(&set, format));
+          } else if let Some(status) = set.status() {
+            eprintln!("{status}");
+          }
+        }
+      }
+      Err(e) => {
+        failed += 1;
+        eprintln!("error in statement {}: {e}", i + 1);
+        if stop_on_error {
+          break;
+        }
+      }
+    }
+  }
+  if failed > 0 { Err(anyhow!("{failed} statement(s) failed")) } else { Ok(()) }
+}
+
+fn csv_field(value: &str) -> String {
+  if value.contains([',', '"', '\n', '\r']) {
+    format!("\"{}\"", value.replace('"', "\"\""))
+  } else {
+    value.to_string()
+  }
+}
+
+/// Renders rows as an aligned text table, CSV with a header line, or a JSON array of objects.
+/// NULL is an empty CSV field, `null` in JSON and `NULL` in a table.
+pub fn format_result(set: &ResultSet, format: OutputFormat) -> String {
+  match format {
+    OutputFormat::Csv => {
+      let mut out = set.columns.iter().map(|c| csv_field(c)).collect::<Vec<_>>().join(",");
+      out.push('\n');
+      for row in &set.rows {
+        let fields: Vec<String> =
+          row.iter().map(|v| v.as_deref().map(csv_field).unwrap_or_default()).collect();
+        out.push_str(&fields.join(","));
+        out.push('\n');
+      }
+      out
+    }
+    OutputFormat::Json => {
+      let rows: Vec<serde_json::Map<String, serde_json::Value>> = set
+        .rows
+        .iter()
+        .map(|row| {
+          set
+            .columns
+            .iter()
+            .zip(row)
+            .map(|(column, value)| {
+              let value = value.clone().map(serde_json::Value::String).unwrap_or_default();
+              (column.clone(), value)
+            })
+            .collect()
+        })
+        .collect();
+      format!("{}\n", serde_json::to_string_pretty(&rows).unwrap_or_default())
+    }
+    OutputFormat::Table => {
+      let cells: Vec<Vec<&str>> = set
+        .rows
+        .iter()
+        .map(|row| row.iter().map(|v| v.as_deref().unwrap_or("NULL")).collect())
+        .collect();
+      let widths: Vec<usize> = set
+        .columns
+        .iter()
+        .enumerate()
+        .map(|(i, column)| {
+          cells
+            .iter()
+            .map(|row| row[i].chars().count())
+            .chain([column.chars().count()])
+            .max()
+            .unwrap_or(0)
+        })
+        .collect();
+      let line = |values: Vec<&str>| {
+        values
+          .iter()
+          .zip(&widths)
+          .map(|(value, width)| format!("{value:<width$}"))
+          .collect::<Vec<_>>()
+          .join(" | ")
+          .trim_end()
+          .to_string()
+      };
+      let mut out = line(set.columns.iter().map(String::as_str).collect());
+      out.push('\n');
+      out.push_str(&widths.iter().map(|w| "-".repeat(*w)).collect::<Vec<_>>().join("-+-"));
+      out.push('\n');
+      for row in cells {
+        out.push_str(&line(row));
+        out.push('\n');
+      }
+      out.push_str(&format!("({} rows)\n", set.rows.len()));
+      out
+    }
+  }
+}
+
+#[test]
+fn test_cli_args_and_formats() {
+  let cli = Cli::parse_from([
+    "pg-admin",
+    "ask",
+    "top 5 customers by spend",
+    "--profile",
+    "local",
+    "--run",
+    "--format",
+    "csv",
+  ]);
+  assert_eq!(cli.profile.as_deref(), Some("local"));
+  assert!(matches!(cli.command, Some(Command::Ask { run: true, format: OutputFormat::Csv, .. })));
+  assert!(Cli::parse_from(["pg-admin"]).command.is_none());
+
+  let mut setting: Setting = toml::from_str(
+    "active_postgres = 'local'\nactive_llm = 'default'\nllm_profiles = {}\n\
+     [postgres_profiles.local]\n[postgres_profiles.replica]\n",
+  )
+  .unwrap();
+  select_profiles(&Cli::parse_from(["pg-admin", "--profile", "replica"]), &mut setting).unwrap();
+  assert_eq!(
+    (setting.active_postgres.as_str(), setting.active_llm.as_str()),
+    ("replica", "default")
+  );
+  assert!(
+    select_profiles(&Cli::parse_from(["pg-admin", "--llm", "missing"]), &mut setting).is_err()
+  );
+
+  let set = ResultSet {
+    columns: vec!["name".into(), "note".into()],
+    rows: vec![
+      vec![Some("Ann".into()), Some("says \"hi\", twice".into())],
+      vec![Some("Bob".into()), None],
+    ],
+    row_count: None,
+  };
+  assert_eq!(
+    format_result(&set, OutputFormat::Csv),
+    "name,note\nAnn,\"says \"\"hi\"\", twice\"\nBob,\n"
+  );
+  let json: serde_json::Value =
+    serde_json::from_str(&format_result(&set, OutputFormat::Json)).unwrap();
+  assert_eq!(json[1]["name"], "Bob");
+  assert!(json[1]["note"].is_null());
+  assert_eq!(
+    format_result(&set, OutputFormat::Table),
+    "name | note\n-----+-----------------\nAnn  | says \"hi\", twice\nBob  | NULL\n(2 rows)\n"
+  );
+}
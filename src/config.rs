@@ -1,5 +1,11 @@
+use crate::conninfo::{self, parse_uri, quote};
+use crate::secrets::{SecretSource, pgpass_password};
+use anyhow::{Context, anyhow};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use toml_edit::{DocumentMut, Item, Table, value};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Setting {
@@ -7,33 +13,272 @@ pub struct Setting {
   pub llm_profiles: std::collections::HashMap<String, LlmConfig>,
   pub active_postgres: String,
   pub active_llm: String,
+  #[serde(default)]
+  pub execution: ExecutionConfig,
 }
 
+/// How scripts with several statements are run.
 #[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ExecutionConfig {
+  /// Stop at the first failing statement instead of running the rest
+  #[serde(default = "default_stop_on_error")]
+  pub stop_on_error: bool,
+}
+
+fn default_stop_on_error() -> bool {
+  true
+}
+
+impl Default for ExecutionConfig {
+  fn default() -> Self {
+    Self { stop_on_error: default_stop_on_error() }
+  }
+}
+
+/// A Postgres profile. Fields left empty (port 0) are taken from `url`, then `service`, then the
+/// `PG*` environment variables, then libpq's defaults; see [`PostgresConfig::resolved`].
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq)]
 pub struct PostgresConfig {
+  /// Host name, or the directory of a Unix socket
+  #[serde(default)]
   pub host: String,
+  #[serde(default)]
   pub port: u16,
+  #[serde(default)]
   pub user: String,
+  /// Leave empty to use `password_from` or `~/.pgpass`
+  #[serde(default)]
   pub password: String,
+  #[serde(default)]
   pub dbname: String,
+  /// `postgresql://` connection URI
+  #[serde(default)]
+  pub url: Option<String>,
+  /// Entry of `pg_service.conf`
+  #[serde(default)]
+  pub service: Option<String>,
+  #[serde(default)]
+  pub password_from: Option<SecretSource>,
 }
 
 impl PostgresConfig {
+  /// `key=value` connection string with every value quoted. An empty password is left out.
   pub fn as_str(&self) -> String {
-    let config_str = format!(
-      "host={} user={} password={} dbname={} port={}",
-      self.host, self.user, self.password, self.dbname, self.port
-    );
+    let port = self.port.to_string();
+    let mut params = vec![("host", self.host.as_str()), ("user", &self.user)];
+    if !self.password.is_empty() {
+      params.push(("password", &self.password));
+    }
+    params.extend([("dbname", self.dbname.as_str()), ("port", &port)]);
+    params
+      .iter()
+      .map(|(key, value)| format!("{key}={}", quote(value)))
+      .collect::<Vec<_>>()
+      .join(" ")
+  }
+
+  /// Fills the fields that are not set from `other`.
+  fn fill_from(&mut self, other: PostgresConfig) {
+    for (field, value) in [
+      (&mut self.host, other.host),
+      (&mut self.user, other.user),
+      (&mut self.password, other.password),
+      (&mut self.dbname, other.dbname),
+    ] {
+      if field.is_empty() {
+        *field = value;
+      }
+    }
+    if self.port == 0 {
+      self.port = other.port;
+    }
+  }
+
+  /// Copy with every connection setting filled in and the password resolved, ready to connect.
+  pub fn resolved(&self) -> anyhow::Result<Self> {
+    let mut config = self.clone();
+    if let Some(url) = &self.url {
+      config.fill_from(parse_uri(url).context("invalid connection URL")?);
+    }
+    let service = self.service.clone().or_else(|| std::env::var("PGSERVICE").ok());
+    if let Some(name) = service.filter(|name| !name.is_empty()) {
+      config.fill_from(conninfo::service(&name)?);
+    }
+    let mut env = conninfo::from_env(|name| std::env::var(name).ok())?;
+    if self.password_from.is_some() {
+      // an explicit source beats PGPASSWORD
+      env.password.clear();
+    }
+    config.fill_from(env);
+
+    if config.host.is_empty() {
+      config.host = "localhost".into();
+    }
+    if config.port == 0 {
+      config.port = 5432;
+    }
+    if config.user.is_empty() {
+      config.user = std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_else(|_| "postgres".into());
+    }
+    if config.dbname.is_empty() {
+      config.dbname = config.user.clone();
+    }
+    config.resolve_secrets()
+  }
 
-    config_str
+  /// Connection URL without the password, for display and logs.
+  pub fn redacted_url(&self) -> String {
+    let host = self.host.replace('/', "%2F");
+    format!("postgresql://{}@{}:{}/{}", self.user, host, self.port, self.dbname)
+  }
+
+  /// Copy with the password filled in: the configured one, else from `password_from`, else from
+  /// the libpq password file.
+  pub fn resolve_secrets(&self) -> anyhow::Result<Self> {
+    let mut resolved = self.clone();
+    if !self.password.is_empty() {
+      return Ok(resolved);
+    }
+    if let Some(source) = &self.password_from {
+      resolved.password =
+        source.resolve().with_context(|| format!("password of {}", self.redacted_url()))?;
+    } else if let Some(password) = pgpass_password(&self.host, self.port, &self.dbname, &self.user)
+    {
+      resolved.password = password;
+    }
+    Ok(resolved)
   }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+impl std::fmt::Debug for PostgresConfig {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("PostgresConfig")
+      .field("host", &self.host)
+      .field("port", &self.port)
+      .field("user", &self.user)
+      .field("password", &redacted(&self.password))
+      .field("dbname", &self.dbname)
+      .field("url", &self.url.as_deref().map(redacted_uri))
+      .field("service", &self.service)
+      .field("password_from", &self.password_from)
+      .finish()
+  }
+}
+
+/// `uri` with the password of its user info and of a `password` parameter masked.
+fn redacted_uri(uri: &str) -> String {
+  let (rest, query) = uri.split_once('?').unwrap_or((uri, ""));
+  let (scheme, rest) = rest.split_once("://").unwrap_or(("", rest));
+  let (authority, path) = rest.split_once('/').map_or((rest, ""), |(a, p)| (a, p));
+  let authority = match authority.rsplit_once('@') {
+    Some((userinfo, host)) if userinfo.contains(':') => {
+      let user = userinfo.split(':').next().unwrap_or_default();
+      format!("{user}:***@{host}")
+    }
+    _ => authority.to_string(),
+  };
+  let mut masked = format!("{scheme}://{authority}");
+  if rest.contains('/') {
+    masked = format!("{masked}/{path}");
+  }
+  if !query.is_empty() {
+    let params: Vec<_> = query
+      .split('&')
+      .map(|pair| if pair.starts_with("password=") { "password=***" } else { pair })
+      .collect();
+    masked = format!("{masked}?{}", params.join("&"));
+  }
+  masked
+}
+
+fn redacted(secret: &str) -> &'static str {
+  if secret.is_empty() { "" } else { "***" }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
 pub struct LlmConfig {
   pub api_url: String,
-  //pub api_key: String,
+  /// Sent as a bearer token; local servers usually need none
+  #[serde(default)]
+  pub api_key: Option<String>,
+  #[serde(default)]
+  pub api_key_from: Option<SecretSource>,
   pub model: String,
+  #[serde(default)]
+  pub temperature: f64,
+  /// Most result rows sent to the LLM when summarizing a result
+  #[serde(default = "default_summary_rows")]
+  pub summary_rows: usize,
+  /// Token cap for the result sample sent when summarizing a result
+  #[serde(default = "default_summary_tokens")]
+  pub summary_tokens: usize,
+  /// Context window of the model in tokens; older chat turns are pruned to stay within it
+  #[serde(default = "default_context_tokens")]
+  pub context_tokens: usize,
+  /// Append every LLM request and reply to this fixture file, see `fixtures::Fixture`
+  #[serde(default)]
+  pub record_to: Option<PathBuf>,
+}
+
+fn default_summary_rows() -> usize {
+  50
+}
+
+fn default_summary_tokens() -> usize {
+  2000
+}
+
+fn default_context_tokens() -> usize {
+  8192
+}
+
+impl LlmConfig {
+  /// Profile with default limits.
+  pub fn new(api_url: &str, model: &str) -> Self {
+    Self {
+      api_url: api_url.into(),
+      api_key: None,
+      api_key_from: None,
+      model: model.into(),
+      temperature: 0.0,
+      summary_rows: default_summary_rows(),
+      summary_tokens: default_summary_tokens(),
+      context_tokens: default_context_tokens(),
+      record_to: None,
+    }
+  }
+
+  /// Tokens the request messages may use, leaving a quarter of the context for the reply.
+  pub fn prompt_budget(&self) -> usize {
+    self.context_tokens - self.context_tokens / 4
+  }
+
+  /// Copy with `api_key` filled in from `api_key_from` when it is not set.
+  pub fn resolve_secrets(&self) -> anyhow::Result<Self> {
+    let mut resolved = self.clone();
+    if let (None, Some(source)) = (&self.api_key, &self.api_key_from) {
+      resolved.api_key = Some(source.resolve().context("API key of the LLM profile")?);
+    }
+    Ok(resolved)
+  }
+}
+
+impl std::fmt::Debug for LlmConfig {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("LlmConfig")
+      .field("api_url", &self.api_url)
+      .field("api_key", &self.api_key.as_deref().map(redacted))
+      .field("api_key_from", &self.api_key_from)
+      .field("model", &self.model)
+      .field("temperature", &self.temperature)
+      .field("summary_rows", &self.summary_rows)
+      .field("summary_tokens", &self.summary_tokens)
+      .field("context_tokens", &self.context_tokens)
+      .field("record_to", &self.record_to)
+      .finish()
+  }
 }
 
 impl Setting {
@@ -43,6 +288,13 @@ impl Setting {
     path
   }
 
+  /// Path of an app data file stored next to `config.toml`.
+  pub fn data_path(file_name: &str) -> PathBuf {
+    let mut path = Self::config_path();
+    path.set_file_name(file_name);
+    path
+  }
+
   pub fn try_load() -> Result<Self, config::ConfigError> {
     let builder = config::Config::builder()
       // set built-in defaults
@@ -73,3 +325,272 @@ impl Setting {
     self.llm_profiles.get(&self.active_llm)
   }
 }
+
+/// Sets `key`, or removes it when `text` is empty so the secret can come from elsewhere.
+fn set_or_remove(profile: &mut Item, key: &str, text: &str) {
+  if !text.is_empty() {
+    profile[key] = value(text);
+  } else if let Some(table) = profile.as_table_like_mut() {
+    table.remove(key);
+  }
+}
+
+/// `config.toml` opened for editing. Changes keep the comments and layout of the file.
+#[derive(Debug, Clone)]
+pub struct ConfigDocument {
+  path: PathBuf,
+  doc: DocumentMut,
+}
+
+impl ConfigDocument {
+  /// Opens the file at `path`; a missing file is an empty document.
+  pub fn load(path: &Path) -> anyhow::Result<Self> {
+    let raw = match std::fs::read_to_string(path) {
+      Ok(raw) => raw,
+      Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+      Err(e) => return Err(e).with_context(|| format!("failed to read {}", path.display())),
+    };
+    let doc = raw.parse().with_context(|| format!("invalid TOML in {}", path.display()))?;
+    Ok(Self { path: path.to_path_buf(), doc })
+  }
+
+  pub fn save(&self) -> anyhow::Result<()> {
+    std::fs::write(&self.path, self.doc.to_string())
+      .with_context(|| format!("failed to write {}", self.path.display()))
+  }
+
+  /// Each profile of `section` parsed on its own, so one bad entry doesn't hide the others.
+  fn profiles<T: DeserializeOwned>(&self, section: &str) -> BTreeMap<String, Result<T, String>> {
+    // the document already parsed as TOML, so only the profile tables can fail here
+    let Ok(toml::Value::Table(mut root)) = toml::from_str::<toml::Value>(&self.doc.to_string())
+    else {
+      return BTreeMap::new();
+    };
+    let Some(toml::Value::Table(profiles)) = root.remove(section) else {
+      return BTreeMap::new();
+    };
+    profiles
+      .into_iter()
+      .map(|(name, profile)| {
+        let parsed = profile.try_into().map_err(|e| format!("{section}.{name}: {}", e.message()));
+        (name, parsed)
+      })
+      .collect()
+  }
+
+  fn active(&self, key: &str) -> String {
+    self.doc.get(key).and_then(Item::as_str).unwrap_or_default().to_string()
+  }
+
+  /// Why profiles are missing from `postgres_profiles` and `llm_profiles`, one line per entry.
+  pub fn profile_errors(&self) -> Vec<String> {
+    let postgres = self.profiles::<PostgresConfig>("postgres_profiles").into_values();
+    let llm = self.profiles::<LlmConfig>("llm_profiles").into_values();
+    postgres.filter_map(Result::err).chain(llm.filter_map(Result::err)).collect()
+  }
+
+  /// Postgres profiles by name; entries that don't parse are left out, see `profile_errors`.
+  pub fn postgres_profiles(&self) -> BTreeMap<String, PostgresConfig> {
+    let profiles = self.profiles("postgres_profiles").into_iter();
+    profiles.filter_map(|(name, profile)| Some((name, profile.ok()?))).collect()
+  }
+
+  pub fn active_postgres(&self) -> String {
+    self.active("active_postgres")
+  }
+
+  /// Table of profile `name` in `section`, created when missing.
+  fn profile_mut(&mut self, section: &str, name: &str) -> &mut Item {
+    let profiles = self.doc.entry(section).or_insert_with(|| {
+      let mut table = Table::new();
+      table.set_implicit(true);
+      Item::Table(table)
+    });
+    let profile = &mut profiles[name];
+    if !profile.is_table() {
+      *profile = Item::Table(Table::new());
+    }
+    profile
+  }
+
+  fn remove_profile(&mut self, section: &str, name: &str, active: &str) -> anyhow::Result<()> {
+    if active == name {
+      return Err(anyhow!("'{name}' is the active profile"));
+    }
+    self
+      .doc
+      .get_mut(section)
+      .and_then(Item::as_table_like_mut)
+      .and_then(|profiles| profiles.remove(name))
+      .map(|_| ())
+      .ok_or_else(|| anyhow!("No profile named '{name}'"))
+  }
+
+  /// Adds or updates a profile. Keys the form does not know about are kept.
+  pub fn set_postgres_profile(&mut self, name: &str, config: &PostgresConfig) {
+    let profile = self.profile_mut("postgres_profiles", name);
+    set_or_remove(profile, "url", config.url.as_deref().unwrap_or_default());
+    set_or_remove(profile, "service", config.service.as_deref().unwrap_or_default());
+    set_or_remove(profile, "host", &config.host);
+    if config.port == 0 {
+      set_or_remove(profile, "port", "");
+    } else {
+      profile["port"] = value(i64::from(config.port));
+    }
+    set_or_remove(profile, "user", &config.user);
+    set_or_remove(profile, "password", &config.password);
+    set_or_remove(profile, "dbname", &config.dbname);
+  }
+
+  /// Removes a profile other than the active one.
+  pub fn remove_postgres_profile(&mut self, name: &str) -> anyhow::Result<()> {
+    let active = self.active_postgres();
+    self.remove_profile("postgres_profiles", name, &active)
+  }
+
+  pub fn set_active_postgres(&mut self, name: &str) {
+    self.doc["active_postgres"] = value(name);
+  }
+
+  /// LLM profiles by name; entries that don't parse are left out, see `profile_errors`.
+  pub fn llm_profiles(&self) -> BTreeMap<String, LlmConfig> {
+    let profiles = self.profiles("llm_profiles").into_iter();
+    profiles.filter_map(|(name, profile)| Some((name, profile.ok()?))).collect()
+  }
+
+  pub fn active_llm(&self) -> String {
+    self.active("active_llm")
+  }
+
+  /// Adds or updates the fields the LLM profile editor shows; limits and `record_to` are kept.
+  pub fn set_llm_profile(&mut self, name: &str, config: &LlmConfig) {
+    let profile = self.profile_mut("llm_profiles", name);
+    profile["api_url"] = value(&config.api_url);
+    set_or_remove(profile, "api_key", config.api_key.as_deref().unwrap_or_default());
+    profile["model"] = value(&config.model);
+    profile["temperature"] = value(config.temperature);
+    profile["context_tokens"] = value(config.context_tokens as i64);
+  }
+
+  pub fn remove_llm_profile(&mut self, name: &str) -> anyhow::Result<()> {
+    let active = self.active_llm();
+    self.remove_profile("llm_profiles", name, &active)
+  }
+
+  pub fn set_active_llm(&mut self, name: &str) {
+    self.doc["active_llm"] = value(name);
+  }
+}
+
+#[test]
+fn test_config_document_keeps_comments() {
+  let path = std::env::temp_dir().join(format!("pg_admin_config_{}.toml", std::process::id()));
+  std::fs::write(
+    &path,
+    r#"# which profile to use
+active_postgres = "local"
+active_llm = "default"
+
+[postgres_profiles.local]
+host = "localhost" # the dev container
+port = 5432
+user = "postgres"
+password = "password"
+dbname = "postgres"
+
+[llm_profiles.default]
+api_url = "http://localhost:11434/v1"
+model = "llama3"
+"#,
+  )
+  .unwrap();
+
+  let mut doc = ConfigDocument::load(&path).unwrap();
+  let mut staging = doc.postgres_profiles()["local"].clone();
+  staging.host = "staging.internal".into();
+  doc.set_postgres_profile("staging", &staging);
+  doc.set_active_postgres("staging");
+  assert!(doc.remove_postgres_profile("staging").is_err());
+  doc.save().unwrap();
+
+  let raw = std::fs::read_to_string(&path).unwrap();
+  assert!(raw.starts_with("# which profile to use\n"));
+  assert!(raw.contains("host = \"localhost\" # the dev container"));
+  let doc = ConfigDocument::load(&path).unwrap();
+  assert_eq!(doc.active_postgres(), "staging");
+  assert_eq!(doc.postgres_profiles()["staging"], staging);
+
+  let mut doc = doc;
+  doc.remove_postgres_profile("local").unwrap();
+  assert_eq!(doc.postgres_profiles().len(), 1);
+  assert!(doc.doc.to_string().contains("[llm_profiles.default]"));
+
+  let mut llm = doc.llm_profiles()["default"].clone();
+  assert_eq!(llm.context_tokens, 8192);
+  llm.api_key = Some("sk-test".into());
+  llm.temperature = 0.5;
+  doc.set_llm_profile("default", &llm);
+  doc.set_active_llm("default");
+  assert_eq!(doc.llm_profiles()["default"], llm);
+  assert!(doc.remove_llm_profile("default").is_err());
+
+  // a bad entry hides only itself and the active profile stays protected
+  let broken = "[postgres_profiles.broken]\nport = \"x\"\n";
+  std::fs::write(&path, format!("{}{broken}", doc.doc)).unwrap();
+  let mut doc = ConfigDocument::load(&path).unwrap();
+  assert_eq!(doc.postgres_profiles().keys().collect::<Vec<_>>(), ["staging"]);
+  assert_eq!(doc.profile_errors().len(), 1);
+  assert!(doc.profile_errors()[0].starts_with("postgres_profiles.broken: "));
+  assert!(doc.remove_postgres_profile("staging").is_err());
+  let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_secrets_are_redacted_and_resolved() {
+  let config: PostgresConfig = toml::from_str(
+    r#"
+    host = "db.internal"
+    port = 5432
+    user = "report"
+    dbname = "sales"
+    password_from = { command = "echo from-command" }
+    "#,
+  )
+  .unwrap();
+  assert_eq!(config.redacted_url(), "postgresql://report@db.internal:5432/sales");
+  let resolved = config.resolve_secrets().unwrap();
+  assert_eq!(resolved.password, "from-command");
+  assert!(format!("{resolved:?}").contains(r#"password: "***""#));
+
+  let mut llm = LlmConfig::new("https://api.example.com/v1", "gpt-4o");
+  llm.api_key_from = Some(SecretSource::Command("echo sk-live".into()));
+  let llm = llm.resolve_secrets().unwrap();
+  assert_eq!(llm.api_key.as_deref(), Some("sk-live"));
+  assert!(format!("{llm:?}").contains(r#"api_key: Some("***")"#));
+}
+
+#[test]
+fn test_connection_url_and_quoting() {
+  let config: PostgresConfig = toml::from_str(
+    r#"url = "postgresql://report:it's%20secret@%2Fvar%2Frun%2Fpostgresql:5433/sales?password=x""#,
+  )
+  .unwrap();
+  let debug = format!("{config:?}");
+  assert!(!debug.contains("secret") && debug.contains("report:***@"));
+  assert!(debug.contains("password=***"));
+
+  let config =
+    PostgresConfig { url: config.url.map(|url| url.replace("?password=x", "")), ..config };
+  let resolved = config.resolved().unwrap();
+  assert_eq!(resolved.host, "/var/run/postgresql");
+  assert_eq!((resolved.port, resolved.dbname.as_str()), (5433, "sales"));
+  assert_eq!(resolved.redacted_url(), "postgresql://report@%2Fvar%2Frun%2Fpostgresql:5433/sales");
+  assert_eq!(
+    resolved.as_str(),
+    r"host='/var/run/postgresql' user='report' password='it\'s secret' dbname='sales' port='5433'"
+  );
+
+  // explicit fields win over the URL
+  let explicit = PostgresConfig { dbname: "hr".into(), ..config };
+  assert_eq!(explicit.resolved().unwrap().dbname, "hr");
+}
@@ -1,12 +1,23 @@
+use crate::history::HistoryKind;
+use crate::llm::estimate_tokens;
+use crate::trace::{Trace, TraceEvent};
+use chrono::{DateTime, Local};
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct ChatMessage {
   pub role: String,
   pub content: String,
 }
 
+impl ChatMessage {
+  /// Rough token count, including a few tokens of per-message framing.
+  pub fn estimated_tokens(&self) -> usize {
+    estimate_tokens(&self.content) + 4
+  }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct LlmResponse {
   #[serde(default)]
@@ -17,11 +28,55 @@ pub struct LlmResponse {
   pub explanation: String,
 }
 
-#[derive(Debug, Clone)]
+/// Tokens set aside for the note that replaces pruned turns
+const PRUNED_NOTE_TOKENS: usize = 200;
+
+/// Role of messages only shown in the chat, never sent to the LLM
+pub const ANSWER_ROLE: &str = "answer";
+
+/// Start of the follow-up prompt, which is replaced on every question
+pub const FOLLOW_UP_HEADER: &str = "The current query, written for";
+
+/// The last accepted SQL, which follow-up questions edit.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CurrentQuery {
+  /// Question that produced (or last edited) the SQL
+  pub question: String,
+  pub sql: String,
+  /// Result columns, known once the SQL was executed
+  pub columns: Vec<String>,
+}
+
+/// SQL generated or executed during a conversation.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueryRecord {
+  pub kind: HistoryKind,
+  pub timestamp: DateTime<Local>,
+  pub sql: String,
+}
+
+/// Told about every change the agent makes to a conversation, e.g. to redraw a chat view while
+/// a question is being answered.
+pub trait ConversationObserver {
+  fn updated(&mut self, conversation: &Conversation);
+}
+
+/// For callers that only need the conversation once the agent is done.
+impl ConversationObserver for () {
+  fn updated(&mut self, _conversation: &Conversation) {}
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Conversation {
   pub messages: Vec<ChatMessage>,
+  pub current_query: Option<CurrentQuery>,
   /// Tracks what tables have been asked about and what columns we know
   pub known_tables: HashMap<String, HashSet<String>>,
+  /// Generated and executed SQL, oldest first
+  pub queries: Vec<QueryRecord>,
+  /// What the agent did for each question, oldest first
+  pub traces: Vec<Trace>,
 }
 
 impl Default for Conversation {
@@ -32,7 +87,70 @@ impl Default for Conversation {
 
 impl Conversation {
   pub fn new() -> Self {
-    Self { messages: vec![], known_tables: HashMap::new() }
+    Self {
+      messages: vec![],
+      current_query: None,
+      known_tables: HashMap::new(),
+      queries: vec![],
+      traces: vec![],
+    }
+  }
+
+  /// Starts a new question: forgets messages and the current query but keeps learned schema,
+  /// the query log and the traces.
+  pub fn reset(&mut self) {
+    self.messages.clear();
+    self.current_query = None;
+  }
+
+  pub fn has_message(&self, role: &str, content: &str) -> bool {
+    self.messages.iter().any(|m| m.role == role && m.content == content)
+  }
+
+  pub fn set_current_query(&mut self, question: &str, sql: &str) {
+    self.current_query =
+      Some(CurrentQuery { question: question.into(), sql: sql.into(), columns: vec![] });
+  }
+
+  pub fn record_query(&mut self, kind: HistoryKind, sql: &str) {
+    self.queries.push(QueryRecord { kind, timestamp: Local::now(), sql: sql.into() });
+  }
+
+  /// Remembers the result columns when `sql` is the current query.
+  pub fn record_result_columns(&mut self, sql: &str, columns: &[String]) {
+    let normalize = |s: &str| s.trim().trim_end_matches(';').trim().to_string();
+    if let Some(current) =
+      self.current_query.as_mut().filter(|c| normalize(&c.sql) == normalize(sql))
+    {
+      current.columns = columns.to_vec();
+    }
+  }
+
+  /// Instructions to treat the next question as an edit of the current query, if there is one.
+  pub fn follow_up_prompt(&self) -> Option<String> {
+    let current = self.current_query.as_ref()?;
+    let mut prompt = format!("{FOLLOW_UP_HEADER} \"{}\", is:\n{}\n", current.question, current.sql);
+    if !current.columns.is_empty() {
+      prompt.push_str(&format!("Its result columns are: {}.\n", current.columns.join(", ")));
+    }
+    prompt.push_str(
+      "If the next message refines it (for example \"now only for 2024\" or \"group that by \
+       city\"), return the complete edited query in sql, keeping everything not asked to change. \
+       Only start from scratch when the message is clearly a new question.",
+    );
+    Some(prompt)
+  }
+
+  pub fn start_trace(&mut self, question: &str) {
+    self.traces.push(Trace::new(question));
+  }
+
+  /// Adds a step to the trace of the question being answered.
+  pub fn trace(&mut self, event: TraceEvent, latency: std::time::Duration, tokens: usize) {
+    match self.traces.last_mut() {
+      Some(trace) => trace.push(event, latency, tokens),
+      None => tracing::warn!(?event, "trace event without a started trace"),
+    }
   }
 
   pub fn add_user(&mut self, content: &str) {
@@ -43,9 +161,106 @@ impl Conversation {
     self.messages.push(ChatMessage { role: "system".into(), content: content.into() });
   }
 
+  /// Replaces the system message starting with `header`, or removes it when `content` is `None`.
+  ///
+  /// The message joins the leading system messages, so `within_budget` never prunes it.
+  pub fn set_pinned_system(&mut self, header: &str, content: Option<&str>) {
+    self.messages.retain(|m| !(m.role == "system" && m.content.starts_with(header)));
+    if let Some(content) = content {
+      let at = self.messages.iter().take_while(|m| m.role == "system").count();
+      self.messages.insert(at, ChatMessage { role: "system".into(), content: content.into() });
+    }
+  }
+
   pub fn add_assistant(&mut self, content: &str) {
     self.messages.push(ChatMessage { role: "assistant".into(), content: content.into() });
   }
+  /// Adds a written answer about a result; shown in the chat only.
+  pub fn add_answer(&mut self, content: &str) {
+    self.messages.push(ChatMessage { role: ANSWER_ROLE.into(), content: content.into() });
+  }
+
+  /// Messages to send to the LLM.
+  pub fn llm_messages(&self) -> Vec<ChatMessage> {
+    self.messages.iter().filter(|m| m.role != ANSWER_ROLE).cloned().collect()
+  }
+
+  /// Estimated tokens of everything `llm_messages` would send.
+  pub fn estimated_tokens(&self) -> usize {
+    self.llm_messages().iter().map(ChatMessage::estimated_tokens).sum()
+  }
+
+  /// Known table columns as a system message, so they survive pruning.
+  fn schema_message(&self) -> Option<ChatMessage> {
+    if self.known_tables.is_empty() {
+      return None;
+    }
+    let mut tables: Vec<String> = self
+      .known_tables
+      .iter()
+      .map(|(table, columns)| {
+        let mut columns: Vec<&str> = columns.iter().map(String::as_str).collect();
+        columns.sort();
+        format!("{table}({})", columns.join(", "))
+      })
+      .collect();
+    tables.sort();
+    Some(ChatMessage {
+      role: "system".into(),
+      content: format!("Columns learned so far:\n{}", tables.join("\n")),
+    })
+  }
+
+  /// Copy of the conversation whose LLM messages fit in `budget` tokens.
+  ///
+  /// The leading system messages (instructions and the prompts for the current question) and the
+  /// learned schema are always kept. The oldest turns are
+  /// dropped first and replaced by a short note listing the questions they contained, if any were
+  /// dropped; the newest message is kept even when it alone exceeds the budget.
+  pub fn within_budget(&self, budget: usize) -> Conversation {
+    if self.estimated_tokens() <= budget {
+      return self.clone();
+    }
+    let messages = self.llm_messages();
+    let pinned_len = messages.iter().take_while(|m| m.role == "system").count();
+    let (pinned, turns) = messages.split_at(pinned_len);
+    let mut head: Vec<ChatMessage> = pinned.to_vec();
+    head.extend(self.schema_message());
+
+    let mut used: usize =
+      head.iter().map(ChatMessage::estimated_tokens).sum::<usize>() + PRUNED_NOTE_TOKENS;
+    let mut kept = 0;
+    for message in turns.iter().rev() {
+      let tokens = message.estimated_tokens();
+      if kept > 0 && used + tokens > budget {
+        break;
+      }
+      used += tokens;
+      kept += 1;
+    }
+    let (dropped, recent) = turns.split_at(turns.len() - kept);
+
+    if !dropped.is_empty() {
+      let mut note =
+        format!("{} earlier messages were removed to fit the context window.", dropped.len());
+      let questions: Vec<String> = dropped
+        .iter()
+        .filter(|m| m.role == "user")
+        .map(|m| m.content.chars().take(120).collect())
+        .collect();
+      if !questions.is_empty() {
+        note.push_str(" They included these user messages:");
+        for question in questions.iter().rev().take(5).rev() {
+          note.push_str(&format!("\n- {}", question.replace('\n', " ")));
+        }
+      }
+      head.push(ChatMessage { role: "system".into(), content: note });
+    }
+    head.extend(recent.iter().cloned());
+
+    Conversation { messages: head, ..self.clone() }
+  }
+
   /// Update knowledge of a table → columns mapping
   pub fn remember_table_columns(&mut self, table: &str, columns: Vec<String>) {
     self.known_tables.entry(table.to_string()).or_default().extend(columns);
@@ -60,3 +275,76 @@ impl Conversation {
       .unwrap_or(false)
   }
 }
+
+#[test]
+fn test_follow_up_prompt_and_reset() {
+  let mut conversation = Conversation::new();
+  assert!(conversation.follow_up_prompt().is_none());
+
+  conversation.remember_table_columns("orders", vec!["city".into()]);
+  conversation.add_user("revenue per month");
+  conversation
+    .set_current_query("revenue per month", "SELECT month, sum(total) FROM orders GROUP BY 1");
+  conversation.record_result_columns("SELECT 1", &["x".to_string()]);
+  conversation.record_result_columns(
+    "SELECT month, sum(total) FROM orders GROUP BY 1;",
+    &["month".to_string(), "sum".to_string()],
+  );
+  let prompt = conversation.follow_up_prompt().unwrap();
+  assert!(prompt.contains("GROUP BY 1"));
+  assert!(prompt.contains("Its result columns are: month, sum."));
+
+  conversation.reset();
+  assert!(conversation.messages.is_empty());
+  assert!(conversation.current_query.is_none());
+  assert!(conversation.known_tables.contains_key("orders"));
+
+  conversation.add_system("instructions");
+  conversation.set_pinned_system(FOLLOW_UP_HEADER, Some(&prompt));
+  conversation.add_assistant("ok");
+  conversation.set_pinned_system(FOLLOW_UP_HEADER, Some(&prompt));
+  let follow_ups: Vec<usize> = (0..conversation.messages.len())
+    .filter(|&i| conversation.messages[i].content.starts_with(FOLLOW_UP_HEADER))
+    .collect();
+  assert_eq!(follow_ups, vec![1]);
+  assert_eq!(conversation.within_budget(10).messages[1].content, prompt);
+  conversation.set_pinned_system(FOLLOW_UP_HEADER, None);
+  assert!(!conversation.messages.iter().any(|m| m.content.starts_with(FOLLOW_UP_HEADER)));
+}
+
+#[test]
+fn test_within_budget_keeps_pinned_messages() {
+  let mut conversation = Conversation::new();
+  conversation.add_system("instructions");
+  conversation.remember_table_columns("orders", vec!["total".into(), "city".into()]);
+  for i in 0..50 {
+    conversation.add_user(&format!("question {i} {}", "x".repeat(200)));
+    conversation.add_assistant(&"y".repeat(200));
+  }
+  conversation.add_answer(&"z".repeat(10_000));
+  assert!(conversation.estimated_tokens() > 2000);
+  assert_eq!(conversation.within_budget(100_000).messages.len(), conversation.messages.len());
+
+  let pruned = conversation.within_budget(1000);
+  assert!(pruned.estimated_tokens() <= 1000);
+  assert_eq!(pruned.messages[0].content, "instructions");
+  assert_eq!(pruned.messages[1].content, "Columns learned so far:\norders(city, total)");
+  let note = &pruned.messages[2].content;
+  let question =
+    |i: usize| format!("- question {i} {}", "x".repeat(200)).chars().take(122).collect::<String>();
+  let mut lines = note.lines();
+  assert!(lines.next().unwrap().ends_with(
+    "earlier messages were removed to fit the context window. They included these user messages:"
+  ));
+  assert_eq!(lines.collect::<Vec<_>>(), (38..43).map(question).collect::<Vec<_>>());
+  assert_eq!(pruned.messages.last().unwrap().content, "y".repeat(200));
+  assert!(pruned.messages.iter().all(|m| m.role != ANSWER_ROLE));
+  assert_eq!(pruned.known_tables, conversation.known_tables);
+
+  // a single message over the budget is kept, and nothing was dropped to note
+  let mut single = Conversation::new();
+  single.add_user(&"q".repeat(10_000));
+  let kept = single.within_budget(1000);
+  assert_eq!(kept.messages.len(), single.messages.len());
+  assert!(kept.messages.iter().all(|m| !m.content.contains("were removed")));
+}
@@ -0,0 +1,184 @@
+use crate::db_client::{Execution, ResultSet};
+use crate::schema::{BUILTIN_FUNCTIONS, ColumnInfo, SchemaModel, TableInfo};
+use crate::sql::splitter::normalize_statement;
+use anyhow::{Context, anyhow};
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use tracing::debug;
+
+/// What the agent needs from a database: running SQL and looking at the schema. Implemented by
+/// [`crate::db_client::DbClient`] and by [`FakeDatabase`] for tests without a server.
+pub trait Database: Send + Sync {
+  /// Whether connection settings were given.
+  fn is_configured(&self) -> impl Future<Output = bool> + Send;
+
+  fn execute(&self, sql: &str) -> impl Future<Output = anyhow::Result<Execution>> + Send;
+
+  /// Tables of the public schema.
+  fn list_tables(&self) -> impl Future<Output = anyhow::Result<Vec<String>>> + Send;
+
+  /// `(name, data type)` of each column of `table`.
+  fn table_columns(
+    &self,
+    table: &str,
+  ) -> impl Future<Output = anyhow::Result<Vec<(String, String)>>> + Send;
+
+  /// Cached schema model; introspects the database on first use.
+  fn schema_model(&self) -> impl Future<Output = anyhow::Result<Arc<SchemaModel>>> + Send;
+
+  /// Answers an LLM clarification: the table list, or the columns of every quoted table.
+  fn fetch_info(&self, query_string: &str) -> impl Future<Output = anyhow::Result<String>> + Send {
+    async move {
+      if query_string.eq_ignore_ascii_case("list all available tables") {
+        let tables = self.list_tables().await.context("failed to list tables")?;
+        debug!("Fetched list of tables {:?}", tables);
+        return Ok(format!("Available tables: {tables:?}"));
+      }
+
+      // extract all single-quoted names: " 'a' and 'b' " -> ["a","b"]
+      let quoted: Vec<&str> = query_string
+        .split('\'')
+        .skip(1)
+        .step_by(2)
+        .map(str::trim)
+        .filter(|candidate| !candidate.is_empty())
+        .collect();
+      if quoted.is_empty() {
+        // If nothing matched we give a clear error including the original clarification.
+        return Err(anyhow!("I cannot resolve clarification: {}", query_string));
+      }
+
+      let mut outputs: Vec<String> = Vec::with_capacity(quoted.len());
+      for table in quoted {
+        let cols: Vec<String> = self
+          .table_columns(table)
+          .await
+          .with_context(|| format!("error querying columns of table '{table}'"))?
+          .into_iter()
+          .map(|(name, data_type)| format!("{name}:{data_type}"))
+          .collect();
+        debug!("Fetched table info of {}", table);
+        outputs.push(format!("Table {table} has columns: {cols:?}"));
+      }
+      Ok(outputs.join("\n"))
+    }
+  }
+}
+
+/// In-memory database with a declared schema and fixed results per statement.
+#[derive(Debug, Default)]
+pub struct FakeDatabase {
+  /// Public tables and their `(name, data type)` columns
+  tables: BTreeMap<String, Vec<(String, String)>>,
+  results: HashMap<String, ResultSet>,
+  /// Statements run so far, as given
+  executed: Mutex<Vec<String>>,
+  unconfigured: bool,
+}
+
+impl FakeDatabase {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// A database without connection settings, as before a profile is chosen.
+  pub fn unconfigured() -> Self {
+    Self { unconfigured: true, ..Self::default() }
+  }
+
+  pub fn table(mut self, name: &str, columns: &[(&str, &str)]) -> Self {
+    let columns = columns.iter().map(|(n, t)| (n.to_string(), t.to_string())).collect();
+    self.tables.insert(name.into(), columns);
+    self
+  }
+
+  /// Rows returned by `sql`, none of them NULL; use [`FakeDatabase::result`] for anything else.
+  pub fn rows(self, sql: &str, columns: &[&str], rows: &[&[&str]]) -> Self {
+    let result = ResultSet {
+      columns: columns.iter().map(|c| c.to_string()).collect(),
+      rows: rows.iter().map(|row| row.iter().map(|v| Some(v.to_string())).collect()).collect(),
+      row_count: Some(rows.len() as u64),
+    };
+    self.result(sql, result)
+  }
+
+  pub fn result(mut self, sql: &str, result: ResultSet) -> Self {
+    self.results.insert(normalize_statement(sql), result);
+    self
+  }
+
+  pub fn executed(&self) -> Vec<String> {
+    self.executed.lock().unwrap().clone()
+  }
+}
+
+impl Database for FakeDatabase {
+  async fn is_configured(&self) -> bool {
+    !self.unconfigured
+  }
+
+  /// Returns the fixed result of `sql`; statements without one fail like an unknown relation.
+  async fn execute(&self, sql: &str) -> anyhow::Result<Execution> {
+    self.executed.lock().unwrap().push(sql.to_string());
+    let result = self
+      .results
+      .get(&normalize_statement(sql))
+      .cloned()
+      .ok_or_else(|| anyhow!("no fixed result for statement: {sql}"))?;
+    Ok(Execution { result_sets: vec![result], notices: vec![] })
+  }
+
+  async fn list_tables(&self) -> anyhow::Result<Vec<String>> {
+    Ok(self.tables.keys().cloned().collect())
+  }
+
+  async fn table_columns(&self, table: &str) -> anyhow::Result<Vec<(String, String)>> {
+    // like information_schema, an unknown table has no columns
+    Ok(self.tables.get(table).cloned().unwrap_or_default())
+  }
+
+  async fn schema_model(&self) -> anyhow::Result<Arc<SchemaModel>> {
+    let tables = self
+      .tables
+      .iter()
+      .map(|(name, columns)| TableInfo {
+        schema: "public".into(),
+        name: name.clone(),
+        columns: columns
+          .iter()
+          .map(|(name, data_type)| ColumnInfo { name: name.clone(), data_type: data_type.clone() })
+          .collect(),
+      })
+      .collect();
+    Ok(Arc::new(SchemaModel {
+      tables,
+      foreign_keys: vec![],
+      functions: BUILTIN_FUNCTIONS.iter().map(|f| f.to_string()).collect(),
+    }))
+  }
+}
+
+#[tokio::test]
+async fn test_fake_database() {
+  let db = FakeDatabase::new()
+    .table("users", &[("id", "integer"), ("full_name", "character varying")])
+    .table("orders", &[("id", "integer")])
+    .rows("SELECT full_name FROM users", &["full_name"], &[&["Ann"], &["Bob"]]);
+
+  assert_eq!(
+    db.fetch_info("list all available tables").await.unwrap(),
+    r#"Available tables: ["orders", "users"]"#
+  );
+  assert_eq!(
+    db.fetch_info("What are the columns in 'users'?").await.unwrap(),
+    r#"Table users has columns: ["id:integer", "full_name:character varying"]"#
+  );
+  assert!(db.fetch_info("What do you mean?").await.is_err());
+
+  let execution = db.execute("SELECT full_name\n  FROM users;").await.unwrap();
+  assert_eq!(execution.result_sets[0].rows[1], vec![Some("Bob".to_string())]);
+  assert!(db.execute("SELECT * FROM missing").await.is_err());
+  assert_eq!(db.executed().len(), 2);
+  assert!(db.schema_model().await.unwrap().table("users").is_some());
+}
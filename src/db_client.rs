@@ -1,14 +1,199 @@
 use crate::config::PostgresConfig;
+use crate::database::Database;
+use crate::explain::Plan;
+use crate::schema::{BUILTIN_FUNCTIONS, ColumnInfo, ForeignKey, IndexInfo, SchemaModel, TableInfo};
+use crate::secrets::resolve_in_background;
+use crate::sql::splitter::{command_verb, split_statements};
+use crate::sql::tokenizer::{Token, TokenKind, tokenize};
 use anyhow::{Context, anyhow};
-use once_cell::sync::OnceCell;
+use std::sync::Arc;
 use tokio::sync::Mutex;
-use tokio_postgres::NoTls;
-use tracing::{debug, error};
+use tokio_postgres::{AsyncMessage, NoTls, SimpleQueryMessage};
+use tracing::{debug, error, warn};
 
 #[derive(Debug)]
 pub struct DbClient {
-  pub PG_CLIENT: OnceCell<tokio_postgres::Client>,
+  /// Replaced when another profile is connected, see [`DbClient::setup_db_client`]
+  pub PG_CLIENT: std::sync::RwLock<Option<Arc<tokio_postgres::Client>>>,
   pub config: Mutex<Option<PostgresConfig>>,
+  pub schema_cache: Mutex<Option<Arc<SchemaModel>>>,
+  /// NOTICE/WARNING messages received on the shared connection and not yet collected
+  notices: NoticeSink,
+  /// Connection pinned by an explicit transaction, see [`DbClient::begin`]
+  transaction: Mutex<Option<Transaction>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum TxStatus {
+  #[default]
+  Idle,
+  InTransaction,
+  /// A statement failed; only a rollback is possible
+  Failed,
+}
+
+#[derive(Debug)]
+struct Transaction {
+  client: tokio_postgres::Client,
+  notices: NoticeSink,
+  status: TxStatus,
+}
+
+/// Notices of one connection, filled by the task driving it.
+type NoticeSink = Arc<std::sync::Mutex<Vec<ServerNotice>>>;
+
+fn take_notices(sink: &NoticeSink) -> Vec<ServerNotice> {
+  std::mem::take(&mut *sink.lock().unwrap())
+}
+
+/// Connects with `config_str`, collecting the connection's notices into `notices`.
+async fn connect(
+  config_str: &str,
+  notices: NoticeSink,
+) -> Result<tokio_postgres::Client, tokio_postgres::Error> {
+  let (client, mut connection) = tokio_postgres::connect(config_str, NoTls).await?;
+  debug!("Postgres client initialized..");
+
+  // Drive the connection ourselves to keep the notices it would otherwise only log
+  tokio::spawn(async move {
+    loop {
+      match std::future::poll_fn(|cx| connection.poll_message(cx)).await {
+        Some(Ok(AsyncMessage::Notice(notice))) => {
+          notices.lock().unwrap().push(ServerNotice {
+            severity: notice.severity().to_string(),
+            message: notice.message().to_string(),
+            detail: notice.detail().map(str::to_string),
+            hint: notice.hint().map(str::to_string),
+          });
+        }
+        Some(Ok(_)) => {}
+        Some(Err(e)) => {
+          error!("error connecting to postgres: {e}");
+          break;
+        }
+        None => {
+          debug!("Postgres connection closed..");
+          break;
+        }
+      }
+    }
+  });
+  Ok(client)
+}
+
+/// `create_index` as hypopg takes it: a single `CREATE [UNIQUE] INDEX` statement, without
+/// `CONCURRENTLY`.
+fn hypothetical_index(create_index: &str) -> anyhow::Result<String> {
+  let not_create_index = || anyhow!("Not a single CREATE INDEX statement: {create_index}");
+  let [statement] = split_statements(create_index).try_into().map_err(|_| not_create_index())?;
+  let text = statement.text;
+  let words: Vec<Token> = tokenize(&text)
+    .into_iter()
+    .filter(|t| {
+      !matches!(t.kind, TokenKind::Whitespace | TokenKind::LineComment | TokenKind::BlockComment)
+    })
+    .collect();
+  let is_word = |i: usize, word: &str| {
+    words.get(i).is_some_and(|t| {
+      matches!(t.kind, TokenKind::Keyword | TokenKind::Identifier)
+        && t.text(&text).eq_ignore_ascii_case(word)
+    })
+  };
+  let index_at = if is_word(1, "UNIQUE") { 2 } else { 1 };
+  if !is_word(0, "CREATE") || !is_word(index_at, "INDEX") {
+    return Err(not_create_index());
+  }
+  // not allowed inside a transaction and irrelevant for planning
+  if is_word(index_at + 1, "CONCURRENTLY") {
+    let keyword = words[index_at + 1];
+    let rest = text[keyword.end..].trim_start();
+    return Ok(format!("{}{rest}", &text[..keyword.start]));
+  }
+  Ok(text)
+}
+
+/// Whether a transaction is open after running `sql`, given whether one was open before.
+fn transaction_open_after(sql: &str, open_before: bool) -> bool {
+  split_statements(sql).iter().fold(open_before, |open, statement| {
+    let upper = statement.text.to_uppercase();
+    let words: Vec<&str> = upper.split_whitespace().map(|w| w.trim_end_matches(';')).collect();
+    // AND CHAIN starts a new transaction as soon as the current one ends
+    let chained = words.windows(2).any(|pair| pair == ["AND", "CHAIN"]);
+    match command_verb(&statement.text).as_deref() {
+      Some("BEGIN" | "START") => true,
+      Some("COMMIT" | "END" | "ABORT") => open && chained,
+      // ROLLBACK TO SAVEPOINT keeps the transaction
+      Some("ROLLBACK") => open && (words.contains(&"TO") || chained),
+      // PREPARE TRANSACTION hands the transaction over to a later COMMIT PREPARED
+      Some("PREPARE") if words.get(1) == Some(&"TRANSACTION") => false,
+      _ => open,
+    }
+  })
+}
+
+/// A failed execution keeps the notices the server sent before the error in its message.
+fn with_notices(e: tokio_postgres::Error, notices: &[ServerNotice]) -> anyhow::Error {
+  if notices.is_empty() {
+    return e.into();
+  }
+  let notices: String = notices.iter().map(|n| format!("\n{n}")).collect();
+  anyhow!("{e}{notices}")
+}
+
+/// A NOTICE, WARNING or other non-error message sent by the server, e.g. by `RAISE NOTICE`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerNotice {
+  pub severity: String,
+  pub message: String,
+  pub detail: Option<String>,
+  pub hint: Option<String>,
+}
+
+impl std::fmt::Display for ServerNotice {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}: {}", self.severity, self.message)?;
+    if let Some(detail) = &self.detail {
+      write!(f, "\nDETAIL: {detail}")?;
+    }
+    if let Some(hint) = &self.hint {
+      write!(f, "\nHINT: {hint}")?;
+    }
+    Ok(())
+  }
+}
+
+/// Output of one statement. Commands that return no rows have no columns.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ResultSet {
+  pub columns: Vec<String>,
+  /// Values in text format, `None` for NULL
+  pub rows: Vec<Vec<Option<String>>>,
+  /// Rows returned or affected, as reported by the server when the statement completed
+  pub row_count: Option<u64>,
+}
+
+impl ResultSet {
+  pub fn returns_rows(&self) -> bool {
+    !self.columns.is_empty()
+  }
+
+  /// Short outcome for the status line, e.g. `3 rows` or `1 row affected`.
+  pub fn status(&self) -> Option<String> {
+    let count = self.row_count?;
+    let rows = if count == 1 { "row" } else { "rows" };
+    Some(match (self.returns_rows(), count) {
+      (true, _) => format!("{count} {rows}"),
+      (false, 0) => "OK".to_string(),
+      (false, _) => format!("{count} {rows} affected"),
+    })
+  }
+}
+
+/// Everything a single execution produced, in order.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Execution {
+  pub result_sets: Vec<ResultSet>,
+  pub notices: Vec<ServerNotice>,
 }
 
 impl Default for DbClient {
@@ -19,7 +204,13 @@ impl Default for DbClient {
 
 impl DbClient {
   pub fn new() -> DbClient {
-    Self { PG_CLIENT: OnceCell::new(), config: Mutex::new(None) }
+    Self {
+      PG_CLIENT: std::sync::RwLock::new(None),
+      config: Mutex::new(None),
+      schema_cache: Mutex::new(None),
+      notices: NoticeSink::default(),
+      transaction: Mutex::new(None),
+    }
   }
 
   pub async fn query(&self, query_string: &str) -> anyhow::Result<Vec<tokio_postgres::Row>> {
@@ -43,34 +234,178 @@ impl DbClient {
     Ok(rows)
   }
 
-  pub async fn get_db_client(&self) -> Option<&tokio_postgres::Client> {
-    self.PG_CLIENT.get()
+  /// Runs `sql` with the simple query protocol, which accepts several `;`-separated statements
+  /// and returns a result set with its row count for each of them. Executions hold the
+  /// transaction lock, so they run one at a time and the notices received meanwhile are their
+  /// own.
+  pub async fn execute(&self, sql: &str) -> anyhow::Result<Execution> {
+    let mut tx = self.transaction.lock().await;
+    let (messages, notices) = match tx.as_mut() {
+      Some(tx) => {
+        debug!(?sql, "in transaction");
+        let result = tx.client.simple_query(sql).await;
+        let notices = take_notices(&tx.notices);
+        match result {
+          Ok(messages) => (messages, notices),
+          Err(e) => {
+            tx.status = TxStatus::Failed;
+            return Err(with_notices(e, &notices));
+          }
+        }
+      }
+      // BEGIN typed into the editor: run on a dedicated connection and pin it like `begin` does
+      None if transaction_open_after(sql, false) => {
+        debug!(?sql, "starts a transaction");
+        let (client, notices) = self.dedicated_connection().await?;
+        let result = client.simple_query(sql).await;
+        let received = take_notices(&notices);
+        // on error the connection is dropped, which rolls the transaction back
+        let messages = result.map_err(|e| with_notices(e, &received))?;
+        *tx = Some(Transaction { client, notices, status: TxStatus::InTransaction });
+        (messages, received)
+      }
+      None => {
+        self.try_connect().await?;
+        let client =
+          self.get_db_client().await.ok_or_else(|| anyhow!("No Postgres client available"))?;
+        debug!(?sql);
+        // drop notices of queries run outside `execute`, e.g. schema introspection
+        take_notices(&self.notices);
+        let result = client.simple_query(sql).await;
+        let notices = take_notices(&self.notices);
+        match result {
+          Ok(messages) => (messages, notices),
+          Err(e) => return Err(with_notices(e, &notices)),
+        }
+      }
+    };
+    // COMMIT/ROLLBACK typed into the editor end the pinned transaction as well
+    if tx.is_some() && !transaction_open_after(sql, true) {
+      *tx = None;
+    }
+    drop(tx);
+
+    let mut result_sets = Vec::new();
+    let mut current: Option<ResultSet> = None;
+    for message in messages {
+      match message {
+        SimpleQueryMessage::RowDescription(columns) => {
+          current = Some(ResultSet {
+            columns: columns.iter().map(|c| c.name().to_string()).collect(),
+            ..Default::default()
+          });
+        }
+        SimpleQueryMessage::Row(row) => {
+          let set = current.get_or_insert_with(|| ResultSet {
+            columns: row.columns().iter().map(|c| c.name().to_string()).collect(),
+            ..Default::default()
+          });
+          set.rows.push((0..row.len()).map(|i| row.get(i).map(str::to_string)).collect());
+        }
+        SimpleQueryMessage::CommandComplete(rows) => {
+          let mut set = current.take().unwrap_or_default();
+          set.row_count = Some(rows);
+          result_sets.push(set);
+        }
+        _ => {}
+      }
+    }
+
+    Ok(Execution { result_sets, notices })
+  }
+
+  /// Explains a single statement. `ANALYZE` executes it, so outside an explicit transaction the
+  /// statement runs inside one that is rolled back afterwards.
+  pub async fn explain(&self, sql: &str, analyze: bool) -> anyhow::Result<Plan> {
+    let options = if analyze { "FORMAT JSON, ANALYZE, BUFFERS" } else { "FORMAT JSON" };
+    let explain = format!("EXPLAIN ({options}) {sql}");
+    let wrap = analyze && self.tx_status().await == TxStatus::Idle;
+    let execution = if wrap {
+      let result = self.execute(&format!("BEGIN; {explain}; ROLLBACK")).await;
+      if result.is_err() {
+        // the failed statement left the implicit transaction open
+        let _ = self.execute("ROLLBACK").await;
+      }
+      result?
+    } else {
+      self.execute(&explain).await?
+    };
+    plan_from(&execution)
+  }
+
+  /// Whether the hypopg extension, which plans with hypothetical indexes, is installed.
+  pub async fn has_hypopg(&self) -> anyhow::Result<bool> {
+    let execution = self.execute("SELECT 1 FROM pg_extension WHERE extname = 'hypopg'").await?;
+    Ok(execution.result_sets.first().is_some_and(|set| !set.rows.is_empty()))
   }
 
+  /// Re-plans `sql` as if the index from `create_index` existed, using a hypothetical hypopg
+  /// index. The index is never built, so this fails when hypopg is not installed.
+  pub async fn replan_with_index(&self, sql: &str, create_index: &str) -> anyhow::Result<Plan> {
+    if self.tx_status().await != TxStatus::Idle {
+      return Err(anyhow!("Commit or roll back the open transaction first"));
+    }
+    let create_index = hypothetical_index(create_index)?;
+    if !self.has_hypopg().await? {
+      return Err(anyhow!("The hypopg extension is needed to re-plan with an index"));
+    }
+    let literal = format!("'{}'", create_index.replace('\'', "''"));
+    self.execute(&format!("SELECT * FROM hypopg_create_index({literal})")).await?;
+    let plan = self.explain(sql, false).await;
+    if let Err(e) = self.execute("SELECT hypopg_reset()").await {
+      error!("Unable to reset hypothetical indexes: {e:?}");
+    }
+    plan
+  }
+
+  /// Indexes of the given tables (by name, in any schema).
+  pub async fn list_indexes(&self, tables: &[String]) -> anyhow::Result<Vec<IndexInfo>> {
+    self.try_connect().await?;
+    let client =
+      self.get_db_client().await.ok_or_else(|| anyhow!("No Postgres client available"))?;
+    let rows = client
+      .query(
+        "SELECT schemaname::text, tablename::text, indexname::text, indexdef \
+         FROM pg_indexes WHERE tablename = ANY($1) ORDER BY 1, 2, 3",
+        &[&tables],
+      )
+      .await?;
+    Ok(
+      rows
+        .iter()
+        .map(|r| IndexInfo {
+          schema: r.get(0),
+          table: r.get(1),
+          name: r.get(2),
+          definition: r.get(3),
+        })
+        .collect(),
+    )
+  }
+
+  pub async fn get_db_client(&self) -> Option<Arc<tokio_postgres::Client>> {
+    self.PG_CLIENT.read().unwrap().clone()
+  }
+
+  /// Connects with `config`, replacing the current connection. An open transaction is dropped
+  /// with its connection, which makes the server roll it back.
   pub async fn setup_db_client(&self, config: Option<PostgresConfig>) -> anyhow::Result<()> {
-    let Some(conf) = config.clone() else {
+    let Some(conf) = config else {
       error!("Missing postgres config");
       return Err(anyhow!("Missing postgres config"));
     };
+    let conf = resolve_in_background(move || conf.resolved()).await?;
+    debug!("Trying to connect to db server at: {}", conf.redacted_url());
 
-    let config_str = conf.as_str();
-    debug!("Trying to connect to db server at: {}", config_str);
-
-    match tokio_postgres::connect(&config_str, NoTls).await {
-      Ok((client, connection)) => {
-        debug!("Postgres client initialized..");
-
-        tokio::spawn(async move {
-          if let Err(e) = connection.await {
-            error!("error connecting to postgres: {e}");
-          } else {
-            debug!("Postgres connection is successful..");
-          }
-        });
-
-        if self.PG_CLIENT.set(client).is_err() {
-          debug!("PG_CLIENT already initialized, skipping reset");
+    match connect(&conf.as_str(), self.notices.clone()).await {
+      Ok(client) => {
+        if self.PG_CLIENT.write().unwrap().replace(Arc::new(client)).is_some() {
+          debug!("Replaced the previous Postgres connection");
+        }
+        if self.transaction.lock().await.take().is_some() {
+          warn!("Open transaction dropped with the previous connection");
         }
+        *self.schema_cache.lock().await = None;
 
         let mut cfg = self.config.lock().await;
         *cfg = Some(conf);
@@ -83,6 +418,61 @@ impl DbClient {
       }
     }
   }
+
+  /// Connects with `config` without touching the current connection and returns the server
+  /// version.
+  pub async fn test_connection(&self, config: &PostgresConfig) -> anyhow::Result<String> {
+    let config = config.clone();
+    let config = resolve_in_background(move || config.resolved()).await?;
+    let client = connect(&config.as_str(), NoticeSink::default()).await?;
+    let row = client.query_one("SHOW server_version", &[]).await?;
+    Ok(row.get(0))
+  }
+
+  /// Starts an explicit transaction on a dedicated connection. Until it is committed or rolled
+  /// back, `execute` runs on that connection instead of auto-committing.
+  pub async fn begin(&self) -> anyhow::Result<()> {
+    let mut tx = self.transaction.lock().await;
+    if tx.is_some() {
+      return Err(anyhow!("A transaction is already open"));
+    }
+    let (client, notices) = self.dedicated_connection().await?;
+    client.simple_query("BEGIN").await?;
+    *tx = Some(Transaction { client, notices, status: TxStatus::InTransaction });
+    Ok(())
+  }
+
+  /// A new connection besides the shared one, for a transaction.
+  async fn dedicated_connection(&self) -> anyhow::Result<(tokio_postgres::Client, NoticeSink)> {
+    let conf =
+      self.config.lock().await.clone().ok_or_else(|| anyhow!("Missing Postgres config"))?;
+    let notices = NoticeSink::default();
+    let client = connect(&conf.as_str(), notices.clone()).await?;
+    Ok((client, notices))
+  }
+
+  /// Commits the open transaction. A failed transaction cannot commit; it is rolled back and an
+  /// error is returned.
+  pub async fn commit(&self) -> anyhow::Result<()> {
+    let tx = self.transaction.lock().await.take().ok_or_else(|| anyhow!("No open transaction"))?;
+    if tx.status == TxStatus::Failed {
+      tx.client.simple_query("ROLLBACK").await?;
+      return Err(anyhow!("Transaction failed and was rolled back"));
+    }
+    tx.client.simple_query("COMMIT").await?;
+    Ok(())
+  }
+
+  pub async fn rollback(&self) -> anyhow::Result<()> {
+    let tx = self.transaction.lock().await.take().ok_or_else(|| anyhow!("No open transaction"))?;
+    tx.client.simple_query("ROLLBACK").await?;
+    Ok(())
+  }
+
+  pub async fn tx_status(&self) -> TxStatus {
+    self.transaction.lock().await.as_ref().map(|tx| tx.status).unwrap_or(TxStatus::Idle)
+  }
+
   pub async fn try_connect(&self) -> anyhow::Result<()> {
     if self.get_db_client().await.is_some() {
       return Ok(());
@@ -107,68 +497,162 @@ impl DbClient {
     Ok(rows.into_iter().map(|r| r.get::<_, String>(0)).collect())
   }
 
-  pub async fn fetch_info(&self, query_string: &str) -> anyhow::Result<String> {
-    // list tables (unchanged)
-    if query_string.eq_ignore_ascii_case("list all available tables") {
-      let rows = self
-        .query("SELECT table_name FROM information_schema.tables WHERE table_schema = 'public'")
-        .await
-        .context("failed to query information_schema.tables")?;
-      // normalize to strings
-      let tables: Vec<String> = rows.iter().map(|r| r.get::<_, String>(0)).collect();
-      debug!("Fetched list of tables {:?}", tables);
-      return Ok(format!("Available tables: {tables:?}"));
+  /// Cached schema model; introspects the database on first use.
+  pub async fn schema_model(&self) -> anyhow::Result<Arc<SchemaModel>> {
+    if let Some(model) = self.schema_cache.lock().await.as_ref() {
+      return Ok(model.clone());
     }
+    self.refresh_schema_model().await
+  }
 
-    // extract all single-quoted names: " 'a' and 'b' " -> ["a","b"]
-    let mut quoted: Vec<String> = Vec::new();
-    let parts: Vec<&str> = query_string.split('\'').collect();
-    for i in (1..parts.len()).step_by(2) {
-      let candidate = parts[i].trim();
-      if !candidate.is_empty() {
-        quoted.push(candidate.to_string());
+  pub async fn refresh_schema_model(&self) -> anyhow::Result<Arc<SchemaModel>> {
+    let model = Arc::new(self.introspect_schema().await?);
+    *self.schema_cache.lock().await = Some(model.clone());
+    Ok(model)
+  }
+
+  async fn introspect_schema(&self) -> anyhow::Result<SchemaModel> {
+    let rows = self
+      .query(
+        "SELECT table_schema::text, table_name::text, column_name::text, data_type::text \
+         FROM information_schema.columns \
+         WHERE table_schema NOT IN ('pg_catalog', 'information_schema') \
+         ORDER BY table_schema, table_name, ordinal_position",
+      )
+      .await
+      .context("failed to introspect columns")?;
+
+    let mut tables: Vec<TableInfo> = Vec::new();
+    for row in rows {
+      let (schema, name): (String, String) = (row.get(0), row.get(1));
+      let column = ColumnInfo { name: row.get(2), data_type: row.get(3) };
+      match tables.last_mut() {
+        Some(t) if t.schema == schema && t.name == name => t.columns.push(column),
+        _ => tables.push(TableInfo { schema, name, columns: vec![column] }),
       }
     }
 
-    if !quoted.is_empty() {
-      // For each table, query column_name and data_type and return aggregated result.
-      let mut outputs: Vec<String> = Vec::with_capacity(quoted.len());
-      for table in quoted {
-        // Query with simple formatting (ok for internal admin tool). Add context so errors are clear.
-        let q = format!(
-          "SELECT column_name, data_type FROM information_schema.columns WHERE table_name = '{table}'"
-        );
-        let rows = self.query(&q).await.with_context(|| {
-          format!("error querying information_schema.columns for table '{table}'")
-        })?;
-
-        let cols: Vec<String> = rows
-          .iter()
-          .map(|r| format!("{}:{}", r.get::<_, String>(0), r.get::<_, String>(1)))
-          .collect();
-
-        debug!("Fetched table info of {}", table);
-        outputs.push(format!("Table {table} has columns: {cols:?}"));
-      }
+    let rows = self
+      .query(
+        "SELECT con.oid::bigint, ns.nspname::text, cl.relname::text, att.attname::text, \
+                fns.nspname::text, fcl.relname::text, fatt.attname::text \
+         FROM pg_constraint con \
+         JOIN pg_class cl ON cl.oid = con.conrelid \
+         JOIN pg_namespace ns ON ns.oid = cl.relnamespace \
+         JOIN pg_class fcl ON fcl.oid = con.confrelid \
+         JOIN pg_namespace fns ON fns.oid = fcl.relnamespace \
+         CROSS JOIN LATERAL unnest(con.conkey, con.confkey) WITH ORDINALITY AS k(col, fcol, ord) \
+         JOIN pg_attribute att ON att.attrelid = con.conrelid AND att.attnum = k.col \
+         JOIN pg_attribute fatt ON fatt.attrelid = con.confrelid AND fatt.attnum = k.fcol \
+         WHERE con.contype = 'f' AND ns.nspname NOT IN ('pg_catalog', 'information_schema') \
+         ORDER BY con.oid, k.ord",
+      )
+      .await
+      .context("failed to introspect foreign keys")?;
 
-      return Ok(outputs.join("\n"));
+    let mut foreign_keys: Vec<(i64, ForeignKey)> = Vec::new();
+    for row in rows {
+      let oid: i64 = row.get(0);
+      match foreign_keys.last_mut() {
+        Some((last_oid, fk)) if *last_oid == oid => {
+          fk.columns.push(row.get(3));
+          fk.ref_columns.push(row.get(6));
+        }
+        _ => foreign_keys.push((
+          oid,
+          ForeignKey {
+            schema: row.get(1),
+            table: row.get(2),
+            columns: vec![row.get(3)],
+            ref_schema: row.get(4),
+            ref_table: row.get(5),
+            ref_columns: vec![row.get(6)],
+          },
+        )),
+      }
     }
 
-    // If nothing matched we give a clear error including the original clarification.
-    Err(anyhow!("I cannot resolve clarification: {}", query_string))
+    let rows = self
+      .query(
+        "SELECT DISTINCT p.proname::text FROM pg_proc p \
+         JOIN pg_namespace n ON n.oid = p.pronamespace \
+         WHERE n.nspname NOT IN ('pg_catalog', 'information_schema')",
+      )
+      .await
+      .context("failed to introspect functions")?;
+    let mut functions: Vec<String> = rows.iter().map(|r| r.get(0)).collect();
+    functions.extend(BUILTIN_FUNCTIONS.iter().map(|f| f.to_string()));
+    functions.sort();
+    functions.dedup();
+
+    debug!("Introspected {} tables, {} foreign keys", tables.len(), foreign_keys.len());
+    Ok(SchemaModel {
+      tables,
+      foreign_keys: foreign_keys.into_iter().map(|(_, fk)| fk).collect(),
+      functions,
+    })
   }
 }
-#[tokio::test]
-async fn test_query_select_users() {
-  let _ = tracing_subscriber::fmt().with_env_filter("debug").try_init();
-  let client = DbClient::new();
-  let config = PostgresConfig {
+impl Database for DbClient {
+  async fn is_configured(&self) -> bool {
+    self.config.lock().await.is_some()
+  }
+
+  async fn execute(&self, sql: &str) -> anyhow::Result<Execution> {
+    DbClient::execute(self, sql).await
+  }
+
+  async fn list_tables(&self) -> anyhow::Result<Vec<String>> {
+    DbClient::list_tables(self).await
+  }
+
+  async fn table_columns(&self, table: &str) -> anyhow::Result<Vec<(String, String)>> {
+    let rows = self
+      .query(&format!(
+        "SELECT column_name::text, data_type::text FROM information_schema.columns \
+         WHERE table_name = '{}' ORDER BY ordinal_position",
+        table.replace('\'', "''")
+      ))
+      .await?;
+    Ok(rows.iter().map(|r| (r.get(0), r.get(1))).collect())
+  }
+
+  async fn schema_model(&self) -> anyhow::Result<Arc<SchemaModel>> {
+    DbClient::schema_model(self).await
+  }
+}
+
+/// Parses the plan out of the result sets of an execution that ran one `EXPLAIN (FORMAT JSON)`.
+fn plan_from(execution: &Execution) -> anyhow::Result<Plan> {
+  let json = execution
+    .result_sets
+    .iter()
+    .find(|set| set.returns_rows())
+    .and_then(|set| set.rows.first())
+    .and_then(|row| row.first().cloned().flatten())
+    .ok_or_else(|| anyhow!("EXPLAIN returned no plan"))?;
+  Plan::parse(&json)
+}
+
+/// The local database the DB tests run against, loaded with `tests/structure.sql` and
+/// `tests/seed.sql`.
+#[cfg(test)]
+pub(crate) fn test_config() -> PostgresConfig {
+  PostgresConfig {
     host: "localhost".to_string(),
     port: 5432,
     user: "postgres".to_string(),
     password: "password".to_string(),
     dbname: "postgres".to_string(),
-  };
+    ..Default::default()
+  }
+}
+
+#[tokio::test]
+async fn test_query_select_users() {
+  let _ = tracing_subscriber::fmt().with_env_filter("debug").try_init();
+  let client = DbClient::new();
+  let config = test_config();
   client.setup_db_client(Some(config)).await;
   let rows = client.query("SELECT id, email FROM users LIMIT 5").await.unwrap();
   assert!(!rows.is_empty(), "Expected some users from seed data");
@@ -181,13 +665,7 @@ async fn test_query_select_users() {
 async fn test_fetch_info_tables() {
   let _ = tracing_subscriber::fmt().with_env_filter("debug").try_init();
   let client = DbClient::new();
-  let config = PostgresConfig {
-    host: "localhost".to_string(),
-    port: 5432,
-    user: "postgres".to_string(),
-    password: "password".to_string(),
-    dbname: "postgres".to_string(),
-  };
+  let config = test_config();
   client.setup_db_client(Some(config)).await;
   /*  client.setup_db_client().await;*/
   let result = client.fetch_info("list all available tables").await.unwrap();
@@ -199,13 +677,7 @@ async fn test_fetch_info_tables() {
 async fn test_fetch_info_columns_books() {
   let _ = tracing_subscriber::fmt().with_env_filter("debug").try_init();
   let client = DbClient::new();
-  let config = PostgresConfig {
-    host: "localhost".to_string(),
-    port: 5432,
-    user: "postgres".to_string(),
-    password: "password".to_string(),
-    dbname: "postgres".to_string(),
-  };
+  let config = test_config();
   client.setup_db_client(Some(config)).await;
   //client.setup_db_client().await;
   let result = client.fetch_info("What are the columns in 'books'?").await.unwrap();
@@ -217,13 +689,7 @@ async fn test_fetch_info_columns_books() {
 async fn test_insert_and_select_review() {
   let _ = tracing_subscriber::fmt().with_env_filter("debug").try_init();
   let client = DbClient::new();
-  let config = PostgresConfig {
-    host: "localhost".to_string(),
-    port: 5432,
-    user: "postgres".to_string(),
-    password: "password".to_string(),
-    dbname: "postgres".to_string(),
-  };
+  let config = test_config();
   client.setup_db_client(Some(config)).await;
   //client.setup_db_client().await;
   // Insert new review
@@ -244,15 +710,175 @@ async fn test_insert_and_select_review() {
 async fn test_error_on_invalid_query() {
   let _ = tracing_subscriber::fmt().with_env_filter("debug").try_init();
   let client = DbClient::new();
-  let config = PostgresConfig {
-    host: "localhost".to_string(),
-    port: 5432,
-    user: "postgres".to_string(),
-    password: "password".to_string(),
-    dbname: "postgres".to_string(),
-  };
+  let config = test_config();
   client.setup_db_client(Some(config)).await;
   //client.setup_db_client().await;
   let result = client.query("SELECT * FROM non_existing_table").await;
   assert!(result.is_err(), "Querying non-existent table should fail");
 }
+
+#[tokio::test]
+async fn test_schema_model_introspection() {
+  let _ = tracing_subscriber::fmt().with_env_filter("debug").try_init();
+  let client = DbClient::new();
+  let config = test_config();
+  client.setup_db_client(Some(config)).await;
+  let model = client.schema_model().await.unwrap();
+  let books = model.table("books").expect("books table");
+  assert!(books.columns.iter().any(|c| c.name == "title"));
+  let reviews = model.table("public.reviews").expect("reviews table");
+  assert!(!model.foreign_keys_between(reviews, books).is_empty());
+  assert!(model.functions.iter().any(|f| f == "count"));
+}
+
+#[tokio::test]
+async fn test_execute_returns_every_result_set_and_notices() {
+  let _ = tracing_subscriber::fmt().with_env_filter("debug").try_init();
+  let client = DbClient::new();
+  let config = test_config();
+  client.setup_db_client(Some(config)).await;
+  let execution = client
+    .execute(
+      "CREATE TEMP TABLE exec_test (id int, note text);
+       INSERT INTO exec_test VALUES (1, 'a'), (2, NULL);
+       UPDATE exec_test SET note = 'b' WHERE id = 2;
+       SELECT id, note FROM exec_test ORDER BY id;
+       DO $$ BEGIN RAISE NOTICE 'done; really'; END $$;",
+    )
+    .await
+    .unwrap();
+
+  let statuses: Vec<String> = execution.result_sets.iter().map(|s| s.status().unwrap()).collect();
+  assert_eq!(statuses, vec!["OK", "2 rows affected", "1 row affected", "2 rows", "OK"]);
+  let select = &execution.result_sets[3];
+  assert_eq!(select.columns, vec!["id", "note"]);
+  assert_eq!(select.rows[1], vec![Some("2".to_string()), Some("b".to_string())]);
+  assert!(!execution.result_sets[1].returns_rows());
+  assert_eq!(execution.notices.len(), 1);
+  assert_eq!(execution.notices[0].severity, "NOTICE");
+  assert_eq!(execution.notices[0].message, "done; really");
+}
+
+#[tokio::test]
+async fn test_transaction_status_and_rollback() {
+  let _ = tracing_subscriber::fmt().with_env_filter("debug").try_init();
+  let client = DbClient::new();
+  let config = test_config();
+  client.setup_db_client(Some(config)).await;
+  client.begin().await.unwrap();
+  assert!(client.begin().await.is_err());
+  client.execute("CREATE TABLE tx_rollback_test (id int)").await.unwrap();
+  assert_eq!(client.tx_status().await, TxStatus::InTransaction);
+  assert!(client.execute("SELECT 1 / 0").await.is_err());
+  assert_eq!(client.tx_status().await, TxStatus::Failed);
+  assert!(client.commit().await.is_err());
+  assert_eq!(client.tx_status().await, TxStatus::Idle);
+
+  let execution = client.execute("SELECT to_regclass('tx_rollback_test') IS NULL").await.unwrap();
+  assert_eq!(execution.result_sets[0].rows[0][0].as_deref(), Some("t"));
+
+  // BEGIN and ROLLBACK typed into the editor
+  client.execute("BEGIN; CREATE TABLE tx_typed_test (id int)").await.unwrap();
+  assert_eq!(client.tx_status().await, TxStatus::InTransaction);
+  client.execute("SAVEPOINT s; ROLLBACK TO SAVEPOINT s").await.unwrap();
+  assert_eq!(client.tx_status().await, TxStatus::InTransaction);
+  client.execute("ROLLBACK").await.unwrap();
+  assert_eq!(client.tx_status().await, TxStatus::Idle);
+  let execution = client.execute("SELECT to_regclass('tx_typed_test') IS NULL").await.unwrap();
+  assert_eq!(execution.result_sets[0].rows[0][0].as_deref(), Some("t"));
+  client.execute("BEGIN; SELECT 1; COMMIT").await.unwrap();
+  assert_eq!(client.tx_status().await, TxStatus::Idle);
+
+  // AND CHAIN opens the next transaction right away
+  client.execute("BEGIN; COMMIT AND CHAIN").await.unwrap();
+  assert_eq!(client.tx_status().await, TxStatus::InTransaction);
+  client.execute("ROLLBACK AND CHAIN").await.unwrap();
+  assert_eq!(client.tx_status().await, TxStatus::InTransaction);
+  client.execute("ROLLBACK AND NO CHAIN").await.unwrap();
+  assert_eq!(client.tx_status().await, TxStatus::Idle);
+  // the test server allows no prepared transactions, so only the tracking is checked
+  assert!(!transaction_open_after("PREPARE TRANSACTION 'tx1'", true));
+  assert!(transaction_open_after("PREPARE q AS SELECT 1", true));
+}
+
+#[tokio::test]
+async fn test_switching_connection() {
+  let client = DbClient::new();
+  let config = test_config();
+  assert!(!client.test_connection(&config).await.unwrap().is_empty());
+  let wrong = PostgresConfig { dbname: "no_such_db".into(), ..config.clone() };
+  assert!(client.test_connection(&wrong).await.is_err());
+
+  client.setup_db_client(Some(config.clone())).await.unwrap();
+  client.schema_model().await.unwrap();
+  client.begin().await.unwrap();
+  client.setup_db_client(Some(config)).await.unwrap();
+  assert_eq!(client.tx_status().await, TxStatus::Idle);
+  assert!(client.schema_cache.lock().await.is_none());
+  assert!(!client.list_tables().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_explain_analyze_rolls_back() {
+  let _ = tracing_subscriber::fmt().with_env_filter("debug").try_init();
+  let client = DbClient::new();
+  let config = test_config();
+  client.setup_db_client(Some(config)).await;
+  let plan = client.explain("SELECT * FROM users WHERE id < 10", false).await.unwrap();
+  assert!(!plan.is_analyzed());
+
+  let plan =
+    client.explain("CREATE TABLE explain_rollback_test AS SELECT 1 AS id", true).await.unwrap();
+  assert!(plan.is_analyzed());
+  assert!(plan.execution_time.is_some());
+  let execution =
+    client.execute("SELECT to_regclass('explain_rollback_test') IS NULL").await.unwrap();
+  assert_eq!(execution.result_sets[0].rows[0][0].as_deref(), Some("t"));
+}
+
+#[tokio::test]
+async fn test_replan_with_index() {
+  let _ = tracing_subscriber::fmt().with_env_filter("debug").try_init();
+  let client = DbClient::new();
+  let config = test_config();
+  client.setup_db_client(Some(config)).await;
+  client
+    .execute(
+      "DROP TABLE IF EXISTS replan_test;
+       CREATE TABLE replan_test AS SELECT g AS id, g % 1000 AS bucket FROM generate_series(1, 50000) g;
+       ANALYZE replan_test;",
+    )
+    .await
+    .unwrap();
+
+  let sql = "SELECT * FROM replan_test WHERE bucket = 7";
+  let before = client.explain(sql, false).await.unwrap();
+  let after =
+    client.replan_with_index(sql, "CREATE INDEX CONCURRENTLY ON replan_test (bucket)").await;
+  if client.has_hypopg().await.unwrap() {
+    assert!(after.unwrap().root.total_cost < before.root.total_cost);
+  } else {
+    // without hypopg the index is never built
+    assert!(after.is_err());
+  }
+  assert!(client.replan_with_index(sql, "DROP TABLE replan_test").await.is_err());
+  assert!(client.list_indexes(&["replan_test".to_string()]).await.unwrap().is_empty());
+  let indexes = client.list_indexes(&["users".to_string()]).await.unwrap();
+  assert!(indexes.iter().any(|i| i.definition.contains("UNIQUE")));
+
+  client.execute("DROP TABLE replan_test").await.unwrap();
+}
+
+#[test]
+fn test_hypothetical_index() {
+  assert_eq!(
+    hypothetical_index("create unique index Concurrently idx_concurrently ON t (a);").unwrap(),
+    "create unique index idx_concurrently ON t (a)"
+  );
+  assert_eq!(
+    hypothetical_index("CREATE INDEX ON \"Concurrently\" (a)").unwrap(),
+    "CREATE INDEX ON \"Concurrently\" (a)"
+  );
+  assert!(hypothetical_index("CREATE INDEX ON t (a); DROP TABLE t").is_err());
+  assert!(hypothetical_index("DROP INDEX idx").is_err());
+}
@@ -0,0 +1,280 @@
+use crate::agent::Agent;
+use crate::conversation::Conversation;
+use crate::db_client::{DbClient, ResultSet};
+use crate::sql::splitter::{command_verb, split_statements};
+use crate::trace::TraceEvent;
+use anyhow::{Context, anyhow};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::Instant;
+
+/// A question with SQL known to answer it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EvalCase {
+  pub question: String,
+  /// Gold query the generated SQL is compared against
+  pub sql: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct EvalFile {
+  #[serde(default)]
+  cases: Vec<EvalCase>,
+}
+
+/// Reads `[[cases]]` entries from a TOML file.
+pub fn load_cases(path: &Path) -> anyhow::Result<Vec<EvalCase>> {
+  let raw = std::fs::read_to_string(path)
+    .with_context(|| format!("failed to read eval cases {}", path.display()))?;
+  let file: EvalFile =
+    toml::from_str(&raw).with_context(|| format!("invalid eval cases {}", path.display()))?;
+  Ok(file.cases)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaseResult {
+  pub question: String,
+  pub gold_sql: String,
+  pub generated: Option<String>,
+  /// Why the case failed when it did not produce the gold result
+  pub error: Option<String>,
+  pub correct: bool,
+  /// LLM requests made for the question
+  pub turns: usize,
+  /// Estimated tokens sent and received
+  pub tokens: usize,
+  pub latency_ms: u64,
+}
+
+/// Outcome of all cases for one LLM profile.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EvalReport {
+  pub profile: String,
+  pub model: String,
+  pub cases: Vec<CaseResult>,
+}
+
+impl EvalReport {
+  /// Share of cases whose generated SQL returned the gold result.
+  pub fn accuracy(&self) -> f64 {
+    self.average(|c| if c.correct { 1.0 } else { 0.0 })
+  }
+
+  pub fn avg_turns(&self) -> f64 {
+    self.average(|c| c.turns as f64)
+  }
+
+  pub fn avg_tokens(&self) -> f64 {
+    self.average(|c| c.tokens as f64)
+  }
+
+  pub fn avg_latency_ms(&self) -> f64 {
+    self.average(|c| c.latency_ms as f64)
+  }
+
+  fn average(&self, value: impl Fn(&CaseResult) -> f64) -> f64 {
+    if self.cases.is_empty() {
+      return 0.0;
+    }
+    self.cases.iter().map(value).sum::<f64>() / self.cases.len() as f64
+  }
+
+  /// One line per case, then the totals.
+  pub fn to_text(&self) -> String {
+    let mut out = format!("== {} ({})\n", self.profile, self.model);
+    for case in &self.cases {
+      out.push_str(&format!(
+        "[{}] {} ({} turns, ~{} tokens, {} ms)\n",
+        if case.correct { "pass" } else { "FAIL" },
+        case.question,
+        case.turns,
+        case.tokens,
+        case.latency_ms
+      ));
+      if let Some(error) = &case.error {
+        out.push_str(&format!("       {error}\n"));
+      }
+    }
+    out.push_str(&format!(
+      "accuracy {:.1}% ({}/{}), avg {:.1} turns, ~{:.0} tokens, {:.0} ms\n",
+      self.accuracy() * 100.0,
+      self.cases.iter().filter(|c| c.correct).count(),
+      self.cases.len(),
+      self.avg_turns(),
+      self.avg_tokens(),
+      self.avg_latency_ms()
+    ));
+    out
+  }
+}
+
+/// Execution accuracy: both results have the same width and the same rows. Row order only
+/// counts when `ordered`, i.e. the gold query sorts its result.
+pub fn same_result(gold: &ResultSet, generated: &ResultSet, ordered: bool) -> bool {
+  if gold.columns.len() != generated.columns.len() {
+    return false;
+  }
+  if ordered {
+    return gold.rows == generated.rows;
+  }
+  let mut gold_rows = gold.rows.clone();
+  let mut generated_rows = generated.rows.clone();
+  gold_rows.sort();
+  generated_rows.sort();
+  gold_rows == generated_rows
+}
+
+fn is_ordered(sql: &str) -> bool {
+  sql.split_whitespace().collect::<Vec<_>>().join(" ").to_uppercase().contains("ORDER BY")
+}
+
+/// Rejects anything but a single query. A `COMMIT` or a second statement would escape the
+/// rollback in `result_of`.
+fn check_single_query(sql: &str) -> anyhow::Result<()> {
+  let statements = split_statements(sql);
+  let [statement] = statements.as_slice() else {
+    return Err(anyhow!("Expected one statement, got {}", statements.len()));
+  };
+  match command_verb(&statement.text).as_deref() {
+    Some("SELECT" | "WITH" | "VALUES" | "TABLE") => Ok(()),
+    verb => Err(anyhow!("Only queries are evaluated, not {}", verb.unwrap_or("this statement"))),
+  }
+}
+
+/// Last row-returning result of the query `sql`. It runs in a transaction that is always rolled
+/// back, so it cannot change the data.
+async fn result_of(db_client: &DbClient, sql: &str) -> anyhow::Result<ResultSet> {
+  check_single_query(sql)?;
+  db_client.begin().await?;
+  let execution = db_client.execute(sql).await;
+  db_client.rollback().await?;
+  execution?
+    .result_sets
+    .into_iter()
+    .rev()
+    .find(ResultSet::returns_rows)
+    .ok_or_else(|| anyhow!("Query returned no rows"))
+}
+
+/// Generates SQL for every case with the agent's current LLM and compares its result with the
+/// gold query's.
+pub async fn evaluate(agent: &Agent, profile: &str, cases: &[EvalCase]) -> EvalReport {
+  let model = agent.llm_client.read().await.as_ref().map(|c| c.model.clone()).unwrap_or_default();
+  let mut results = Vec::new();
+  for case in cases {
+    let mut conversation = Conversation::new();
+    let started = Instant::now();
+    let generated = agent.text_to_sql(&case.question, &mut conversation, &mut ()).await;
+    let latency_ms = started.elapsed().as_millis() as u64;
+    let (turns, tokens) = conversation
+      .traces
+      .last()
+      .map(|trace| {
+        let turns =
+          trace.steps.iter().filter(|s| matches!(s.event, TraceEvent::LlmRequest { .. })).count();
+        (turns, trace.total_tokens())
+      })
+      .unwrap_or_default();
+
+    let mut result = CaseResult {
+      question: case.question.clone(),
+      gold_sql: case.sql.clone(),
+      generated: None,
+      error: None,
+      correct: false,
+      turns,
+      tokens,
+      latency_ms,
+    };
+    match generated {
+      Ok(sql) => {
+        let gold = result_of(&agent.db_client, &case.sql).await;
+        let actual = result_of(&agent.db_client, &sql).await;
+        match (gold, actual) {
+          (Ok(gold), Ok(actual)) => {
+            result.correct = same_result(&gold, &actual, is_ordered(&case.sql));
+            if !result.correct {
+              result.error = Some("Result differs from the gold query".into());
+            }
+          }
+          (Err(e), _) => result.error = Some(format!("Gold query failed: {e}")),
+          (_, Err(e)) => result.error = Some(format!("Generated query failed: {e}")),
+        }
+        result.generated = Some(sql);
+      }
+      Err(e) => result.error = Some(format!("No SQL generated: {e}")),
+    }
+    results.push(result);
+  }
+  EvalReport { profile: profile.into(), model, cases: results }
+}
+
+#[test]
+fn test_same_result() {
+  let set = |rows: &[&str]| ResultSet {
+    columns: vec!["name".into()],
+    rows: rows.iter().map(|r| vec![Some(r.to_string())]).collect(),
+    row_count: None,
+  };
+  assert!(same_result(&set(&["a", "b"]), &set(&["b", "a"]), false));
+  assert!(!same_result(&set(&["a", "b"]), &set(&["b", "a"]), true));
+  assert!(!same_result(&set(&["a", "b"]), &set(&["a"]), false));
+  assert!(is_ordered("SELECT * FROM books ORDER\n  BY price"));
+  assert!(!is_ordered("SELECT * FROM books"));
+
+  assert!(check_single_query("WITH t AS (SELECT 1) SELECT * FROM t;").is_ok());
+  assert!(check_single_query("DELETE FROM books").is_err());
+  assert!(check_single_query("SELECT 1; COMMIT; DELETE FROM books").is_err());
+}
+
+#[tokio::test]
+async fn test_evaluate_with_mocked_llm() {
+  use crate::config::LlmConfig;
+  use crate::db_client::test_config;
+  use crate::glossary::Glossary;
+  use httpmock::prelude::*;
+  use tokio::sync::RwLock;
+
+  let server = MockServer::start_async().await;
+  let reply =
+    |content: &str| serde_json::json!({ "choices": [{ "message": { "content": content } }] });
+  // the SQL once the agent answered the clarification, the clarification before that
+  server
+    .mock_async(|when, then| {
+      when.method(POST).path("/v1/chat/completions").body_contains("Now that you have the schema");
+      then.status(200).json_body(reply(
+        r#"{"explanation": "", "sql": "SELECT full_name FROM users ORDER BY id", "clarification": ""}"#,
+      ));
+    })
+    .await;
+  server
+    .mock_async(|when, then| {
+      when.method(POST).path("/v1/chat/completions");
+      then.status(200).json_body(reply(
+        r#"{"explanation": "", "sql": "", "clarification": "What are the columns in 'users'?"}"#,
+      ));
+    })
+    .await;
+
+  let agent = Agent {
+    db_client: DbClient::new(),
+    llm_client: RwLock::new(Some(LlmConfig::new(&server.url("/v1"), "mock"))),
+    glossary: RwLock::new(Glossary::default()),
+    active_profile: RwLock::new(None),
+  };
+  let config = test_config();
+  agent.db_client.setup_db_client(Some(config)).await.unwrap();
+
+  let cases = vec![
+    EvalCase { question: "names of all users".into(), sql: "SELECT full_name FROM users".into() },
+    EvalCase { question: "emails of all users".into(), sql: "SELECT email FROM users".into() },
+  ];
+  let report = evaluate(&agent, "mock", &cases).await;
+  assert_eq!(report.cases[0].generated.as_deref(), Some("SELECT full_name FROM users ORDER BY id"));
+  assert!(report.cases[0].correct, "{:?}", report.cases[0]);
+  assert!(!report.cases[1].correct);
+  assert_eq!(report.accuracy(), 0.5);
+  assert_eq!(report.avg_turns(), 2.0);
+  assert!(report.avg_tokens() > 0.0);
+  assert!(report.to_text().contains("accuracy 50.0% (1/2)"));
+}
@@ -0,0 +1,226 @@
+use anyhow::{Context, anyhow};
+use serde::Deserialize;
+
+/// One node of an `EXPLAIN (FORMAT JSON)` plan. Actual and buffer figures are only present
+/// with `ANALYZE` / `BUFFERS`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct PlanNode {
+  #[serde(rename = "Node Type")]
+  pub node_type: String,
+  #[serde(rename = "Relation Name")]
+  pub relation_name: Option<String>,
+  #[serde(rename = "Alias")]
+  pub alias: Option<String>,
+  #[serde(rename = "Index Name")]
+  pub index_name: Option<String>,
+  #[serde(rename = "Join Type")]
+  pub join_type: Option<String>,
+  #[serde(rename = "Startup Cost", default)]
+  pub startup_cost: f64,
+  #[serde(rename = "Total Cost", default)]
+  pub total_cost: f64,
+  #[serde(rename = "Plan Rows", default)]
+  pub plan_rows: f64,
+  #[serde(rename = "Actual Startup Time")]
+  pub actual_startup_time: Option<f64>,
+  #[serde(rename = "Actual Total Time")]
+  pub actual_total_time: Option<f64>,
+  #[serde(rename = "Actual Rows")]
+  pub actual_rows: Option<f64>,
+  #[serde(rename = "Actual Loops")]
+  pub actual_loops: Option<f64>,
+  #[serde(rename = "Shared Hit Blocks")]
+  pub shared_hit_blocks: Option<u64>,
+  #[serde(rename = "Shared Read Blocks")]
+  pub shared_read_blocks: Option<u64>,
+  #[serde(rename = "Plans", default)]
+  pub children: Vec<PlanNode>,
+}
+
+impl PlanNode {
+  /// Node type with the relation or index it reads, e.g. `Index Scan using users_pkey on users`.
+  pub fn title(&self) -> String {
+    let mut title = match &self.join_type {
+      Some(join) if self.node_type.contains("Join") || self.node_type == "Nested Loop" => {
+        format!("{} {join}", self.node_type)
+      }
+      _ => self.node_type.clone(),
+    };
+    if let Some(index) = &self.index_name {
+      title.push_str(&format!(" using {index}"));
+    }
+    if let Some(relation) = &self.relation_name {
+      title.push_str(&format!(" on {relation}"));
+      if let Some(alias) = self.alias.as_ref().filter(|a| *a != relation) {
+        title.push_str(&format!(" {alias}"));
+      }
+    }
+    title
+  }
+
+  /// Time spent over all loops, in ms.
+  pub fn inclusive_time(&self) -> Option<f64> {
+    Some(self.actual_total_time? * self.actual_loops.unwrap_or(1.0))
+  }
+
+  /// Time spent in this node alone, excluding its children.
+  pub fn exclusive_time(&self) -> Option<f64> {
+    let children: f64 = self.children.iter().filter_map(|c| c.inclusive_time()).sum();
+    Some((self.inclusive_time()? - children).max(0.0))
+  }
+
+  /// Cost of this node alone, excluding its children.
+  pub fn exclusive_cost(&self) -> f64 {
+    let children: f64 = self.children.iter().map(|c| c.total_cost).sum();
+    (self.total_cost - children).max(0.0)
+  }
+
+  /// How far the row estimate is off, as a factor: positive when the planner underestimated,
+  /// negative when it overestimated, e.g. `10.0` for 10 estimated and 100 actual rows.
+  pub fn rows_estimate_error(&self) -> Option<f64> {
+    let actual = self.actual_rows?.max(1.0);
+    let estimated = self.plan_rows.max(1.0);
+    Some(if actual >= estimated { actual / estimated } else { -(estimated / actual) })
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+struct ExplainOutput {
+  #[serde(rename = "Plan")]
+  plan: PlanNode,
+  #[serde(rename = "Planning Time")]
+  planning_time: Option<f64>,
+  #[serde(rename = "Execution Time")]
+  execution_time: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Plan {
+  pub root: PlanNode,
+  pub planning_time: Option<f64>,
+  pub execution_time: Option<f64>,
+}
+
+/// A plan node flattened for display, numbered in pre-order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlanRow<'a> {
+  pub id: usize,
+  pub depth: usize,
+  pub node: &'a PlanNode,
+  /// Share of the total time (or cost, without `ANALYZE`) spent in the node itself
+  pub share: f64,
+}
+
+impl Plan {
+  /// Parses the output of `EXPLAIN (FORMAT JSON)`.
+  pub fn parse(json: &str) -> anyhow::Result<Plan> {
+    let outputs: Vec<ExplainOutput> =
+      serde_json::from_str(json).context("Unable to parse EXPLAIN output")?;
+    let output = outputs.into_iter().next().ok_or_else(|| anyhow!("EXPLAIN returned no plan"))?;
+    Ok(Plan {
+      root: output.plan,
+      planning_time: output.planning_time,
+      execution_time: output.execution_time,
+    })
+  }
+
+  pub fn is_analyzed(&self) -> bool {
+    self.root.actual_total_time.is_some()
+  }
+
+  /// All nodes in pre-order with their depth and share of the total.
+  pub fn rows(&self) -> Vec<PlanRow<'_>> {
+    let analyzed = self.is_analyzed();
+    let total =
+      if analyzed { self.root.inclusive_time().unwrap_or_default() } else { self.root.total_cost };
+    let mut rows = Vec::new();
+    let mut stack = vec![(0, &self.root)];
+    while let Some((depth, node)) = stack.pop() {
+      let own =
+        if analyzed { node.exclusive_time().unwrap_or_default() } else { node.exclusive_cost() };
+      let share = if total > 0.0 { own / total } else { 0.0 };
+      rows.push(PlanRow { id: rows.len(), depth, node, share });
+      stack.extend(node.children.iter().rev().map(|c| (depth + 1, c)));
+    }
+    rows
+  }
+}
+
+impl Plan {
+  /// Indented text rendering, one node per line, for prompts and logs.
+  pub fn to_text(&self) -> String {
+    self
+      .rows()
+      .iter()
+      .map(|row| {
+        let node = row.node;
+        let mut line = format!(
+          "{}-> {} (cost={:.2}..{:.2} rows={}",
+          "  ".repeat(row.depth),
+          node.title(),
+          node.startup_cost,
+          node.total_cost,
+          node.plan_rows
+        );
+        if let (Some(time), Some(rows)) = (node.actual_total_time, node.actual_rows) {
+          line.push_str(&format!(
+            ") (actual time={time:.3} rows={rows} loops={}",
+            node.actual_loops.unwrap_or(1.0)
+          ));
+        }
+        line.push(')');
+        line
+      })
+      .collect::<Vec<_>>()
+      .join("\n")
+  }
+
+  /// Tables the plan reads, without duplicates.
+  pub fn relations(&self) -> Vec<String> {
+    let mut relations = Vec::new();
+    for row in self.rows() {
+      if let Some(relation) = row.node.relation_name.as_ref().filter(|r| !relations.contains(*r)) {
+        relations.push(relation.clone());
+      }
+    }
+    relations
+  }
+}
+
+#[test]
+fn test_parse_analyzed_plan() {
+  let json = r#"[{
+    "Plan": {
+      "Node Type": "Hash Join", "Join Type": "Inner",
+      "Startup Cost": 1.0, "Total Cost": 50.0, "Plan Rows": 10,
+      "Actual Startup Time": 0.1, "Actual Total Time": 10.0, "Actual Rows": 1000, "Actual Loops": 1,
+      "Shared Hit Blocks": 12, "Shared Read Blocks": 3,
+      "Plans": [
+        {"Node Type": "Seq Scan", "Relation Name": "orders", "Alias": "o",
+         "Startup Cost": 0.0, "Total Cost": 40.0, "Plan Rows": 500,
+         "Actual Startup Time": 0.0, "Actual Total Time": 8.0, "Actual Rows": 500, "Actual Loops": 1},
+        {"Node Type": "Hash", "Startup Cost": 0.0, "Total Cost": 5.0, "Plan Rows": 100,
+         "Actual Startup Time": 0.0, "Actual Total Time": 0.5, "Actual Rows": 10, "Actual Loops": 1}
+      ]
+    },
+    "Planning Time": 0.2,
+    "Execution Time": 10.5
+  }]"#;
+  let plan = Plan::parse(json).unwrap();
+  assert!(plan.is_analyzed());
+  assert_eq!(plan.execution_time, Some(10.5));
+  assert_eq!(plan.root.title(), "Hash Join Inner");
+  assert_eq!(plan.root.children[0].title(), "Seq Scan on orders o");
+  assert_eq!(plan.root.rows_estimate_error(), Some(100.0));
+  assert_eq!(plan.root.children[1].rows_estimate_error(), Some(-10.0));
+
+  let rows = plan.rows();
+  let order: Vec<(usize, &str)> =
+    rows.iter().map(|r| (r.depth, r.node.node_type.as_str())).collect();
+  assert_eq!(order, vec![(0, "Hash Join"), (1, "Seq Scan"), (1, "Hash")]);
+  assert!((rows[1].share - 0.8).abs() < 1e-9);
+  assert!((rows[0].share - 0.15).abs() < 1e-9);
+
+  assert_eq!(plan.relations(), vec!["orders".to_string()]);
+  assert!(plan.to_text().contains("\n  -> Seq Scan on orders o (cost=0.00..40.00 rows=500)"));
+}
@@ -0,0 +1,99 @@
+use crate::conversation::ChatMessage;
+use anyhow::Context;
+use httpmock::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// One LLM call: the messages sent and the raw reply text.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Interaction {
+  pub messages: Vec<ChatMessage>,
+  pub response: String,
+}
+
+/// Recorded LLM calls, stored as JSON, to replay agent runs without a model.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Fixture {
+  pub interactions: Vec<Interaction>,
+}
+
+impl Fixture {
+  pub fn load(path: &Path) -> anyhow::Result<Self> {
+    let raw = std::fs::read_to_string(path)
+      .with_context(|| format!("failed to read LLM fixture {}", path.display()))?;
+    serde_json::from_str(&raw).with_context(|| format!("invalid LLM fixture {}", path.display()))
+  }
+
+  pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+      std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(path, serde_json::to_string_pretty(self)?)
+      .with_context(|| format!("failed to write LLM fixture {}", path.display()))
+  }
+
+  /// Appends one call to the fixture at `path`, creating it if needed.
+  pub fn record(path: &Path, messages: &[ChatMessage], response: &str) -> anyhow::Result<()> {
+    let mut fixture = if path.exists() { Self::load(path)? } else { Self::default() };
+    fixture
+      .interactions
+      .push(Interaction { messages: messages.to_vec(), response: response.into() });
+    fixture.save(path)
+  }
+
+  /// Starts a chat completions server that answers with the recorded replies. Use
+  /// `server.url("/v1")` as the profile's `api_url`.
+  ///
+  /// A request gets the reply of the interaction with the same sequence of message roles.
+  /// Contents are not compared, so fixtures survive prompt edits and different table lists; an
+  /// agent run only appends messages, so the roles tell its turns apart.
+  pub async fn serve(&self) -> MockServer {
+    let server = MockServer::start_async().await;
+    // the first matching mock wins and `messages` is matched as a prefix: longest first
+    let mut interactions: Vec<&Interaction> = self.interactions.iter().collect();
+    interactions.sort_by_key(|i| std::cmp::Reverse(i.messages.len()));
+    for interaction in interactions {
+      let roles: Vec<serde_json::Value> =
+        interaction.messages.iter().map(|m| serde_json::json!({ "role": m.role })).collect();
+      let partial = serde_json::json!({ "messages": roles }).to_string();
+      let body = serde_json::json!({
+        "choices": [{ "message": { "role": "assistant", "content": interaction.response } }]
+      });
+      server
+        .mock_async(|when, then| {
+          when.method(POST).path("/v1/chat/completions").json_body_partial(partial);
+          then.status(200).json_body(body);
+        })
+        .await;
+    }
+    server
+  }
+}
+
+#[tokio::test]
+async fn test_record_and_replay() {
+  use crate::config::LlmConfig;
+  use crate::llm::complete;
+
+  let path = std::env::temp_dir().join(format!("pg_admin_fixture_{}.json", std::process::id()));
+  let _ = std::fs::remove_file(&path);
+  let message =
+    |role: &str, content: &str| ChatMessage { role: role.into(), content: content.into() };
+  let first = vec![message("system", "rules"), message("user", "question")];
+  let mut second = first.clone();
+  second.extend([message("assistant", "first reply"), message("user", "more")]);
+  Fixture::record(&path, &first, "first reply").unwrap();
+  Fixture::record(&path, &second, "second reply").unwrap();
+  let fixture = Fixture::load(&path).unwrap();
+  assert_eq!(fixture.interactions.len(), 2);
+  let _ = std::fs::remove_file(&path);
+
+  let server = fixture.serve().await;
+  let config = LlmConfig::new(&server.url("/v1"), "replay");
+  let client = reqwest::Client::new();
+  assert_eq!(complete(&client, &config, &first).await.unwrap(), "first reply");
+  // contents may differ, the roles pick the turn
+  second[3].content = "something else".into();
+  assert_eq!(complete(&client, &config, &second).await.unwrap(), "second reply");
+  assert!(complete(&client, &config, &second[..1]).await.is_err());
+}
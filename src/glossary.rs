@@ -0,0 +1,193 @@
+use crate::config::Setting;
+use anyhow::{Context, anyhow};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tracing::debug;
+
+/// Start of the glossary prompt, which is replaced on every question
+pub const PROMPT_HEADER: &str = "Business glossary.";
+
+/// A business term with the SQL that defines it in our schema.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct GlossaryEntry {
+  pub term: String,
+  #[serde(default)]
+  pub aliases: Vec<String>,
+  #[serde(default)]
+  pub kind: GlossaryKind,
+  /// SQL snippet: an expression for metrics, a predicate for filters or a full query
+  pub sql: String,
+  #[serde(default)]
+  pub description: String,
+  /// Preferred join paths, e.g. "orders o JOIN users u ON u.id = o.user_id"
+  #[serde(default)]
+  pub joins: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum GlossaryKind {
+  Metric,
+  Filter,
+  Join,
+  #[default]
+  Query,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+struct GlossaryFile {
+  #[serde(default)]
+  entries: Vec<GlossaryEntry>,
+}
+
+/// Glossary loaded from `glossary.toml` (shared) and `glossary.<profile>.toml` (per Postgres
+/// profile), both living next to `config.toml`. New entries are written to the profile file.
+#[derive(Debug, Clone, Default)]
+pub struct Glossary {
+  /// File new entries are written to; `None` until a glossary is loaded, so that saving an
+  /// empty glossary can't overwrite one on disk
+  pub path: Option<PathBuf>,
+  pub entries: Vec<GlossaryEntry>,
+  /// Entries from the shared file, used for matching but never written back
+  pub shared: Vec<GlossaryEntry>,
+}
+
+impl Glossary {
+  pub fn shared_path() -> PathBuf {
+    Setting::data_path("glossary.toml")
+  }
+
+  pub fn profile_path(profile: &str) -> PathBuf {
+    Setting::data_path(&format!("glossary.{profile}.toml"))
+  }
+
+  pub fn load(profile: Option<&str>) -> anyhow::Result<Self> {
+    let shared_path = Self::shared_path();
+    let Some(profile) = profile else {
+      let entries = read_entries(&shared_path)?;
+      return Ok(Self { path: Some(shared_path), entries, shared: vec![] });
+    };
+
+    let path = Self::profile_path(profile);
+    let entries = read_entries(&path)?;
+    let shared = read_entries(&shared_path)?;
+    debug!("Loaded {} glossary entries for profile {}", entries.len() + shared.len(), profile);
+    Ok(Self { path: Some(path), entries, shared })
+  }
+
+  pub fn all(&self) -> impl Iterator<Item = &GlossaryEntry> {
+    self.entries.iter().chain(self.shared.iter())
+  }
+
+  /// Entries whose term or one of its aliases appears as a whole phrase in the question.
+  pub fn matching(&self, question: &str) -> Vec<&GlossaryEntry> {
+    let question_lc = question.to_lowercase();
+    self
+      .all()
+      .filter(|e| {
+        std::iter::once(&e.term).chain(e.aliases.iter()).any(|t| contains_phrase(&question_lc, t))
+      })
+      .collect()
+  }
+
+  /// Render matched entries as a message the LLM can use while writing SQL.
+  pub fn prompt_for(entries: &[&GlossaryEntry]) -> Option<String> {
+    if entries.is_empty() {
+      return None;
+    }
+
+    let mut out = format!(
+      "{PROMPT_HEADER} These terms have fixed definitions in this database; use them exactly \
+       instead of guessing:\n"
+    );
+    for e in entries {
+      out.push_str(&format!("- \"{}\" ({:?}): {}\n", e.term, e.kind, e.sql.trim()));
+      if !e.description.is_empty() {
+        out.push_str(&format!("  meaning: {}\n", e.description));
+      }
+      for j in &e.joins {
+        out.push_str(&format!("  preferred join: {j}\n"));
+      }
+    }
+    Some(out)
+  }
+
+  /// Add or replace (by term) an entry and persist the profile file.
+  pub fn upsert(&mut self, entry: GlossaryEntry) -> anyhow::Result<()> {
+    match self.entries.iter_mut().find(|e| e.term.eq_ignore_ascii_case(&entry.term)) {
+      Some(existing) => *existing = entry,
+      None => self.entries.push(entry),
+    }
+    self.save()
+  }
+
+  pub fn save(&self) -> anyhow::Result<()> {
+    let path =
+      self.path.as_ref().ok_or_else(|| anyhow!("The glossary was not loaded and is read-only"))?;
+    let file = GlossaryFile { entries: self.entries.clone() };
+    let body = toml::to_string_pretty(&file)?;
+    std::fs::write(path, body)
+      .with_context(|| format!("failed to write glossary to {}", path.display()))
+  }
+}
+
+fn read_entries(path: &Path) -> anyhow::Result<Vec<GlossaryEntry>> {
+  if !path.exists() {
+    return Ok(vec![]);
+  }
+  let raw = std::fs::read_to_string(path)
+    .with_context(|| format!("failed to read glossary {}", path.display()))?;
+  let file: GlossaryFile =
+    toml::from_str(&raw).with_context(|| format!("invalid glossary {}", path.display()))?;
+  Ok(file.entries)
+}
+
+/// Case-insensitive phrase match on word boundaries ("revenue" does not match "revenues_tmp").
+fn contains_phrase(haystack_lc: &str, phrase: &str) -> bool {
+  let phrase = phrase.trim().to_lowercase();
+  if phrase.is_empty() {
+    return false;
+  }
+  let is_word = |c: char| c.is_alphanumeric() || c == '_';
+  haystack_lc.match_indices(&phrase).any(|(i, _)| {
+    let before = haystack_lc[..i].chars().next_back();
+    let after = haystack_lc[i + phrase.len()..].chars().next();
+    !before.is_some_and(is_word) && !after.is_some_and(is_word)
+  })
+}
+
+#[test]
+fn test_matching_terms_and_aliases() {
+  let glossary = Glossary {
+    path: None,
+    entries: vec![GlossaryEntry {
+      term: "revenue".into(),
+      aliases: vec!["sales".into()],
+      kind: GlossaryKind::Metric,
+      sql: "SUM(oi.quantity * oi.price)".into(),
+      description: String::new(),
+      joins: vec!["orders o JOIN order_items oi ON oi.order_id = o.id".into()],
+    }],
+    shared: vec![GlossaryEntry {
+      term: "churned customer".into(),
+      aliases: vec![],
+      kind: GlossaryKind::Filter,
+      sql: "NOT EXISTS (SELECT 1 FROM orders o WHERE o.user_id = u.id)".into(),
+      description: String::new(),
+      joins: vec![],
+    }],
+  };
+
+  assert_eq!(glossary.matching("Total Revenue per month").len(), 1);
+  assert_eq!(glossary.matching("sales by city")[0].term, "revenue");
+  assert_eq!(glossary.matching("list every churned customer")[0].term, "churned customer");
+  assert!(glossary.matching("revenues_tmp rows").is_empty());
+
+  let prompt = Glossary::prompt_for(&glossary.matching("revenue")).unwrap();
+  assert!(prompt.contains("SUM(oi.quantity * oi.price)"));
+  assert!(prompt.contains("preferred join"));
+
+  // a glossary that was never loaded can't overwrite the file on disk
+  let mut unloaded = Glossary::default();
+  assert!(unloaded.upsert(glossary.entries[0].clone()).is_err());
+}
@@ -0,0 +1,146 @@
+use crate::config::Setting;
+use anyhow::Context;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tracing::error;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum HistoryKind {
+  /// SQL sent to Postgres from the editor
+  Executed,
+  /// SQL produced by `Agent::text_to_sql`
+  Generated,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct HistoryEntry {
+  pub kind: HistoryKind,
+  pub timestamp: DateTime<Local>,
+  #[serde(default)]
+  pub profile: Option<String>,
+  /// Empty for a generation that failed
+  #[serde(default, skip_serializing_if = "String::is_empty")]
+  pub sql: String,
+  /// Natural language question that produced the SQL, if any
+  #[serde(default)]
+  pub question: Option<String>,
+  pub duration_ms: u64,
+  #[serde(default)]
+  pub row_count: Option<usize>,
+  #[serde(default)]
+  pub error: Option<String>,
+}
+
+impl HistoryEntry {
+  /// Every whitespace separated word of the search has to appear in the SQL, question or profile.
+  pub fn matches(&self, search: &str) -> bool {
+    let haystack = format!(
+      "{} {} {}",
+      self.sql,
+      self.question.as_deref().unwrap_or(""),
+      self.profile.as_deref().unwrap_or("")
+    )
+    .to_lowercase();
+    search.to_lowercase().split_whitespace().all(|word| haystack.contains(word))
+  }
+}
+
+/// Append-only history kept as JSON lines in `history.jsonl` next to `config.toml`.
+#[derive(Debug, Clone)]
+pub struct HistoryStore {
+  pub path: PathBuf,
+  pub entries: Vec<HistoryEntry>,
+}
+
+impl Default for HistoryStore {
+  fn default() -> Self {
+    Self { path: Self::default_path(), entries: vec![] }
+  }
+}
+
+impl HistoryStore {
+  /// Keep the in-memory list bounded; the file keeps everything.
+  const MAX_LOADED: usize = 5_000;
+
+  pub fn default_path() -> PathBuf {
+    Setting::data_path("history.jsonl")
+  }
+
+  pub fn load(path: &Path) -> anyhow::Result<Self> {
+    if !path.exists() {
+      return Ok(Self { path: path.to_path_buf(), entries: vec![] });
+    }
+    let raw = std::fs::read_to_string(path)
+      .with_context(|| format!("failed to read history {}", path.display()))?;
+    let mut entries: Vec<HistoryEntry> = raw
+      .lines()
+      .filter(|l| !l.trim().is_empty())
+      .filter_map(|l| match serde_json::from_str(l) {
+        Ok(entry) => Some(entry),
+        Err(e) => {
+          error!("Skipping corrupt history line: {e}");
+          None
+        }
+      })
+      .collect();
+    if entries.len() > Self::MAX_LOADED {
+      entries.drain(..entries.len() - Self::MAX_LOADED);
+    }
+    Ok(Self { path: path.to_path_buf(), entries })
+  }
+
+  pub fn record(&mut self, entry: HistoryEntry) -> anyhow::Result<()> {
+    let line = serde_json::to_string(&entry)?;
+    let mut file = std::fs::OpenOptions::new()
+      .create(true)
+      .append(true)
+      .open(&self.path)
+      .with_context(|| format!("failed to open history {}", self.path.display()))?;
+    writeln!(file, "{line}")?;
+    self.entries.push(entry);
+    Ok(())
+  }
+
+  /// Newest first.
+  pub fn search(&self, search: &str) -> Vec<&HistoryEntry> {
+    self.entries.iter().rev().filter(|e| e.matches(search)).collect()
+  }
+}
+
+#[test]
+fn test_record_load_and_search() {
+  let path = std::env::temp_dir().join(format!("pg-admin-history-{}.jsonl", std::process::id()));
+  let _ = std::fs::remove_file(&path);
+
+  let mut store = HistoryStore::load(&path).unwrap();
+  let entry = |kind, sql: &str, question: Option<&str>| HistoryEntry {
+    kind,
+    timestamp: Local::now(),
+    profile: Some("local".into()),
+    sql: sql.into(),
+    question: question.map(Into::into),
+    duration_ms: 3,
+    row_count: Some(1),
+    error: None,
+  };
+  store.record(entry(HistoryKind::Generated, "SELECT * FROM books", Some("all books"))).unwrap();
+  store.record(entry(HistoryKind::Executed, "SELECT count(*) FROM users", None)).unwrap();
+  let failed = HistoryEntry {
+    error: Some("LLM did not converge".into()),
+    ..entry(HistoryKind::Generated, "", Some("top authors"))
+  };
+  store.record(failed).unwrap();
+  assert!(!std::fs::read_to_string(&path).unwrap().lines().last().unwrap().contains("\"sql\""));
+
+  let reloaded = HistoryStore::load(&path).unwrap();
+  assert_eq!(reloaded.entries, store.entries);
+  assert_eq!(reloaded.search("").len(), 3);
+  assert_eq!(reloaded.search("")[1].kind, HistoryKind::Executed);
+  assert_eq!(reloaded.search("ALL books")[0].sql, "SELECT * FROM books");
+  assert!(reloaded.search("users books").is_empty());
+
+  let _ = std::fs::remove_file(&path);
+}
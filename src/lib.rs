@@ -1,6 +1,23 @@
+pub mod advisor;
 pub mod agent;
+pub mod cli;
 pub mod config;
+pub mod conninfo;
 pub mod conversation;
+pub mod database;
 pub mod db_client;
+pub mod eval;
+pub mod explain;
+pub mod fixtures;
+pub mod glossary;
+pub mod history;
 pub mod llm;
+pub mod schema;
+pub mod secrets;
+pub mod sessions;
+pub mod snippets;
+pub mod sql;
+pub mod summary;
+pub mod trace;
 pub mod ui;
+pub mod workspace;
@@ -1,34 +1,131 @@
-use crate::conversation::{Conversation, LlmResponse};
-use anyhow::Result;
+use crate::config::LlmConfig;
+use crate::conversation::{ChatMessage, Conversation, LlmResponse};
+use crate::fixtures::Fixture;
+use anyhow::{Result, anyhow};
 use reqwest::Client;
 
-pub async fn send_request(client: &Client, conv: &Conversation) -> Result<LlmResponse> {
+/// Asks the LLM of `config` for the next text-to-SQL reply in `conv`.
+pub async fn send_request(
+  client: &Client,
+  config: &LlmConfig,
+  conv: &Conversation,
+) -> Result<LlmResponse> {
+  let content = complete(client, config, &conv.llm_messages()).await?;
+  let parsed: LlmResponse = serde_json::from_str(clean_json(&content))?;
+  Ok(parsed)
+}
+
+/// Sends `messages` to the configured chat completions endpoint and returns the raw reply text.
+pub async fn complete(
+  client: &Client,
+  config: &LlmConfig,
+  messages: &[ChatMessage],
+) -> Result<String> {
   #[derive(serde::Serialize)]
   struct Request<'a> {
     model: &'a str,
-    temperature: f32,
-    messages: &'a [crate::conversation::ChatMessage],
+    temperature: f64,
+    messages: &'a [ChatMessage],
   }
 
-  let req =
-    Request { model: "deepseek-coder-v2:latest", temperature: 0.0, messages: &conv.messages };
-
-  let resp = client
-    .post("http://localhost:1234/v1/chat/completions")
+  let req = Request { model: &config.model, temperature: config.temperature, messages };
+  let url = format!("{}/chat/completions", config.api_url.trim_end_matches('/'));
+  let resp = authorized(client.post(url), config)
     .json(&req)
     .send()
     .await?
     .json::<serde_json::Value>()
     .await?;
 
-  let content = resp["choices"][0]["message"]["content"].as_str().unwrap_or("");
-  let cleaned = clean_json(content);
-  let parsed: LlmResponse = serde_json::from_str(cleaned)?;
-  //debug!("Parsed LLM response: {:?}", parsed);
+  let content = resp["choices"][0]["message"]["content"]
+    .as_str()
+    .map(str::to_string)
+    .ok_or_else(|| anyhow!("LLM reply has no content: {resp}"))?;
+  let recorded = config.record_to.as_ref().map(|path| Fixture::record(path, messages, &content));
+  if let Some(Err(e)) = recorded {
+    tracing::error!("Unable to record LLM fixture: {e:?}");
+  }
+  Ok(content)
+}
 
-  Ok(parsed)
+fn authorized(request: reqwest::RequestBuilder, config: &LlmConfig) -> reqwest::RequestBuilder {
+  match config.api_key.as_deref().filter(|key| !key.is_empty()) {
+    Some(key) => request.bearer_auth(key),
+    None => request,
+  }
+}
+
+/// Models offered by the server of `config`: the OpenAI-style `{api_url}/models`, or Ollama's
+/// `/api/tags` when that is not available.
+pub async fn list_models(client: &Client, config: &LlmConfig) -> Result<Vec<String>> {
+  let api_url = config.api_url.trim_end_matches('/');
+  let openai = async {
+    let resp = authorized(client.get(format!("{api_url}/models")), config)
+      .send()
+      .await?
+      .error_for_status()?
+      .json::<serde_json::Value>()
+      .await?;
+    let models = resp["data"].as_array().ok_or_else(|| anyhow!("unexpected reply: {resp}"))?;
+    anyhow::Ok(models.iter().filter_map(|m| m["id"].as_str().map(str::to_string)).collect())
+  };
+  let error = match openai.await {
+    Ok(models) => return Ok(models),
+    Err(e) => e,
+  };
+
+  let base = api_url.trim_end_matches("/v1");
+  let resp = authorized(client.get(format!("{base}/api/tags")), config)
+    .send()
+    .await
+    .and_then(reqwest::Response::error_for_status)
+    .map_err(|_| anyhow!("Unable to list models: {error}"))?
+    .json::<serde_json::Value>()
+    .await?;
+  let models = resp["models"].as_array().ok_or_else(|| anyhow!("unexpected reply: {resp}"))?;
+  Ok(models.iter().filter_map(|m| m["name"].as_str().map(str::to_string)).collect())
+}
+
+/// Rough token count for budgeting prompts (about four characters per token).
+pub fn estimate_tokens(text: &str) -> usize {
+  text.chars().count().div_ceil(4)
 }
 
 pub fn clean_json(raw: &str) -> &str {
   raw.trim().trim_start_matches("```json").trim_start_matches("```").trim_end_matches("```").trim()
 }
+
+#[tokio::test]
+async fn test_list_models_and_api_key() {
+  use httpmock::prelude::*;
+
+  let openai = MockServer::start_async().await;
+  openai
+    .mock_async(|when, then| {
+      when.method(GET).path("/v1/models").header("authorization", "Bearer secret");
+      then.status(200).json_body(serde_json::json!({
+        "object": "list",
+        "data": [{ "id": "gpt-4o-mini" }, { "id": "gpt-4o" }]
+      }));
+    })
+    .await;
+  let mut config = LlmConfig::new(&openai.url("/v1"), "gpt-4o");
+  config.api_key = Some("secret".into());
+  let client = Client::new();
+  assert_eq!(list_models(&client, &config).await.unwrap(), ["gpt-4o-mini", "gpt-4o"]);
+
+  let ollama = MockServer::start_async().await;
+  ollama
+    .mock_async(|when, then| {
+      when.method(GET).path("/api/tags");
+      then.status(200).json_body(serde_json::json!({
+        "models": [{ "name": "llama3:latest" }, { "name": "qwen2.5-coder:7b" }]
+      }));
+    })
+    .await;
+  let config = LlmConfig::new(&ollama.url("/v1"), "llama3");
+  assert_eq!(list_models(&client, &config).await.unwrap(), ["llama3:latest", "qwen2.5-coder:7b"]);
+
+  let config = LlmConfig::new(&ollama.url("/nothing"), "llama3");
+  assert!(list_models(&client, &config).await.is_err());
+}
@@ -1,13 +1,29 @@
+use clap::Parser;
 use freya::launch::launch_cfg;
 use freya::prelude::{LaunchConfig, WindowConfig};
 use pg_admin::agent::{AGENT, Agent};
+use pg_admin::cli::{self, Cli};
 use pg_admin::config::Setting;
 use pg_admin::db_client::DbClient;
+use pg_admin::glossary::Glossary;
+use pg_admin::secrets::resolve_in_background;
 use pg_admin::ui::app::app;
 use tokio::sync::RwLock;
-use tracing::debug;
+use tracing::{debug, error};
 
 fn main() {
+  let cli = Cli::parse();
+  if cli.command.is_some() {
+    // keep stdout for results
+    tracing_subscriber::fmt().with_env_filter("pg_admin=warn").with_writer(std::io::stderr).init();
+    let rt = tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap();
+    if let Err(e) = rt.block_on(cli::run(cli)) {
+      eprintln!("error: {e:#}");
+      std::process::exit(1);
+    }
+    return;
+  }
+
   tracing_subscriber::fmt().with_env_filter("pg_admin=debug").init();
 
   // Starting a custom tokio runtime, otherwise freya starts a runtime
@@ -15,17 +31,42 @@ fn main() {
   let rt = tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap();
   let _guard = rt.enter();
 
-  let agent = Agent { db_client: DbClient::new(), llm_client: RwLock::new(None) };
+  let agent = Agent {
+    db_client: DbClient::new(),
+    llm_client: RwLock::new(None),
+    glossary: RwLock::new(Glossary::default()),
+    active_profile: RwLock::new(None),
+  };
   AGENT.set(agent).unwrap();
 
   match Setting::try_load() {
-    Ok(cfg) => {
+    Ok(mut cfg) => {
+      // --profile and --llm pick the profiles the app starts with; config.toml is left as is
+      if let Err(e) = cli::select_profiles(&cli, &mut cfg) {
+        eprintln!("error: {e:#}");
+        std::process::exit(1);
+      }
       debug!("Loaded config: {:?}", cfg);
       let pg_config = cfg.active_postgres().cloned().unwrap();
       tokio::spawn(async move {
         let agent = AGENT.get().unwrap();
-        agent.db_client.setup_db_client(Some(pg_config)).await;
+        let name = cfg.active_postgres.clone();
+        // the glossary is loaded even when connecting fails, so saving to it doesn't start over
+        if let Err(e) = agent.load_glossary(&name).await {
+          error!("Unable to load the glossary of '{name}': {e:?}");
+        }
+        match agent.db_client.setup_db_client(Some(pg_config)).await {
+          Ok(_) => *agent.active_profile.write().await = Some(name),
+          Err(e) => error!("Unable to connect to '{name}': {e:?}"),
+        }
         let llm_config = cfg.active_llm().cloned().unwrap();
+        let llm_config = match resolve_in_background(move || llm_config.resolve_secrets()).await {
+          Ok(config) => config,
+          Err(e) => {
+            error!("Unable to resolve the LLM API key: {e:?}");
+            cfg.active_llm().cloned().unwrap()
+          }
+        };
         let mut llm_client_guard = agent.llm_client.write().await;
         *llm_client_guard = Some(llm_config);
       });
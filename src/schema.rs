@@ -0,0 +1,143 @@
+use serde::{Deserialize, Serialize};
+
+/// Cached picture of the database used for completion and prompts.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct SchemaModel {
+  pub tables: Vec<TableInfo>,
+  pub foreign_keys: Vec<ForeignKey>,
+  /// User defined functions plus common built-ins
+  pub functions: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TableInfo {
+  pub schema: String,
+  pub name: String,
+  pub columns: Vec<ColumnInfo>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ColumnInfo {
+  pub name: String,
+  pub data_type: String,
+}
+
+/// An existing index, as reported by `pg_indexes`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct IndexInfo {
+  pub schema: String,
+  pub table: String,
+  pub name: String,
+  /// `CREATE INDEX ...` statement
+  pub definition: String,
+}
+
+/// `table.columns` references `ref_table.ref_columns`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ForeignKey {
+  pub schema: String,
+  pub table: String,
+  pub columns: Vec<String>,
+  pub ref_schema: String,
+  pub ref_table: String,
+  pub ref_columns: Vec<String>,
+}
+
+/// Built-in functions offered by completion next to the user defined ones.
+pub static BUILTIN_FUNCTIONS: &[&str] = &[
+  "array_agg",
+  "avg",
+  "coalesce",
+  "concat",
+  "count",
+  "current_date",
+  "date_part",
+  "date_trunc",
+  "extract",
+  "greatest",
+  "json_agg",
+  "jsonb_build_object",
+  "least",
+  "length",
+  "lower",
+  "max",
+  "min",
+  "now",
+  "nullif",
+  "row_number",
+  "string_agg",
+  "substring",
+  "sum",
+  "to_char",
+  "trim",
+  "upper",
+];
+
+impl TableInfo {
+  pub fn qualified_name(&self) -> String {
+    format!("{}.{}", self.schema, self.name)
+  }
+}
+
+impl SchemaModel {
+  pub fn schemas(&self) -> Vec<&str> {
+    let mut schemas: Vec<&str> = self.tables.iter().map(|t| t.schema.as_str()).collect();
+    schemas.sort();
+    schemas.dedup();
+    schemas
+  }
+
+  /// Looks a table up by `name` or `schema.name`, case-insensitively. Unqualified names prefer
+  /// the `public` schema.
+  pub fn table(&self, name: &str) -> Option<&TableInfo> {
+    let name = name.trim_matches('"').to_lowercase();
+    match name.split_once('.') {
+      Some((schema, table)) => self.tables.iter().find(|t| {
+        t.schema.to_lowercase() == schema.trim_matches('"')
+          && t.name.to_lowercase() == table.trim_matches('"')
+      }),
+      None => self
+        .tables
+        .iter()
+        .filter(|t| t.name.to_lowercase() == name)
+        .min_by_key(|t| t.schema != "public"),
+    }
+  }
+
+  pub fn tables_in_schema(&self, schema: &str) -> Vec<&TableInfo> {
+    self.tables.iter().filter(|t| t.schema.eq_ignore_ascii_case(schema)).collect()
+  }
+
+  /// Foreign keys between two tables, in either direction.
+  pub fn foreign_keys_between(&self, a: &TableInfo, b: &TableInfo) -> Vec<&ForeignKey> {
+    let is = |schema: &str, table: &str, t: &TableInfo| t.schema == schema && t.name == table;
+    self
+      .foreign_keys
+      .iter()
+      .filter(|fk| {
+        (is(&fk.schema, &fk.table, a) && is(&fk.ref_schema, &fk.ref_table, b))
+          || (is(&fk.schema, &fk.table, b) && is(&fk.ref_schema, &fk.ref_table, a))
+      })
+      .collect()
+  }
+
+  /// Tables reachable from `table` through one foreign key.
+  pub fn related_tables(&self, table: &TableInfo) -> Vec<&TableInfo> {
+    let mut related: Vec<&TableInfo> = Vec::new();
+    for fk in &self.foreign_keys {
+      let other = if fk.schema == table.schema && fk.table == table.name {
+        self.table(&format!("{}.{}", fk.ref_schema, fk.ref_table))
+      } else if fk.ref_schema == table.schema && fk.ref_table == table.name {
+        self.table(&format!("{}.{}", fk.schema, fk.table))
+      } else {
+        None
+      };
+      if let Some(other) =
+        other.filter(|o| !related.iter().any(|t| t.qualified_name() == o.qualified_name()))
+      {
+        related.push(other);
+      }
+    }
+    related
+  }
+}
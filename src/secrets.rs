@@ -0,0 +1,195 @@
+use crate::config::Setting;
+use age::secrecy::SecretString;
+use anyhow::{Context, anyhow};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+/// Environment variable with the passphrase of the secrets file.
+pub const PASSPHRASE_ENV: &str = "PG_ADMIN_SECRETS_PASSPHRASE";
+
+/// Where a password or API key comes from when it is not written in config.toml, e.g.
+/// `password_from = { command = "pass show db/prod" }`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SecretSource {
+  /// Environment variable holding the secret
+  Env(String),
+  /// Shell command printing the secret
+  Command(String),
+  /// Key in the encrypted secrets file, see [`SecretStore`]
+  Secret(String),
+}
+
+impl SecretSource {
+  pub fn resolve(&self) -> anyhow::Result<String> {
+    match self {
+      SecretSource::Env(name) => {
+        std::env::var(name).map_err(|_| anyhow!("Environment variable {name} is not set"))
+      }
+      SecretSource::Command(command) => run_command(command),
+      SecretSource::Secret(key) => SecretStore::default()
+        .load_with_env_passphrase()?
+        .remove(key)
+        .ok_or_else(|| anyhow!("No secret named '{key}' in the secrets file")),
+    }
+  }
+}
+
+impl std::fmt::Display for SecretSource {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      SecretSource::Env(name) => write!(f, "${name}"),
+      SecretSource::Command(command) => write!(f, "`{command}`"),
+      SecretSource::Secret(key) => write!(f, "secret '{key}'"),
+    }
+  }
+}
+
+/// Runs `resolve` on tokio's blocking pool. A secret command such as `pass show` may wait for a
+/// passphrase prompt, which must stall neither the UI thread nor an async worker.
+pub async fn resolve_in_background<T: Send + 'static>(
+  resolve: impl FnOnce() -> anyhow::Result<T> + Send + 'static,
+) -> anyhow::Result<T> {
+  tokio::task::spawn_blocking(resolve).await.context("secret lookup did not finish")?
+}
+
+/// Runs `command` with the platform shell and returns its output without the trailing newline.
+fn run_command(command: &str) -> anyhow::Result<String> {
+  let output = if cfg!(windows) {
+    std::process::Command::new("cmd").args(["/C", command]).output()
+  } else {
+    std::process::Command::new("sh").args(["-c", command]).output()
+  }
+  .with_context(|| format!("failed to run `{command}`"))?;
+  if !output.status.success() {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    return Err(anyhow!("`{command}` failed with {}: {}", output.status, stderr.trim()));
+  }
+  let stdout = String::from_utf8(output.stdout).context("secret command printed invalid UTF-8")?;
+  Ok(stdout.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// Password-file entries as libpq reads them: `host:port:database:username:password`, `*`
+/// matching anything and `\` escaping `:` and `\`.
+fn pgpass_lookup(
+  contents: &str,
+  host: &str,
+  port: u16,
+  dbname: &str,
+  user: &str,
+) -> Option<String> {
+  let port = port.to_string();
+  contents.lines().filter(|line| !line.trim_start().starts_with('#')).find_map(|line| {
+    let mut fields = vec![String::new()];
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+      match c {
+        '\\' => fields.last_mut()?.extend(chars.next()),
+        ':' if fields.len() < 5 => fields.push(String::new()),
+        c => fields.last_mut()?.push(c),
+      }
+    }
+    let [h, p, d, u, password] = <[String; 5]>::try_from(fields).ok()?;
+    let matches = |pattern: &str, value: &str| pattern == "*" || pattern == value;
+    (matches(&h, host) && matches(&p, &port) && matches(&d, dbname) && matches(&u, user))
+      .then_some(password)
+  })
+}
+
+fn pgpass_path() -> Option<PathBuf> {
+  if let Ok(path) = std::env::var("PGPASSFILE") {
+    return Some(PathBuf::from(path));
+  }
+  if cfg!(windows) {
+    std::env::var("APPDATA").ok().map(|dir| Path::new(&dir).join("postgresql/pgpass.conf"))
+  } else {
+    std::env::var("HOME").ok().map(|dir| Path::new(&dir).join(".pgpass"))
+  }
+}
+
+/// Password for the connection from the libpq password file (`PGPASSFILE` or `~/.pgpass`). Like
+/// libpq, a file others can read is ignored on Unix.
+pub fn pgpass_password(host: &str, port: u16, dbname: &str, user: &str) -> Option<String> {
+  let path = pgpass_path()?;
+  #[cfg(unix)]
+  {
+    use std::os::unix::fs::PermissionsExt;
+    let mode = std::fs::metadata(&path).ok()?.permissions().mode();
+    if mode & 0o077 != 0 {
+      warn!("Ignoring {}: it must not be readable by group or others (chmod 0600)", path.display());
+      return None;
+    }
+  }
+  let contents = std::fs::read_to_string(&path).ok()?;
+  pgpass_lookup(&contents, host, port, dbname, user)
+}
+
+/// Named secrets in a TOML table, encrypted with a passphrase in the age format. The file can
+/// also be written with `age -p`.
+#[derive(Debug, Clone)]
+pub struct SecretStore {
+  pub path: PathBuf,
+}
+
+impl Default for SecretStore {
+  fn default() -> Self {
+    Self { path: Setting::data_path("secrets.toml.age") }
+  }
+}
+
+impl SecretStore {
+  pub fn load(&self, passphrase: &str) -> anyhow::Result<BTreeMap<String, String>> {
+    let encrypted = std::fs::read(&self.path)
+      .with_context(|| format!("failed to read secrets file {}", self.path.display()))?;
+    let identity = age::scrypt::Identity::new(SecretString::from(passphrase.to_string()));
+    let plain = age::decrypt(&identity, &encrypted)
+      .with_context(|| format!("unable to decrypt {}", self.path.display()))?;
+    let raw = String::from_utf8(plain).context("secrets file is not UTF-8")?;
+    toml::from_str(&raw).with_context(|| format!("invalid secrets in {}", self.path.display()))
+  }
+
+  /// Reads the file with the passphrase from [`PASSPHRASE_ENV`].
+  pub fn load_with_env_passphrase(&self) -> anyhow::Result<BTreeMap<String, String>> {
+    let passphrase = std::env::var(PASSPHRASE_ENV)
+      .map_err(|_| anyhow!("Set {PASSPHRASE_ENV} to read {}", self.path.display()))?;
+    self.load(&passphrase)
+  }
+
+  pub fn save(&self, passphrase: &str, secrets: &BTreeMap<String, String>) -> anyhow::Result<()> {
+    let recipient = age::scrypt::Recipient::new(SecretString::from(passphrase.to_string()));
+    let encrypted = age::encrypt(&recipient, toml::to_string(secrets)?.as_bytes())?;
+    std::fs::write(&self.path, encrypted)
+      .with_context(|| format!("failed to write secrets file {}", self.path.display()))
+  }
+}
+
+#[test]
+fn test_secret_sources() {
+  let pgpass = "# comment\n\
+    db.internal:5432:sales:report:s3cr\\:et\n\
+    *:*:*:postgres:fallback\n";
+  assert_eq!(pgpass_lookup(pgpass, "db.internal", 5432, "sales", "report").unwrap(), "s3cr:et");
+  assert_eq!(pgpass_lookup(pgpass, "localhost", 5433, "app", "postgres").unwrap(), "fallback");
+  assert!(pgpass_lookup(pgpass, "db.internal", 5432, "hr", "report").is_none());
+
+  assert_eq!(SecretSource::Command("echo hunter2".into()).resolve().unwrap(), "hunter2");
+  assert!(SecretSource::Command("exit 3".into()).resolve().is_err());
+  assert!(SecretSource::Env("PG_ADMIN_TEST_UNSET_SECRET".into()).resolve().is_err());
+  let source: SecretSource = toml::from_str::<toml::Table>(r#"from = { env = "PGPASSWORD" }"#)
+    .unwrap()["from"]
+    .clone()
+    .try_into()
+    .unwrap();
+  assert_eq!(source, SecretSource::Env("PGPASSWORD".into()));
+
+  let path = std::env::temp_dir().join(format!("pg_admin_secrets_{}.age", std::process::id()));
+  let store = SecretStore { path: path.clone() };
+  let secrets = BTreeMap::from([("prod".to_string(), "pa:ss".to_string())]);
+  store.save("correct horse", &secrets).unwrap();
+  assert!(!std::fs::read(&path).unwrap().windows(5).any(|w| w == b"pa:ss"));
+  assert_eq!(store.load("correct horse").unwrap(), secrets);
+  assert!(store.load("wrong").is_err());
+  let _ = std::fs::remove_file(&path);
+}
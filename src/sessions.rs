@@ -0,0 +1,206 @@
+use crate::config::Setting;
+use crate::conversation::{ANSWER_ROLE, Conversation, LlmResponse};
+use crate::history::HistoryKind;
+use anyhow::Context;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Counter that keeps ids of sessions created in the same instant apart
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// A named chat, stored as `sessions/<id>.json` next to `config.toml`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+  /// File name stem; the display name can repeat, the id doesn't
+  #[serde(default)]
+  pub id: String,
+  pub name: String,
+  pub created: DateTime<Local>,
+  pub updated: DateTime<Local>,
+  pub conversation: Conversation,
+}
+
+impl Session {
+  pub fn new(name: &str) -> Self {
+    let now = Local::now();
+    let id =
+      format!("{}-{}", now.format("%Y%m%d-%H%M%S-%f"), NEXT_ID.fetch_add(1, Ordering::Relaxed));
+    Self { id, name: name.into(), created: now, updated: now, conversation: Conversation::new() }
+  }
+
+  /// New session named after the current time.
+  pub fn untitled() -> Self {
+    Self::new(&format!("Session {}", Local::now().format("%Y-%m-%d %H:%M")))
+  }
+
+  /// Markdown transcript: the questions and answers, then every generated and executed query.
+  /// System prompts are left out.
+  pub fn to_markdown(&self) -> String {
+    let mut md =
+      format!("# {}\n\n_Created {}_\n\n", self.name, self.created.format("%Y-%m-%d %H:%M"));
+
+    md.push_str("## Conversation\n\n");
+    for message in self.conversation.messages.iter().filter(|m| m.role != "system") {
+      let content = match message.role.as_str() {
+        // assistant turns are the raw JSON replies; show what they say instead
+        "assistant" => match serde_json::from_str::<LlmResponse>(&message.content) {
+          Ok(reply) if !reply.sql.trim().is_empty() => {
+            format!("{}\n\n```sql\n{}\n```", reply.explanation.trim(), reply.sql.trim())
+          }
+          Ok(reply) => format!("{}\n\n> {}", reply.explanation.trim(), reply.clarification.trim()),
+          Err(_) => message.content.clone(),
+        },
+        _ => message.content.clone(),
+      };
+      let speaker = match message.role.as_str() {
+        "user" => "User",
+        "assistant" => "Assistant",
+        ANSWER_ROLE => "Answer",
+        other => other,
+      };
+      md.push_str(&format!("**{speaker}:** {}\n\n", content.trim()));
+    }
+
+    if !self.conversation.queries.is_empty() {
+      md.push_str("## Queries\n\n");
+      for query in self.conversation.queries.iter() {
+        let kind = match query.kind {
+          HistoryKind::Generated => "Generated",
+          HistoryKind::Executed => "Executed",
+        };
+        md.push_str(&format!(
+          "{kind} at {}:\n\n```sql\n{}\n```\n\n",
+          query.timestamp.format("%Y-%m-%d %H:%M:%S"),
+          query.sql.trim()
+        ));
+      }
+    }
+    md
+  }
+}
+
+/// Directory of saved sessions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionStore {
+  pub dir: PathBuf,
+}
+
+impl Default for SessionStore {
+  fn default() -> Self {
+    Self { dir: Setting::data_path("sessions") }
+  }
+}
+
+impl SessionStore {
+  pub fn path_of(&self, session: &Session) -> PathBuf {
+    self.dir.join(format!("{}.json", session.id))
+  }
+
+  /// All sessions, most recently updated first. Unreadable files are skipped.
+  pub fn list(&self) -> anyhow::Result<Vec<Session>> {
+    if !self.dir.exists() {
+      return Ok(vec![]);
+    }
+    let mut sessions: Vec<Session> = std::fs::read_dir(&self.dir)
+      .with_context(|| format!("reading {}", self.dir.display()))?
+      .filter_map(|entry| entry.ok().map(|e| e.path()))
+      .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+      .filter_map(|path| match Self::load(&path) {
+        Ok(session) => Some(session),
+        Err(e) => {
+          tracing::error!("Skipping session {}: {e:?}", path.display());
+          None
+        }
+      })
+      .collect();
+    sessions.sort_by_key(|s| std::cmp::Reverse(s.updated));
+    Ok(sessions)
+  }
+
+  pub fn load(path: &Path) -> anyhow::Result<Session> {
+    let content =
+      std::fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+    let mut session: Session =
+      serde_json::from_str(&content).with_context(|| format!("parsing {}", path.display()))?;
+    // sessions saved before ids were named after their name
+    if session.id.is_empty() {
+      session.id = path.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+    }
+    Ok(session)
+  }
+
+  pub fn save(&self, session: &mut Session) -> anyhow::Result<()> {
+    std::fs::create_dir_all(&self.dir)?;
+    session.updated = Local::now();
+    std::fs::write(self.path_of(session), serde_json::to_string_pretty(session)?)?;
+    Ok(())
+  }
+
+  pub fn delete(&self, session: &Session) -> anyhow::Result<()> {
+    std::fs::remove_file(self.path_of(session))?;
+    Ok(())
+  }
+
+  /// Writes the Markdown transcript next to the session file and returns its path.
+  pub fn export_markdown(&self, session: &Session) -> anyhow::Result<PathBuf> {
+    std::fs::create_dir_all(&self.dir)?;
+    let path = self.dir.join(format!("{}.md", session.id));
+    std::fs::write(&path, session.to_markdown())?;
+    Ok(path)
+  }
+}
+
+#[test]
+fn test_sessions_roundtrip_and_export() {
+  let dir = std::env::temp_dir().join(format!("pg_admin_sessions_{}", std::process::id()));
+  let _ = std::fs::remove_dir_all(&dir);
+  let store = SessionStore { dir: dir.clone() };
+  assert!(store.list().unwrap().is_empty());
+
+  let mut session = Session::new("Revenue by city!");
+  session.conversation.add_system("instructions");
+  session.conversation.add_user("revenue by city");
+  session.conversation.add_assistant(
+    r#"{"explanation": "Sum per city", "sql": "SELECT city, sum(total) FROM orders GROUP BY city", "clarification": ""}"#,
+  );
+  session.conversation.remember_table_columns("orders", vec!["city".into()]);
+  session
+    .conversation
+    .record_query(HistoryKind::Executed, "SELECT city, sum(total) FROM orders GROUP BY city");
+  store.save(&mut session).unwrap();
+  store.save(&mut Session::new("Other")).unwrap();
+  // sessions created in the same minute share a name but not a file
+  let (mut first, mut second) = (Session::untitled(), Session::untitled());
+  assert_ne!(store.path_of(&first), store.path_of(&second));
+  store.save(&mut first).unwrap();
+  store.save(&mut second).unwrap();
+
+  let sessions = store.list().unwrap();
+  assert_eq!(sessions.len(), 4);
+  assert_eq!(sessions[0].id, second.id);
+  let loaded = sessions.iter().find(|s| s.name == "Revenue by city!").unwrap();
+  assert!(loaded.conversation.known_tables["orders"].contains("city"));
+
+  let md = std::fs::read_to_string(store.export_markdown(loaded).unwrap()).unwrap();
+  assert!(md.starts_with("# Revenue by city!"));
+  assert!(!md.contains("instructions"));
+  assert!(md.contains("**User:** revenue by city"));
+  assert!(md.contains("**Assistant:** Sum per city\n\n```sql\nSELECT city"));
+  assert!(md.contains("Executed at "));
+
+  // a session saved before ids keeps its file
+  let created = "2025-01-01T00:00:00+00:00";
+  let old_json = format!(
+    r#"{{"name": "Old chat", "created": "{created}", "updated": "{created}", "conversation": {{}}}}"#
+  );
+  std::fs::write(dir.join("old-chat.json"), old_json).unwrap();
+  let old = store.list().unwrap().into_iter().find(|s| s.name == "Old chat").unwrap();
+  assert_eq!(store.path_of(&old), dir.join("old-chat.json"));
+  store.delete(&old).unwrap();
+
+  store.delete(loaded).unwrap();
+  assert_eq!(store.list().unwrap().len(), 3);
+  let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,193 @@
+use crate::config::Setting;
+use crate::sql::tokenizer::{Token, TokenKind, tokenize};
+use anyhow::{Context, anyhow};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Named, tagged SQL with optional `{{variable}}` placeholders.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SavedQuery {
+  pub name: String,
+  #[serde(default)]
+  pub tags: Vec<String>,
+  #[serde(default)]
+  pub description: String,
+  pub sql: String,
+}
+
+impl SavedQuery {
+  pub fn placeholders(&self) -> Vec<String> {
+    placeholders(&self.sql)
+  }
+
+  pub fn matches(&self, search: &str) -> bool {
+    let search = search.trim().to_lowercase();
+    search.is_empty()
+      || self.name.to_lowercase().contains(&search)
+      || self.description.to_lowercase().contains(&search)
+      || self
+        .tags
+        .iter()
+        .any(|t| t.to_lowercase() == search || format!("#{}", t.to_lowercase()) == search)
+  }
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+struct SnippetFile {
+  #[serde(default)]
+  queries: Vec<SavedQuery>,
+}
+
+/// Saved queries of one Postgres profile, stored in `snippets.<profile>.toml` next to
+/// `config.toml`.
+#[derive(Debug, Clone)]
+pub struct SnippetLibrary {
+  pub path: PathBuf,
+  pub queries: Vec<SavedQuery>,
+}
+
+impl Default for SnippetLibrary {
+  fn default() -> Self {
+    Self { path: Self::path_for(None), queries: vec![] }
+  }
+}
+
+impl SnippetLibrary {
+  pub fn path_for(profile: Option<&str>) -> PathBuf {
+    match profile {
+      Some(profile) => Setting::data_path(&format!("snippets.{profile}.toml")),
+      None => Setting::data_path("snippets.toml"),
+    }
+  }
+
+  pub fn load(path: &Path) -> anyhow::Result<Self> {
+    if !path.exists() {
+      return Ok(Self { path: path.to_path_buf(), queries: vec![] });
+    }
+    let raw = std::fs::read_to_string(path)
+      .with_context(|| format!("failed to read snippets {}", path.display()))?;
+    let file: SnippetFile =
+      toml::from_str(&raw).with_context(|| format!("invalid snippets {}", path.display()))?;
+    Ok(Self { path: path.to_path_buf(), queries: file.queries })
+  }
+
+  pub fn save(&self) -> anyhow::Result<()> {
+    let body = toml::to_string_pretty(&SnippetFile { queries: self.queries.clone() })?;
+    std::fs::write(&self.path, body)
+      .with_context(|| format!("failed to write snippets to {}", self.path.display()))
+  }
+
+  /// Add or replace (by name) a saved query and persist the library.
+  pub fn upsert(&mut self, query: SavedQuery) -> anyhow::Result<()> {
+    match self.queries.iter_mut().find(|q| q.name == query.name) {
+      Some(existing) => *existing = query,
+      None => self.queries.push(query),
+    }
+    self.queries.sort_by_key(|q| q.name.to_lowercase());
+    self.save()
+  }
+
+  pub fn remove(&mut self, name: &str) -> anyhow::Result<()> {
+    self.queries.retain(|q| q.name != name);
+    self.save()
+  }
+
+  pub fn search(&self, search: &str) -> Vec<&SavedQuery> {
+    self.queries.iter().filter(|q| q.matches(search)).collect()
+  }
+}
+
+/// Unique `{{variable}}` names in order of first appearance.
+pub fn placeholders(sql: &str) -> Vec<String> {
+  let mut names: Vec<String> = Vec::new();
+  let mut rest = sql;
+  while let Some(start) = rest.find("{{") {
+    let Some(end) = rest[start + 2..].find("}}") else {
+      break;
+    };
+    let name = rest[start + 2..start + 2 + end].trim();
+    if !name.is_empty() && !names.iter().any(|n| n == name) {
+      names.push(name.to_string());
+    }
+    rest = &rest[start + 2 + end + 2..];
+  }
+  names
+}
+
+/// Replace every `{{variable}}` with its value. A value inside a string literal
+/// (`WHERE email = '{{email}}'`) has its quotes doubled, and its backslashes too in `E'...'`, so it
+/// can't end the string; elsewhere values are inserted verbatim.
+pub fn render(sql: &str, values: &HashMap<String, String>) -> anyhow::Result<String> {
+  let strings: Vec<Token> =
+    tokenize(sql).into_iter().filter(|t| t.kind == TokenKind::String).collect();
+  let mut out = String::with_capacity(sql.len());
+  let mut pos = 0;
+  while let Some(start) = sql[pos..].find("{{").map(|i| pos + i) {
+    let Some(end) = sql[start + 2..].find("}}").map(|i| start + 2 + i) else {
+      break;
+    };
+    let name = sql[start + 2..end].trim();
+    let value = values.get(name).ok_or_else(|| anyhow!("Missing value for '{{{{{name}}}}}'"))?;
+    out.push_str(&sql[pos..start]);
+    match strings.iter().find(|t| t.start < start && end < t.end) {
+      Some(t) if t.text(sql).starts_with(['E', 'e']) => {
+        out.push_str(&value.replace('\\', "\\\\").replace('\'', "''"))
+      }
+      Some(_) => out.push_str(&value.replace('\'', "''")),
+      None => out.push_str(value),
+    }
+    pos = end + 2;
+  }
+  out.push_str(&sql[pos..]);
+  Ok(out)
+}
+
+#[test]
+fn test_placeholders_and_render() {
+  let sql = "SELECT * FROM orders WHERE user_id = {{ user_id }} AND status = '{{status}}' \
+             OR user_id = {{user_id}}";
+  assert_eq!(placeholders(sql), vec!["user_id".to_string(), "status".to_string()]);
+
+  let mut values = HashMap::new();
+  values.insert("user_id".to_string(), "7".to_string());
+  assert!(render(sql, &values).unwrap_err().to_string().contains("{{status}}"));
+
+  values.insert("status".to_string(), "paid".to_string());
+  assert_eq!(
+    render(sql, &values).unwrap(),
+    "SELECT * FROM orders WHERE user_id = 7 AND status = 'paid' OR user_id = 7"
+  );
+
+  let mut values = HashMap::new();
+  values.insert("name".to_string(), r"O'Brien \".to_string());
+  assert_eq!(
+    render("SELECT '{{name}}', E'{{name}}', {{name}}", &values).unwrap(),
+    r"SELECT 'O''Brien \', E'O''Brien \\', O'Brien \"
+  );
+}
+
+#[test]
+fn test_search_by_name_and_tag() {
+  let lib = SnippetLibrary {
+    path: PathBuf::from("snippets.toml"),
+    queries: vec![
+      SavedQuery {
+        name: "Orders of user".into(),
+        tags: vec!["orders".into()],
+        description: String::new(),
+        sql: "SELECT 1".into(),
+      },
+      SavedQuery {
+        name: "Bloat".into(),
+        tags: vec!["Maintenance".into()],
+        description: "table bloat estimate".into(),
+        sql: "SELECT 2".into(),
+      },
+    ],
+  };
+  assert_eq!(lib.search("").len(), 2);
+  assert_eq!(lib.search("#maintenance")[0].name, "Bloat");
+  assert_eq!(lib.search("#MAINTENANCE")[0].name, "Bloat");
+  assert_eq!(lib.search("user")[0].name, "Orders of user");
+}
@@ -0,0 +1,447 @@
+use crate::schema::{SchemaModel, TableInfo};
+use crate::sql::tokenizer::{KEYWORDS, Token, TokenKind, is_keyword, is_unterminated, tokenize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CompletionKind {
+  Join,
+  Column,
+  Table,
+  Schema,
+  Function,
+  Keyword,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Completion {
+  pub kind: CompletionKind,
+  pub label: String,
+  /// Text replacing the word under the cursor
+  pub insert: String,
+  /// Extra info shown next to the label, e.g. a column's data type
+  pub detail: String,
+}
+
+/// Completions for the word ending at the cursor. `start..end` are char indices of that word
+/// in the text, i.e. the range an accepted completion replaces.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Completions {
+  pub start: usize,
+  pub end: usize,
+  pub items: Vec<Completion>,
+}
+
+/// A table referenced in the current statement, with its alias if any.
+#[derive(Debug, Clone, PartialEq)]
+struct TableRef<'a> {
+  table: &'a TableInfo,
+  alias: Option<String>,
+}
+
+impl TableRef<'_> {
+  fn qualifier(&self) -> &str {
+    self.alias.as_deref().unwrap_or(&self.table.name)
+  }
+}
+
+const MAX_ITEMS: usize = 50;
+
+fn is_ident_char(c: char) -> bool {
+  c.is_alphanumeric() || c == '_' || c == '$'
+}
+
+/// Completions at `cursor` (a char index, as reported by the editor).
+pub fn complete(sql: &str, cursor: usize, schema: &SchemaModel) -> Completions {
+  let cursor_byte = sql.char_indices().nth(cursor).map(|(i, _)| i).unwrap_or(sql.len());
+  let before = &sql[..cursor_byte];
+
+  let prefix_len = before.chars().rev().take_while(|c| is_ident_char(*c)).count();
+  let prefix_start_byte =
+    cursor_byte - before.chars().rev().take(prefix_len).map(char::len_utf8).sum::<usize>();
+  let prefix = &sql[prefix_start_byte..cursor_byte];
+  let mut completions = Completions { start: cursor - prefix_len, end: cursor, items: vec![] };
+
+  let tokens = tokenize(sql);
+  // No completion inside strings, comments or quoted identifiers, including at the end of a
+  // line comment or of one that is not closed yet
+  if tokens.iter().any(|t| {
+    let open_end = t.kind == TokenKind::LineComment || is_unterminated(sql, t);
+    t.start < cursor_byte
+      && (cursor_byte < t.end || (cursor_byte == t.end && open_end))
+      && !matches!(
+        t.kind,
+        TokenKind::Identifier | TokenKind::Keyword | TokenKind::Whitespace | TokenKind::Punctuation
+      )
+  }) {
+    return completions;
+  }
+
+  let statement = statement_tokens(sql, &tokens, cursor_byte);
+  let refs = table_refs(sql, &statement, schema);
+  let preceding: Vec<&Token> = statement.iter().filter(|t| t.end <= prefix_start_byte).collect();
+  let prefix_lc = prefix.to_lowercase();
+  let matches = |label: &str| label.to_lowercase().starts_with(&prefix_lc);
+  let mut items: Vec<Completion> = Vec::new();
+
+  // `qualifier.prefix`: columns of an aliased table or tables of a schema
+  if preceding.last().is_some_and(|t| t.text(sql) == "." && t.end == prefix_start_byte) {
+    let qualifier = preceding
+      .len()
+      .checked_sub(2)
+      .map(|i| preceding[i].text(sql).trim_matches('"').to_lowercase())
+      .unwrap_or_default();
+    if let Some(r) = refs.iter().find(|r| r.qualifier().to_lowercase() == qualifier) {
+      items.extend(column_items(r.table).filter(|c| matches(&c.label)));
+    } else if let Some(table) = schema.table(&qualifier) {
+      items.extend(column_items(table).filter(|c| matches(&c.label)));
+    } else {
+      for table in schema.tables_in_schema(&qualifier) {
+        if matches(&table.name) {
+          items.push(table_item(table, false));
+        }
+      }
+    }
+    completions.items = finish(items);
+    return completions;
+  }
+
+  let significant: Vec<&Token> = preceding
+    .iter()
+    .copied()
+    .filter(|t| {
+      !matches!(t.kind, TokenKind::Whitespace | TokenKind::LineComment | TokenKind::BlockComment)
+    })
+    .collect();
+  let last_keyword = significant
+    .iter()
+    .rev()
+    .find(|t| t.kind == TokenKind::Keyword)
+    .map(|t| t.text(sql).to_uppercase());
+  let last = significant.last().map(|t| t.text(sql).to_uppercase());
+
+  match last_keyword.as_deref() {
+    // Table position: right after the keyword, or after a comma in a FROM list
+    Some("FROM" | "JOIN" | "UPDATE" | "INTO" | "TABLE")
+      if last.as_deref() == last_keyword.as_deref() || last.as_deref() == Some(",") =>
+    {
+      if last_keyword.as_deref() == Some("JOIN") {
+        items.extend(join_items(&refs, schema).into_iter().filter(|c| matches(&c.label)));
+      }
+      for table in &schema.tables {
+        if matches(&table.name) {
+          items.push(table_item(table, table.schema != "public"));
+        }
+      }
+      for s in schema.schemas() {
+        if matches(s) {
+          items.push(Completion {
+            kind: CompletionKind::Schema,
+            label: s.to_string(),
+            insert: s.to_string(),
+            detail: "schema".into(),
+          });
+        }
+      }
+    }
+    Some("ON") if last.as_deref() == Some("ON") => {
+      items.extend(on_condition_items(&refs, schema).into_iter().filter(|c| matches(&c.label)));
+      items.extend(scoped_column_items(&refs).filter(|c| matches(&c.label)));
+    }
+    _ => {
+      items.extend(scoped_column_items(&refs).filter(|c| matches(&c.label)));
+      if !prefix.is_empty() {
+        for f in &schema.functions {
+          if matches(f) {
+            items.push(Completion {
+              kind: CompletionKind::Function,
+              label: f.clone(),
+              insert: format!("{f}("),
+              detail: "function".into(),
+            });
+          }
+        }
+      }
+    }
+  }
+
+  if !prefix.is_empty() {
+    for keyword in KEYWORDS.iter().filter(|k| matches(k)) {
+      items.push(Completion {
+        kind: CompletionKind::Keyword,
+        label: keyword.to_string(),
+        insert: keyword.to_string(),
+        detail: String::new(),
+      });
+    }
+  }
+
+  completions.items = finish(items);
+  completions
+}
+
+/// Ranks by kind, drops exact duplicates and caps the list.
+fn finish(mut items: Vec<Completion>) -> Vec<Completion> {
+  items.sort_by(|a, b| a.kind.cmp(&b.kind).then_with(|| a.label.cmp(&b.label)));
+  items.dedup_by(|a, b| a.kind == b.kind && a.insert == b.insert);
+  items.truncate(MAX_ITEMS);
+  items
+}
+
+/// Tokens of the `;`-separated statement containing the cursor.
+fn statement_tokens(sql: &str, tokens: &[Token], cursor_byte: usize) -> Vec<Token> {
+  let is_end = |t: &Token| t.kind == TokenKind::Punctuation && t.text(sql) == ";";
+  let start = tokens.iter().rposition(|t| is_end(t) && t.end <= cursor_byte).map(|i| i + 1);
+  let start = start.unwrap_or(0);
+  let end = tokens[start..]
+    .iter()
+    .position(|t| is_end(t) && t.start >= cursor_byte)
+    .map(|i| start + i)
+    .unwrap_or(tokens.len());
+  tokens[start..end].to_vec()
+}
+
+/// Tables after FROM/JOIN/UPDATE/INTO (and in comma separated FROM lists) with their aliases.
+fn table_refs<'a>(sql: &str, statement: &[Token], schema: &'a SchemaModel) -> Vec<TableRef<'a>> {
+  let words: Vec<&Token> = statement
+    .iter()
+    .filter(|t| {
+      !matches!(t.kind, TokenKind::Whitespace | TokenKind::LineComment | TokenKind::BlockComment)
+    })
+    .collect();
+  let text = |i: usize| words.get(i).map(|t| t.text(sql)).unwrap_or("");
+  let upper = |i: usize| text(i).to_uppercase();
+
+  let mut refs = Vec::new();
+  let mut i = 0;
+  while i < words.len() {
+    let kw = upper(i);
+    if !matches!(kw.as_str(), "FROM" | "JOIN" | "UPDATE" | "INTO") {
+      i += 1;
+      continue;
+    }
+    i += 1;
+    loop {
+      // [schema.]table
+      let mut name = text(i).to_string();
+      if text(i + 1) == "." {
+        name = format!("{}.{}", name, text(i + 2));
+        i += 2;
+      }
+      i += 1;
+      let Some(table) = schema.table(&name) else {
+        break;
+      };
+      let mut alias = None;
+      if upper(i) == "AS" {
+        i += 1;
+      }
+      if words.get(i).is_some_and(|t| {
+        matches!(t.kind, TokenKind::Identifier | TokenKind::QuotedIdentifier)
+          && !is_keyword(t.text(sql))
+      }) {
+        alias = Some(text(i).trim_matches('"').to_string());
+        i += 1;
+      }
+      refs.push(TableRef { table, alias });
+      if kw == "FROM" && text(i) == "," {
+        i += 1;
+        continue;
+      }
+      break;
+    }
+  }
+  refs
+}
+
+fn column_items(table: &TableInfo) -> impl Iterator<Item = Completion> + '_ {
+  table.columns.iter().map(move |c| Completion {
+    kind: CompletionKind::Column,
+    label: c.name.clone(),
+    insert: c.name.clone(),
+    detail: format!("{} · {}", c.data_type, table.name),
+  })
+}
+
+/// Columns of all tables in scope; qualified when more than one table is referenced.
+fn scoped_column_items<'a>(refs: &'a [TableRef<'a>]) -> impl Iterator<Item = Completion> + 'a {
+  let qualify = refs.len() > 1;
+  refs.iter().flat_map(move |r| {
+    column_items(r.table).map(move |mut c| {
+      if qualify {
+        c.insert = format!("{}.{}", r.qualifier(), c.insert);
+      }
+      c
+    })
+  })
+}
+
+fn table_item(table: &TableInfo, qualified: bool) -> Completion {
+  let insert = if qualified { table.qualified_name() } else { table.name.clone() };
+  Completion {
+    kind: CompletionKind::Table,
+    label: table.name.clone(),
+    insert,
+    detail: format!("table · {}", table.schema),
+  }
+}
+
+fn join_condition(schema: &SchemaModel, a: &TableRef, b: &TableRef) -> Option<String> {
+  let fk = schema.foreign_keys_between(a.table, b.table).into_iter().next()?;
+  let (from, to) =
+    if fk.table == a.table.name && fk.schema == a.table.schema { (a, b) } else { (b, a) };
+  Some(
+    fk.columns
+      .iter()
+      .zip(fk.ref_columns.iter())
+      .map(|(c, rc)| format!("{}.{} = {}.{}", from.qualifier(), c, to.qualifier(), rc))
+      .collect::<Vec<_>>()
+      .join(" AND "),
+  )
+}
+
+/// `ON` conditions joining the last referenced table to the ones before it.
+fn on_condition_items(refs: &[TableRef], schema: &SchemaModel) -> Vec<Completion> {
+  let Some((joined, earlier)) = refs.split_last() else {
+    return vec![];
+  };
+  earlier
+    .iter()
+    .filter_map(|other| join_condition(schema, joined, other))
+    .map(|cond| Completion {
+      kind: CompletionKind::Join,
+      label: cond.clone(),
+      insert: cond,
+      detail: "foreign key".into(),
+    })
+    .collect()
+}
+
+/// Whole `table alias ON ...` clauses for tables related to the ones in scope.
+fn join_items(refs: &[TableRef], schema: &SchemaModel) -> Vec<Completion> {
+  let mut items = Vec::new();
+  for r in refs {
+    for related in schema.related_tables(r.table) {
+      let alias = unique_alias(&related.name, refs);
+      let joined = TableRef { table: related, alias: Some(alias.clone()) };
+      if let Some(cond) = join_condition(schema, &joined, r) {
+        let insert = format!("{} {} ON {}", related.name, alias, cond);
+        items.push(Completion {
+          kind: CompletionKind::Join,
+          label: related.name.clone(),
+          insert,
+          detail: format!("join via {}", r.qualifier()),
+        });
+      }
+    }
+  }
+  items
+}
+
+/// Initials of the table name ("order_items" -> "oi"), numbered if already taken.
+fn unique_alias(table: &str, refs: &[TableRef]) -> String {
+  let base: String = table.split('_').filter_map(|p| p.chars().next()).collect();
+  let base = if base.is_empty() { "t".to_string() } else { base.to_lowercase() };
+  let taken = |a: &str| refs.iter().any(|r| r.qualifier().eq_ignore_ascii_case(a));
+  if !taken(&base) {
+    return base;
+  }
+  (2..).map(|n| format!("{base}{n}")).find(|a| !taken(a)).unwrap()
+}
+
+#[cfg(test)]
+fn test_schema() -> SchemaModel {
+  use crate::schema::{ColumnInfo, ForeignKey};
+  let table = |name: &str, cols: &[&str]| TableInfo {
+    schema: "public".into(),
+    name: name.into(),
+    columns: cols
+      .iter()
+      .map(|c| ColumnInfo { name: c.to_string(), data_type: "integer".into() })
+      .collect(),
+  };
+  SchemaModel {
+    tables: vec![
+      table("users", &["id", "email", "full_name"]),
+      table("orders", &["id", "user_id", "total"]),
+      table("order_items", &["id", "order_id", "book_id"]),
+    ],
+    foreign_keys: vec![
+      ForeignKey {
+        schema: "public".into(),
+        table: "orders".into(),
+        columns: vec!["user_id".into()],
+        ref_schema: "public".into(),
+        ref_table: "users".into(),
+        ref_columns: vec!["id".into()],
+      },
+      ForeignKey {
+        schema: "public".into(),
+        table: "order_items".into(),
+        columns: vec!["order_id".into()],
+        ref_schema: "public".into(),
+        ref_table: "orders".into(),
+        ref_columns: vec!["id".into()],
+      },
+    ],
+    functions: vec!["count".into(), "coalesce".into()],
+  }
+}
+
+#[cfg(test)]
+fn labels(sql_with_cursor: &str) -> (Completions, Vec<String>) {
+  let cursor = sql_with_cursor.chars().position(|c| c == '|').unwrap();
+  let sql = sql_with_cursor.replace('|', "");
+  let completions = complete(&sql, cursor, &test_schema());
+  let inserts = completions.items.iter().map(|c| c.insert.clone()).collect();
+  (completions, inserts)
+}
+
+#[test]
+fn test_complete_tables_and_aliased_columns() {
+  let (_, items) = labels("SELECT * FROM ord|");
+  assert_eq!(items, vec!["order_items", "orders", "ORDER"]);
+
+  let (c, items) = labels("SELECT o.to| FROM orders o");
+  assert_eq!(items, vec!["total"]);
+  assert_eq!((c.start, c.end), (9, 11));
+
+  let (_, items) = labels("SELECT em| FROM users u JOIN orders o ON o.user_id = u.id");
+  assert_eq!(items[0], "u.email");
+
+  let (_, items) = labels("SELECT 'o.|' FROM orders o");
+  assert!(items.is_empty());
+}
+
+#[test]
+fn test_no_completion_at_end_of_open_string_or_comment() {
+  for sql in [
+    "SELECT * FROM users WHERE email = 'ab|",
+    "SELECT * FROM users WHERE email = E'it\\'s|",
+    "SELECT \"us|",
+    "-- sel|",
+    "SELECT 1; -- sel|\nSELECT 2",
+  ] {
+    assert!(labels(sql).1.is_empty(), "{sql}");
+  }
+  // right after a closed string or quoted identifier completion works again
+  assert!(!labels("SELECT 'a', co| FROM users").1.is_empty());
+  assert!(!labels("SELECT \"users\" co|").1.is_empty());
+}
+
+#[test]
+fn test_complete_join_conditions_from_foreign_keys() {
+  let (_, items) = labels("SELECT * FROM users u JOIN orders o ON |");
+  assert_eq!(items[0], "o.user_id = u.id");
+
+  let (_, items) = labels("SELECT * FROM orders o JOIN |; SELECT 1");
+  assert!(items.contains(&"users u ON o.user_id = u.id".to_string()));
+  assert!(items.contains(&"order_items oi ON oi.order_id = o.id".to_string()));
+}
+
+#[test]
+fn test_complete_keywords_and_functions() {
+  let (_, items) = labels("SELECT co| FROM users");
+  assert!(items.contains(&"coalesce(".to_string()));
+  assert!(items.contains(&"COLUMN".to_string()));
+  let (_, items) = labels("sel|");
+  assert_eq!(items, vec!["SELECT"]);
+}
@@ -0,0 +1,95 @@
+use crate::sql::tokenizer::{TokenKind, tokenize};
+
+/// Visual style of a highlighted span.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpanStyle {
+  Plain,
+  Keyword,
+  QuotedIdentifier,
+  String,
+  Comment,
+  Number,
+  Parameter,
+}
+
+impl SpanStyle {
+  pub fn color(&self) -> &'static str {
+    match self {
+      SpanStyle::Plain => "rgb(30,30,30)",
+      SpanStyle::Keyword => "rgb(0,70,190)",
+      SpanStyle::QuotedIdentifier => "rgb(120,60,150)",
+      SpanStyle::String => "rgb(20,130,50)",
+      SpanStyle::Comment => "rgb(130,130,130)",
+      SpanStyle::Number => "rgb(190,90,0)",
+      SpanStyle::Parameter => "rgb(180,0,120)",
+    }
+  }
+
+  pub fn font_weight(&self) -> &'static str {
+    match self {
+      SpanStyle::Keyword => "bold",
+      _ => "normal",
+    }
+  }
+
+  pub fn font_style(&self) -> &'static str {
+    match self {
+      SpanStyle::Comment => "italic",
+      _ => "normal",
+    }
+  }
+}
+
+impl From<TokenKind> for SpanStyle {
+  fn from(kind: TokenKind) -> Self {
+    match kind {
+      TokenKind::Keyword => SpanStyle::Keyword,
+      TokenKind::QuotedIdentifier => SpanStyle::QuotedIdentifier,
+      TokenKind::String | TokenKind::DollarQuoted => SpanStyle::String,
+      TokenKind::LineComment | TokenKind::BlockComment => SpanStyle::Comment,
+      TokenKind::Number => SpanStyle::Number,
+      TokenKind::Parameter => SpanStyle::Parameter,
+      TokenKind::Identifier
+      | TokenKind::Operator
+      | TokenKind::Punctuation
+      | TokenKind::Whitespace => SpanStyle::Plain,
+    }
+  }
+}
+
+/// Styled spans covering the whole text. Adjacent tokens with the same style (and whitespace)
+/// are merged, so the editor renders a few spans per line rather than one per token.
+pub fn highlight(sql: &str) -> Vec<(SpanStyle, String)> {
+  let mut spans: Vec<(SpanStyle, String)> = Vec::new();
+  for token in tokenize(sql) {
+    let text = token.text(sql);
+    let style = SpanStyle::from(token.kind);
+    match spans.last_mut() {
+      Some((last_style, last_text))
+        if *last_style == style || token.kind == TokenKind::Whitespace =>
+      {
+        last_text.push_str(text)
+      }
+      _ => spans.push((style, text.to_string())),
+    }
+  }
+  spans
+}
+
+#[test]
+fn test_highlight_merges_spans() {
+  let sql = "SELECT a, b FROM t WHERE c = 'x' -- done";
+  let spans = highlight(sql);
+  assert_eq!(spans.iter().map(|(_, t)| t.as_str()).collect::<String>(), sql);
+  assert_eq!(spans[0], (SpanStyle::Keyword, "SELECT ".to_string()));
+  assert_eq!(spans[1], (SpanStyle::Plain, "a, b ".to_string()));
+  assert!(spans.contains(&(SpanStyle::String, "'x' ".to_string())));
+  assert_eq!(spans.last().unwrap().0, SpanStyle::Comment);
+}
+
+#[test]
+fn test_highlight_large_script() {
+  let sql = "SELECT id, 'v' FROM t WHERE x = $1; -- c\n".repeat(20_000);
+  let spans = highlight(&sql);
+  assert_eq!(spans.iter().map(|(_, t)| t.len()).sum::<usize>(), sql.len());
+}
@@ -0,0 +1,4 @@
+pub mod completion;
+pub mod highlight;
+pub mod splitter;
+pub mod tokenizer;
@@ -0,0 +1,126 @@
+use crate::sql::tokenizer::{TokenKind, tokenize};
+
+/// A statement of a script, as a byte range into it. `text` excludes the terminating `;`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Statement {
+  pub start: usize,
+  pub end: usize,
+  pub text: String,
+}
+
+/// Splits a script on `;`. Semicolons inside strings, quoted identifiers, comments and
+/// dollar-quoted bodies do not split; parentheses are not tracked, so an unbalanced `(` only
+/// breaks its own statement. Statements consisting only of whitespace
+/// and comments are dropped.
+pub fn split_statements(sql: &str) -> Vec<Statement> {
+  let mut statements = Vec::new();
+  let mut start = 0;
+  let mut has_code = false;
+
+  for token in tokenize(sql) {
+    match token.kind {
+      TokenKind::Whitespace | TokenKind::LineComment | TokenKind::BlockComment => continue,
+      TokenKind::Punctuation if token.text(sql) == ";" => {
+        if has_code {
+          statements.push(statement(sql, start, token.start));
+        }
+        start = token.end;
+        has_code = false;
+        continue;
+      }
+      _ => {}
+    }
+    has_code = true;
+  }
+  if has_code {
+    statements.push(statement(sql, start, sql.len()));
+  }
+  statements
+}
+
+fn statement(sql: &str, start: usize, end: usize) -> Statement {
+  let raw = &sql[start..end];
+  let leading = raw.len() - raw.trim_start().len();
+  let trailing = raw.len() - raw.trim_end().len();
+  let (start, end) = (start + leading, end - trailing);
+  Statement { start, end, text: sql[start..end].to_string() }
+}
+
+/// The statement under the cursor (a char index). Between statements, the closest preceding
+/// one wins, so the cursor right after `SELECT 1;` runs `SELECT 1`.
+pub fn statement_at(sql: &str, cursor: usize) -> Option<Statement> {
+  let cursor_byte = char_to_byte(sql, cursor);
+  let statements = split_statements(sql);
+  statements
+    .iter()
+    .find(|s| s.start <= cursor_byte && cursor_byte <= s.end)
+    .or_else(|| statements.iter().rev().find(|s| s.end <= cursor_byte))
+    .or_else(|| statements.first())
+    .cloned()
+}
+
+/// Statements of the selected text; `start..end` are char indices in either order.
+pub fn selected_statements(sql: &str, start: usize, end: usize) -> Vec<Statement> {
+  let (start, end) = (start.min(end), start.max(end));
+  let (start, end) = (char_to_byte(sql, start), char_to_byte(sql, end));
+  split_statements(&sql[start..end])
+    .into_iter()
+    .map(|s| Statement { start: s.start + start, end: s.end + start, text: s.text })
+    .collect()
+}
+
+/// Whitespace-insensitive form of a statement without its trailing `;`, for comparing statements.
+pub fn normalize_statement(sql: &str) -> String {
+  sql.split_whitespace().collect::<Vec<_>>().join(" ").trim_end_matches(';').trim_end().to_string()
+}
+
+/// The command keyword a statement starts with, upper-cased (`SELECT`, `INSERT`, ...).
+pub fn command_verb(sql: &str) -> Option<String> {
+  tokenize(sql)
+    .into_iter()
+    .find(|t| {
+      !matches!(t.kind, TokenKind::Whitespace | TokenKind::LineComment | TokenKind::BlockComment)
+    })
+    .filter(|t| matches!(t.kind, TokenKind::Keyword | TokenKind::Identifier))
+    .map(|t| t.text(sql).to_uppercase())
+}
+
+fn char_to_byte(sql: &str, char_idx: usize) -> usize {
+  sql.char_indices().nth(char_idx).map(|(i, _)| i).unwrap_or(sql.len())
+}
+
+#[test]
+fn test_split_respects_quotes_comments_and_dollar_bodies() {
+  let sql = "SELECT 'a;b' AS \"x;y\"; -- c;\n\
+             CREATE FUNCTION f() RETURNS int AS $$ BEGIN RETURN 1; END; $$ LANGUAGE plpgsql;\n\
+             /* only; a comment */ ;\n\
+             SELECT 2";
+  let statements: Vec<String> = split_statements(sql).into_iter().map(|s| s.text).collect();
+  assert_eq!(
+    statements,
+    vec![
+      "SELECT 'a;b' AS \"x;y\"".to_string(),
+      "-- c;\nCREATE FUNCTION f() RETURNS int AS $$ BEGIN RETURN 1; END; $$ LANGUAGE plpgsql"
+        .to_string(),
+      "SELECT 2".to_string(),
+    ]
+  );
+  assert_eq!(split_statements("SELECT (1; SELECT 2").len(), 2);
+}
+
+#[test]
+fn test_statement_at_cursor_and_selection() {
+  let sql = "SELECT 1;\nSELECT 2;\n\nSELECT 3";
+  assert_eq!(statement_at(sql, 3).unwrap().text, "SELECT 1");
+  assert_eq!(statement_at(sql, 9).unwrap().text, "SELECT 1");
+  assert_eq!(statement_at(sql, 12).unwrap().text, "SELECT 2");
+  assert_eq!(statement_at(sql, 20).unwrap().text, "SELECT 2");
+  assert_eq!(statement_at(sql, sql.len()).unwrap().text, "SELECT 3");
+  assert!(statement_at("  -- nothing", 2).is_none());
+
+  assert_eq!(command_verb("-- note\n update t SET a = 1").as_deref(), Some("UPDATE"));
+
+  let selected: Vec<String> =
+    selected_statements(sql, 20, 10).into_iter().map(|s| s.text).collect();
+  assert_eq!(selected, vec!["SELECT 2".to_string()]);
+}
@@ -0,0 +1,444 @@
+/// Kinds of lexical tokens in Postgres SQL.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TokenKind {
+  Keyword,
+  Identifier,
+  /// `"Mixed Case"` identifiers
+  QuotedIdentifier,
+  /// `'...'`, `E'...'`, `B'...'`, `X'...'`, `U&'...'`
+  String,
+  /// `$$ ... $$` or `$tag$ ... $tag$`
+  DollarQuoted,
+  LineComment,
+  BlockComment,
+  Number,
+  /// Positional parameters: `$1`, `$2`, ...
+  Parameter,
+  Operator,
+  Punctuation,
+  Whitespace,
+}
+
+/// A token as a byte range into the tokenized text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Token {
+  pub kind: TokenKind,
+  pub start: usize,
+  pub end: usize,
+}
+
+impl Token {
+  pub fn text<'a>(&self, source: &'a str) -> &'a str {
+    &source[self.start..self.end]
+  }
+}
+
+/// Keywords highlighted as such. Sorted, so lookups can binary search.
+pub static KEYWORDS: &[&str] = &[
+  "ALL",
+  "ALTER",
+  "ANALYZE",
+  "AND",
+  "ANY",
+  "ARRAY",
+  "AS",
+  "ASC",
+  "BEGIN",
+  "BETWEEN",
+  "BIGINT",
+  "BOOLEAN",
+  "BOTH",
+  "BY",
+  "CASCADE",
+  "CASE",
+  "CAST",
+  "CHECK",
+  "COLLATE",
+  "COLUMN",
+  "COMMIT",
+  "CONFLICT",
+  "CONSTRAINT",
+  "COPY",
+  "CREATE",
+  "CROSS",
+  "CURRENT_DATE",
+  "CURRENT_TIMESTAMP",
+  "CURRENT_USER",
+  "DATABASE",
+  "DATE",
+  "DECLARE",
+  "DEFAULT",
+  "DELETE",
+  "DESC",
+  "DISTINCT",
+  "DO",
+  "DROP",
+  "ELSE",
+  "END",
+  "EXCEPT",
+  "EXECUTE",
+  "EXISTS",
+  "EXPLAIN",
+  "EXTENSION",
+  "FALSE",
+  "FETCH",
+  "FILTER",
+  "FIRST",
+  "FOR",
+  "FOREIGN",
+  "FROM",
+  "FULL",
+  "FUNCTION",
+  "GRANT",
+  "GROUP",
+  "HAVING",
+  "IF",
+  "ILIKE",
+  "IN",
+  "INDEX",
+  "INNER",
+  "INSERT",
+  "INTEGER",
+  "INTERSECT",
+  "INTERVAL",
+  "INTO",
+  "IS",
+  "JOIN",
+  "KEY",
+  "LANGUAGE",
+  "LAST",
+  "LATERAL",
+  "LEADING",
+  "LEFT",
+  "LIKE",
+  "LIMIT",
+  "LOCAL",
+  "MATERIALIZED",
+  "NATURAL",
+  "NOT",
+  "NOTHING",
+  "NULL",
+  "NULLS",
+  "NUMERIC",
+  "OFFSET",
+  "ON",
+  "ONLY",
+  "OR",
+  "ORDER",
+  "OUTER",
+  "OVER",
+  "PARTITION",
+  "PRIMARY",
+  "PROCEDURE",
+  "RAISE",
+  "RECURSIVE",
+  "REFERENCES",
+  "REPLACE",
+  "RETURN",
+  "RETURNING",
+  "RETURNS",
+  "REVOKE",
+  "RIGHT",
+  "ROLLBACK",
+  "ROW",
+  "ROWS",
+  "SAVEPOINT",
+  "SCHEMA",
+  "SELECT",
+  "SEQUENCE",
+  "SET",
+  "SETOF",
+  "SIMILAR",
+  "SOME",
+  "TABLE",
+  "TEMP",
+  "TEMPORARY",
+  "TEXT",
+  "THEN",
+  "TIMESTAMP",
+  "TO",
+  "TRAILING",
+  "TRANSACTION",
+  "TRIGGER",
+  "TRUE",
+  "TRUNCATE",
+  "UNION",
+  "UNIQUE",
+  "UPDATE",
+  "USING",
+  "VACUUM",
+  "VALUES",
+  "VARCHAR",
+  "VIEW",
+  "WHEN",
+  "WHERE",
+  "WINDOW",
+  "WITH",
+];
+
+pub fn is_keyword(word: &str) -> bool {
+  KEYWORDS.binary_search(&word.to_ascii_uppercase().as_str()).is_ok()
+}
+
+fn is_ident_start(c: u8) -> bool {
+  c.is_ascii_alphabetic() || c == b'_' || c >= 0x80
+}
+
+fn is_ident_char(c: u8) -> bool {
+  is_ident_start(c) || c.is_ascii_digit() || c == b'$'
+}
+
+/// Splits Postgres SQL into tokens covering the whole input. Unterminated strings, quoted
+/// identifiers and comments run to the end of the input, so a half-typed script still
+/// highlights sensibly. Runs in a single linear pass.
+pub fn tokenize(sql: &str) -> Vec<Token> {
+  let bytes = sql.as_bytes();
+  let mut tokens = Vec::new();
+  let mut i = 0;
+
+  while i < bytes.len() {
+    let start = i;
+    let c = bytes[i];
+    let next = bytes.get(i + 1).copied();
+
+    let kind = if c.is_ascii_whitespace() {
+      while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+        i += 1;
+      }
+      TokenKind::Whitespace
+    } else if c == b'-' && next == Some(b'-') {
+      i = find_byte(bytes, i, b'\n').unwrap_or(bytes.len());
+      TokenKind::LineComment
+    } else if c == b'/' && next == Some(b'*') {
+      i = skip_block_comment(bytes, i);
+      TokenKind::BlockComment
+    } else if c == b'\'' {
+      i = skip_quoted(bytes, i + 1, b'\'', false);
+      TokenKind::String
+    } else if matches!(c, b'E' | b'e') && next == Some(b'\'') {
+      i = skip_quoted(bytes, i + 2, b'\'', true);
+      TokenKind::String
+    } else if matches!(c, b'B' | b'b' | b'X' | b'x' | b'N' | b'n') && next == Some(b'\'') {
+      i = skip_quoted(bytes, i + 2, b'\'', false);
+      TokenKind::String
+    } else if matches!(c, b'U' | b'u')
+      && next == Some(b'&')
+      && matches!(bytes.get(i + 2), Some(b'\'') | Some(b'"'))
+    {
+      let quote = bytes[i + 2];
+      i = skip_quoted(bytes, i + 3, quote, false);
+      if quote == b'\'' { TokenKind::String } else { TokenKind::QuotedIdentifier }
+    } else if c == b'"' {
+      i = skip_quoted(bytes, i + 1, b'"', false);
+      TokenKind::QuotedIdentifier
+    } else if c == b'$' {
+      match dollar_tag_len(bytes, i) {
+        Some(tag_len) => {
+          let tag = &bytes[i..i + tag_len];
+          i = find_subslice(bytes, i + tag_len, tag).map(|p| p + tag_len).unwrap_or(bytes.len());
+          TokenKind::DollarQuoted
+        }
+        None if next.is_some_and(|n| n.is_ascii_digit()) => {
+          i += 1;
+          while i < bytes.len() && bytes[i].is_ascii_digit() {
+            i += 1;
+          }
+          TokenKind::Parameter
+        }
+        None => {
+          i += 1;
+          TokenKind::Operator
+        }
+      }
+    } else if c.is_ascii_digit() || (c == b'.' && next.is_some_and(|n| n.is_ascii_digit())) {
+      i = skip_number(bytes, i);
+      TokenKind::Number
+    } else if is_ident_start(c) {
+      while i < bytes.len() && is_ident_char(bytes[i]) {
+        i += 1;
+      }
+      if is_keyword(&sql[start..i]) { TokenKind::Keyword } else { TokenKind::Identifier }
+    } else if matches!(c, b'(' | b')' | b'[' | b']' | b',' | b';' | b'.') {
+      i += 1;
+      TokenKind::Punctuation
+    } else if c == b':' && next == Some(b':') {
+      i += 2;
+      TokenKind::Operator
+    } else if b"+-*/<>=~!@#%^&|`?:".contains(&c) {
+      while i < bytes.len() && b"+-*/<>=~!@#%^&|`?".contains(&bytes[i]) {
+        // a comment start ends the operator
+        if i > start
+          && matches!((bytes[i], bytes.get(i + 1)), (b'-', Some(b'-')) | (b'/', Some(b'*')))
+        {
+          break;
+        }
+        i += 1;
+      }
+      i = i.max(start + 1);
+      TokenKind::Operator
+    } else {
+      // any other (possibly multi-byte) character
+      i += utf8_len(c);
+      TokenKind::Punctuation
+    };
+
+    tokens.push(Token { kind, start, end: i.min(bytes.len()) });
+  }
+
+  tokens
+}
+
+fn utf8_len(first: u8) -> usize {
+  match first {
+    0xF0..=0xFF => 4,
+    0xE0..=0xEF => 3,
+    0xC0..=0xDF => 2,
+    _ => 1,
+  }
+}
+
+fn find_byte(bytes: &[u8], from: usize, needle: u8) -> Option<usize> {
+  bytes[from..].iter().position(|&b| b == needle).map(|p| from + p)
+}
+
+fn find_subslice(bytes: &[u8], from: usize, needle: &[u8]) -> Option<usize> {
+  if from > bytes.len() {
+    return None;
+  }
+  bytes[from..].windows(needle.len()).position(|w| w == needle).map(|p| from + p)
+}
+
+/// Postgres block comments nest.
+fn skip_block_comment(bytes: &[u8], mut i: usize) -> usize {
+  let mut depth = 0;
+  while i < bytes.len() {
+    match (bytes[i], bytes.get(i + 1)) {
+      (b'/', Some(b'*')) => {
+        depth += 1;
+        i += 2;
+      }
+      (b'*', Some(b'/')) => {
+        depth -= 1;
+        i += 2;
+        if depth == 0 {
+          return i;
+        }
+      }
+      _ => i += 1,
+    }
+  }
+  bytes.len()
+}
+
+/// Returns the index after the closing quote, or the end of the input when there is none.
+fn skip_quoted(bytes: &[u8], i: usize, quote: u8, backslash_escapes: bool) -> usize {
+  closing_quote(bytes, i, quote, backslash_escapes).unwrap_or(bytes.len())
+}
+
+/// The index after the closing quote. A doubled quote is an escaped quote; with
+/// `backslash_escapes` (E'' strings) a backslash escapes the next byte.
+fn closing_quote(bytes: &[u8], mut i: usize, quote: u8, backslash_escapes: bool) -> Option<usize> {
+  while i < bytes.len() {
+    let c = bytes[i];
+    if backslash_escapes && c == b'\\' {
+      i += 2;
+    } else if c == quote {
+      if bytes.get(i + 1) == Some(&quote) {
+        i += 2;
+      } else {
+        return Some(i + 1);
+      }
+    } else {
+      i += 1;
+    }
+  }
+  None
+}
+
+/// Whether a string or quoted identifier lacks its closing quote, as one still being typed at
+/// the end of the input does.
+pub fn is_unterminated(sql: &str, token: &Token) -> bool {
+  let text = token.text(sql).as_bytes();
+  let Some(open) = text.iter().position(|c| matches!(c, b'\'' | b'"')) else {
+    return false;
+  };
+  matches!(token.kind, TokenKind::String | TokenKind::QuotedIdentifier)
+    && closing_quote(text, open + 1, text[open], text[0].eq_ignore_ascii_case(&b'e')).is_none()
+}
+
+/// Length of a `$tag$` opener at `i`, if there is one.
+pub(crate) fn dollar_tag_len(bytes: &[u8], i: usize) -> Option<usize> {
+  let mut j = i + 1;
+  if bytes.get(j).is_some_and(|c| c.is_ascii_digit()) {
+    return None;
+  }
+  while j < bytes.len() && bytes[j] != b'$' {
+    if !(is_ident_start(bytes[j]) || bytes[j].is_ascii_digit()) {
+      return None;
+    }
+    j += 1;
+  }
+  (j < bytes.len()).then_some(j + 1 - i)
+}
+
+fn skip_number(bytes: &[u8], mut i: usize) -> usize {
+  let digits = |i: &mut usize| {
+    while *i < bytes.len() && (bytes[*i].is_ascii_digit() || bytes[*i] == b'_') {
+      *i += 1;
+    }
+  };
+  digits(&mut i);
+  if i < bytes.len() && bytes[i] == b'.' && bytes.get(i + 1) != Some(&b'.') {
+    i += 1;
+    digits(&mut i);
+  }
+  if i < bytes.len() && matches!(bytes[i], b'e' | b'E') {
+    let mut j = i + 1;
+    if j < bytes.len() && matches!(bytes[j], b'+' | b'-') {
+      j += 1;
+    }
+    if bytes.get(j).is_some_and(|c| c.is_ascii_digit()) {
+      i = j;
+      digits(&mut i);
+    }
+  }
+  i
+}
+
+#[test]
+fn test_keywords_are_sorted() {
+  assert!(KEYWORDS.windows(2).all(|w| w[0] < w[1]));
+}
+
+#[test]
+fn test_tokenize_postgres_constructs() {
+  let sql = "SELECT \"Full Name\", 'it''s', E'a\\'b', $1 -- note\n\
+             FROM users /* outer /* inner */ still */ WHERE x::int >= 1.5e3 AND body = $fn$ '; $fn$;";
+  let kinds: Vec<(TokenKind, &str)> = tokenize(sql)
+    .into_iter()
+    .filter(|t| t.kind != TokenKind::Whitespace)
+    .map(|t| (t.kind, t.text(sql)))
+    .collect();
+
+  assert_eq!(kinds[0], (TokenKind::Keyword, "SELECT"));
+  assert_eq!(kinds[1], (TokenKind::QuotedIdentifier, "\"Full Name\""));
+  assert_eq!(kinds[3], (TokenKind::String, "'it''s'"));
+  assert_eq!(kinds[5], (TokenKind::String, "E'a\\'b'"));
+  assert_eq!(kinds[7], (TokenKind::Parameter, "$1"));
+  assert_eq!(kinds[8], (TokenKind::LineComment, "-- note"));
+  assert!(kinds.contains(&(TokenKind::BlockComment, "/* outer /* inner */ still */")));
+  assert!(kinds.contains(&(TokenKind::Operator, "::")));
+  assert!(kinds.contains(&(TokenKind::Number, "1.5e3")));
+  assert!(kinds.contains(&(TokenKind::Operator, ">=")));
+  assert!(kinds.contains(&(TokenKind::DollarQuoted, "$fn$ '; $fn$")));
+  assert_eq!(kinds.last(), Some(&(TokenKind::Punctuation, ";")));
+}
+
+#[test]
+fn test_tokens_cover_input() {
+  let sql = "select 'unterminated ünïcode → \n from \"x";
+  let tokens = tokenize(sql);
+  assert_eq!(tokens.first().unwrap().start, 0);
+  assert_eq!(tokens.last().unwrap().end, sql.len());
+  assert!(tokens.windows(2).all(|w| w[0].end == w[1].start));
+}
@@ -0,0 +1,85 @@
+use crate::llm::estimate_tokens;
+
+pub static SUMMARY_PROMPT: &str = r#"
+You are a data analyst answering questions about Postgres query results.
+You get the user's question, the SQL that was run and a sample of its result.
+Answer the question in at most three sentences of plain text, quoting the relevant numbers.
+If the sample is truncated, say so when it matters for the answer. Do not output SQL or JSON.
+"#;
+
+/// Longest cell value sent to the LLM; longer values are cut.
+const MAX_CELL_CHARS: usize = 80;
+
+/// A bounded slice of a result, rendered as text for the LLM.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResultSample {
+  pub text: String,
+  pub rows_shown: usize,
+  pub rows_total: usize,
+}
+
+fn truncate_cell(value: &str) -> String {
+  let value = value.replace(['\n', '\t'], " ");
+  if value.chars().count() > MAX_CELL_CHARS {
+    format!("{}…", value.chars().take(MAX_CELL_CHARS).collect::<String>())
+  } else {
+    value
+  }
+}
+
+/// Renders the header and as many rows as fit in both `max_rows` and `max_tokens`.
+pub fn sample_result(
+  headers: &[String],
+  rows: &[Vec<String>],
+  max_rows: usize,
+  max_tokens: usize,
+) -> ResultSample {
+  let mut text = headers.join(" | ");
+  let mut tokens = estimate_tokens(&text);
+  let mut rows_shown = 0;
+  for row in rows.iter().take(max_rows) {
+    let line = row.iter().map(|v| truncate_cell(v)).collect::<Vec<_>>().join(" | ");
+    let line_tokens = estimate_tokens(&line) + 1;
+    if tokens + line_tokens > max_tokens {
+      break;
+    }
+    text.push('\n');
+    text.push_str(&line);
+    tokens += line_tokens;
+    rows_shown += 1;
+  }
+  if rows_shown < rows.len() {
+    text.push_str(&format!("\n(showing {rows_shown} of {} rows)", rows.len()));
+  }
+  ResultSample { text, rows_shown, rows_total: rows.len() }
+}
+
+/// User message asking for a written answer to `question` from the sampled result.
+pub fn summary_request(question: &str, sql: &str, sample: &ResultSample) -> String {
+  format!(
+    "Question: {question}\n\nSQL:\n{sql}\n\nResult ({} rows):\n{}",
+    sample.rows_total, sample.text
+  )
+}
+
+#[test]
+fn test_sample_respects_row_and_token_caps() {
+  let headers = vec!["city".to_string(), "total".to_string()];
+  let rows: Vec<Vec<String>> =
+    (0..100).map(|i| vec![format!("city {i}"), format!("{}", i * 10)]).collect();
+
+  let sample = sample_result(&headers, &rows, 5, 10_000);
+  assert_eq!(sample.rows_shown, 5);
+  assert!(sample.text.starts_with("city | total\ncity 0 | 0\n"));
+  assert!(sample.text.ends_with("(showing 5 of 100 rows)"));
+
+  let sample = sample_result(&headers, &rows, 100, 20);
+  assert!(sample.rows_shown < 5);
+  assert!(estimate_tokens(&sample.text) <= 40);
+
+  let long = vec![vec!["x".repeat(500), "1".to_string()]];
+  let sample = sample_result(&headers, &long, 10, 10_000);
+  assert_eq!(sample.rows_shown, 1);
+  assert!(sample.text.len() < 200);
+  assert!(!sample.text.contains("showing"));
+}
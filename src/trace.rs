@@ -0,0 +1,161 @@
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Something the text-to-SQL agent did while answering a question.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TraceEvent {
+  /// Messages sent to the LLM for one attempt
+  LlmRequest {
+    attempt: usize,
+    messages: usize,
+  },
+  /// The reply, serialized back to JSON
+  LlmResponse {
+    attempt: usize,
+    reply: String,
+  },
+  /// The request failed or the reply was not valid JSON
+  LlmError {
+    attempt: usize,
+    error: String,
+  },
+  /// A lookup sent to the database to answer a clarification
+  Introspection {
+    request: String,
+    response: String,
+    failed: bool,
+  },
+  /// What was fed back to the LLM for a clarification
+  ClarificationResolved {
+    clarification: String,
+    answer: String,
+  },
+  /// A reply that could not be accepted
+  ValidationFailure {
+    reason: String,
+  },
+  FinalSql {
+    sql: String,
+  },
+  /// The question was given up on, e.g. the LLM never returned usable SQL
+  Failed {
+    reason: String,
+  },
+}
+
+impl TraceEvent {
+  pub fn title(&self) -> String {
+    match self {
+      TraceEvent::LlmRequest { attempt, messages } => {
+        format!("LLM request #{attempt} ({messages} messages)")
+      }
+      TraceEvent::LlmResponse { attempt, .. } => format!("LLM response #{attempt}"),
+      TraceEvent::LlmError { attempt, .. } => format!("LLM error #{attempt}"),
+      TraceEvent::Introspection { failed: false, .. } => "DB introspection".into(),
+      TraceEvent::Introspection { failed: true, .. } => "DB introspection failed".into(),
+      TraceEvent::ClarificationResolved { .. } => "Clarification resolved".into(),
+      TraceEvent::ValidationFailure { .. } => "Validation failure".into(),
+      TraceEvent::FinalSql { .. } => "Final SQL".into(),
+      TraceEvent::Failed { .. } => "Failed".into(),
+    }
+  }
+
+  pub fn detail(&self) -> String {
+    match self {
+      TraceEvent::LlmRequest { .. } => String::new(),
+      TraceEvent::LlmResponse { reply, .. } => reply.clone(),
+      TraceEvent::LlmError { error, .. } => error.clone(),
+      TraceEvent::Introspection { request, response, .. } => format!("{request}\n→ {response}"),
+      TraceEvent::ClarificationResolved { clarification, answer } => {
+        format!("{clarification}\n→ {answer}")
+      }
+      TraceEvent::ValidationFailure { reason } => reason.clone(),
+      TraceEvent::FinalSql { sql } => sql.clone(),
+      TraceEvent::Failed { reason } => reason.clone(),
+    }
+  }
+
+  pub fn is_failure(&self) -> bool {
+    matches!(
+      self,
+      TraceEvent::LlmError { .. }
+        | TraceEvent::Introspection { failed: true, .. }
+        | TraceEvent::ValidationFailure { .. }
+        | TraceEvent::Failed { .. }
+    )
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TraceStep {
+  pub timestamp: DateTime<Local>,
+  pub event: TraceEvent,
+  /// Time the step took; zero for steps that do no I/O
+  pub latency_ms: u64,
+  /// Estimated tokens sent to or received from the LLM
+  pub tokens: usize,
+}
+
+/// The steps taken to answer one question.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Trace {
+  pub question: String,
+  pub started: DateTime<Local>,
+  pub steps: Vec<TraceStep>,
+}
+
+impl Trace {
+  pub fn new(question: &str) -> Self {
+    Self { question: question.into(), started: Local::now(), steps: vec![] }
+  }
+
+  pub fn push(&mut self, event: TraceEvent, latency: Duration, tokens: usize) {
+    self.steps.push(TraceStep {
+      timestamp: Local::now(),
+      event,
+      latency_ms: latency.as_millis() as u64,
+      tokens,
+    });
+  }
+
+  pub fn total_latency_ms(&self) -> u64 {
+    self.steps.iter().map(|s| s.latency_ms).sum()
+  }
+
+  pub fn total_tokens(&self) -> usize {
+    self.steps.iter().map(|s| s.tokens).sum()
+  }
+
+  /// The accepted SQL, if the agent got that far.
+  pub fn final_sql(&self) -> Option<&str> {
+    self.steps.iter().rev().find_map(|s| match &s.event {
+      TraceEvent::FinalSql { sql } => Some(sql.as_str()),
+      _ => None,
+    })
+  }
+}
+
+#[test]
+fn test_trace_totals_and_serialization() {
+  let mut trace = Trace::new("latest orders");
+  trace.push(TraceEvent::LlmRequest { attempt: 1, messages: 3 }, Duration::ZERO, 900);
+  trace.push(
+    TraceEvent::LlmResponse { attempt: 1, reply: "{}".into() },
+    Duration::from_millis(1200),
+    20,
+  );
+  trace.push(TraceEvent::ValidationFailure { reason: "both empty".into() }, Duration::ZERO, 0);
+  assert_eq!(trace.total_latency_ms(), 1200);
+  assert_eq!(trace.total_tokens(), 920);
+  assert!(trace.final_sql().is_none());
+  assert!(trace.steps[2].event.is_failure());
+
+  trace.push(TraceEvent::FinalSql { sql: "SELECT 1".into() }, Duration::ZERO, 0);
+  assert_eq!(trace.final_sql(), Some("SELECT 1"));
+
+  let json = serde_json::to_string(&trace).unwrap();
+  assert!(json.contains(r#""kind":"validation_failure""#));
+  assert_eq!(serde_json::from_str::<Trace>(&json).unwrap(), trace);
+}
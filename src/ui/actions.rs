@@ -1,6 +1,17 @@
 use freya::prelude::*;
 
-pub fn action_buttons(trigger_llm_query: Callback<()>, trigger_sql_query: Callback<()>) -> Element {
+pub fn action_buttons(
+  trigger_llm_query: Callback<()>,
+  trigger_sql_query: Callback<()>,
+  run_statement: Callback<()>,
+  run_selection: Callback<()>,
+  explain: Callback<()>,
+  explain_analyze: Callback<()>,
+  open_glossary: Callback<()>,
+  open_history: Callback<()>,
+  open_snippets: Callback<()>,
+  open_trace: Callback<()>,
+) -> Element {
   rsx!(
     rect {
       width: "100%",
@@ -8,6 +19,14 @@ pub fn action_buttons(trigger_llm_query: Callback<()>, trigger_sql_query: Callba
       direction: "horizontal",
       spacing: "10",
       main_align: "end",
+      Button { onclick: open_snippets, label { "Saved queries" } }
+      Button { onclick: open_history, label { "History" } }
+      Button { onclick: open_trace, label { "Agent trace" } }
+      Button { onclick: open_glossary, label { "Save to glossary" } }
+      Button { onclick: explain, label { "Explain" } }
+      Button { onclick: explain_analyze, label { "Explain Analyze" } }
+      Button { onclick: run_statement, label { "Run statement" } }
+      Button { onclick: run_selection, label { "Run selection" } }
       Button { onclick: trigger_sql_query, label { "Execute SQL" } }
       Button { onclick: trigger_llm_query, label { "Text to SQL" } }
     }
@@ -1,13 +1,25 @@
+use crate::agent::AGENT;
+use crate::db_client::TxStatus;
+use crate::snippets::SnippetLibrary;
 use freya::prelude::*;
-use tracing::instrument;
+use tracing::{error, instrument, warn};
 
 use crate::ui::actions::action_buttons;
 use crate::ui::app_state::init_state;
+use crate::ui::connection_manager::connection_manager;
 use crate::ui::connections::{llm_config_view, postgres_config_view};
 use crate::ui::editors::{ai_chat_view, sql_editor_view};
+use crate::ui::glossary_modal::glossary_modal;
 use crate::ui::handlers::init_handlers;
+use crate::ui::history_panel::history_panel;
+use crate::ui::llm_profile_editor::llm_profile_editor;
 use crate::ui::overlay_modal::modal;
 use crate::ui::results::results_table;
+use crate::ui::snippets_sidebar::snippets_sidebar;
+use crate::ui::tabs::{FileAction, file_dialog};
+use crate::ui::trace_panel::trace_panel;
+use crate::ui::transaction::{close_dialog, transaction_bar, use_close_guard};
+use std::time::Duration;
 
 #[instrument]
 pub fn app() -> Element {
@@ -15,6 +27,60 @@ pub fn app() -> Element {
   let tables = use_signal(Vec::new);
   let handlers = init_handlers(&state);
   let show_modal = use_signal(|| false);
+  let mut show_glossary_modal = use_signal(|| false);
+  let mut glossary_term = use_signal(String::new);
+  let editable_nl = state.editable_nl;
+  let open_glossary = Callback::new(move |_: ()| {
+    glossary_term.set(editable_nl.editor().read().to_string().trim().to_string());
+    show_glossary_modal.set(true);
+  });
+  let mut show_history = use_signal(|| false);
+  let open_history = Callback::new(move |_: ()| show_history.set(true));
+  let mut show_connections = use_signal(|| false);
+  let mut show_llm_profiles = use_signal(|| false);
+  let mut show_trace = use_signal(|| false);
+  let open_trace = Callback::new(move |_: ()| show_trace.set(true));
+  let mut show_snippets = use_signal(|| false);
+  let mut snippets = state.snippets;
+  let open_snippets = Callback::new(move |_: ()| {
+    spawn(async move {
+      let profile = match AGENT.get() {
+        Some(agent) => agent.active_profile.read().await.clone(),
+        None => None,
+      };
+      match SnippetLibrary::load(&SnippetLibrary::path_for(profile.as_deref())) {
+        Ok(library) => snippets.set(library),
+        Err(e) => error!("Unable to load saved queries: {e:?}"),
+      }
+      show_snippets.set(true);
+    });
+  });
+
+  let file_action = use_signal(|| None::<FileAction>);
+  let tabs = state.tabs;
+  // Autosave open tabs (including unsaved edits) so they are restored on next launch
+  use_future(move || async move {
+    let mut last_saved = None;
+    loop {
+      tokio::time::sleep(Duration::from_secs(5)).await;
+      let workspace = tabs.peek().to_workspace();
+      if last_saved.as_ref() != Some(&workspace) {
+        tabs.peek().persist();
+        last_saved = Some(workspace);
+      }
+    }
+  });
+  let tx_status = state.tx_status;
+  let show_close = use_signal(|| false);
+  use_close_guard(tx_status);
+  use_drop(move || {
+    tabs.peek().persist();
+    // e.g. closed from the task bar; the server rolls back when the pinned connection goes away
+    if *tx_status.peek() != TxStatus::Idle {
+      warn!("Closing with an open transaction, its changes are rolled back");
+    }
+  });
+  let editable_sql = state.active_editable();
 
   rsx!(
     Body {
@@ -30,22 +96,61 @@ pub fn app() -> Element {
           height: "50",
           direction: "horizontal",
           { postgres_config_view(&state.pg_config, show_modal, tables ) }
+          Button { onclick: move |_| show_connections.set(true), label { "Connections" } }
           { llm_config_view(&state.llm_config) }
+          Button { onclick: move |_| show_llm_profiles.set(true), label { "LLM profiles" } }
+          { transaction_bar(state.tx_status, show_close) }
         }
 
         rect {
           height: "400",
           direction: "horizontal",
           spacing: "15",
-          { sql_editor_view(&mut state) }
-          { ai_chat_view(&mut state) }
+          { sql_editor_view(&mut state, file_action) }
+          { ai_chat_view(&mut state, handlers.summarize_result) }
         }
 
-        { action_buttons(handlers.trigger_llm_query, handlers.trigger_sql_query) }
+        {
+          action_buttons(
+            handlers.trigger_llm_query,
+            handlers.trigger_sql_query,
+            handlers.run_statement,
+            handlers.run_selection,
+            handlers.explain,
+            handlers.explain_analyze,
+            open_glossary,
+            open_history,
+            open_snippets,
+            open_trace,
+          )
+        }
       }
-      { results_table(&state.results) }
+      { results_table(&state.active_results()) }
 
       { modal(show_modal, tables) }
+      { glossary_modal(show_glossary_modal, editable_sql, glossary_term) }
+      { history_panel(show_history, state.history, editable_sql, editable_nl) }
+      { trace_panel(show_trace, state.conversation) }
+      {
+        connection_manager(
+          show_connections,
+          state.pg_config,
+          state.tx_status,
+          state.conversation,
+          state.llm_busy,
+        )
+      }
+      { llm_profile_editor(show_llm_profiles, state.llm_config) }
+      { file_dialog(file_action, tabs) }
+      { close_dialog(show_close, state.tx_status) }
+      {
+        snippets_sidebar(
+          show_snippets,
+          state.snippets,
+          editable_sql,
+          handlers.run_selection,
+        )
+      }
     }
   )
 }
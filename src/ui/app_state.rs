@@ -1,18 +1,67 @@
 use crate::agent::AGENT;
-use crate::config::LlmConfig;
+use crate::config::{LlmConfig, Setting};
 use crate::conversation::Conversation;
-use crate::ui::results::TableData;
+use crate::db_client::TxStatus;
+use crate::history::HistoryStore;
+use crate::sessions::{Session, SessionStore};
+use crate::snippets::SnippetLibrary;
+use crate::ui::completion_popup::SchemaResource;
+use crate::ui::results::QueryResults;
+use crate::ui::tabs::{EditorTabs, use_editor_tabs};
 use freya::prelude::*;
+use std::time::Duration;
 
 pub struct AppState {
   pub focus_sql: UseFocus,
   pub focus_text: UseFocus,
-  pub editable_sql: UseEditable,
+  pub tabs: Signal<EditorTabs>,
   pub editable_nl: UseEditable,
-  pub results: Signal<TableData>,
   pub pg_config: Resource<PostgresStatus>,
   pub llm_config: Resource<LlmStatus>,
+  /// Cached schema model for editor completions, loaded once connected
+  pub schema: SchemaResource,
   pub conversation: Signal<Conversation>,
+  /// Chat session the conversation is saved to; its own `conversation` copy is only filled on
+  /// save
+  pub session: Signal<Session>,
+  /// Set while the LLM works on the conversation. The agent works on a copy that replaces the
+  /// conversation on every step, so it is not reset, answered or switched meanwhile.
+  pub llm_busy: Signal<bool>,
+  pub history: Signal<HistoryStore>,
+  pub snippets: Signal<SnippetLibrary>,
+  pub tx_status: Signal<TxStatus>,
+  /// `execution.stop_on_error` of config.toml, read at startup
+  pub stop_on_error: bool,
+}
+
+/// How long the conversation must stay unchanged before it is saved
+const SESSION_SAVE_DELAY: Duration = Duration::from_secs(1);
+
+/// Writes `session` with the current conversation on the blocking pool, unless the conversation
+/// is empty.
+pub fn save_session(session: Signal<Session>, conversation: Signal<Conversation>) {
+  let conversation = conversation.peek().clone();
+  if conversation.messages.is_empty() && conversation.queries.is_empty() {
+    return;
+  }
+  // the session's own conversation copy is stale, so it is not cloned
+  let mut current = {
+    let session = session.peek();
+    Session {
+      id: session.id.clone(),
+      name: session.name.clone(),
+      created: session.created,
+      updated: session.updated,
+      conversation,
+    }
+  };
+  spawn(async move {
+    let saved =
+      tokio::task::spawn_blocking(move || SessionStore::default().save(&mut current)).await;
+    if !matches!(saved, Ok(Ok(_))) {
+      tracing::error!("Unable to save chat session: {saved:?}");
+    }
+  });
 }
 
 #[derive(Debug, Clone)]
@@ -28,21 +77,26 @@ pub enum LlmStatus {
   Connected { config: LlmConfig },
 }
 
+impl AppState {
+  pub fn active_editable(&self) -> UseEditable {
+    self.tabs.read().active_editable()
+  }
+
+  pub fn active_results(&self) -> Signal<QueryResults> {
+    self.tabs.read().active_results()
+  }
+}
+
 pub fn init_state() -> AppState {
   let focus_sql = use_focus();
   let focus_text = use_focus();
 
-  let editable_sql = use_editable(
-    || EditableConfig::new("".into()).with_allow_tabs(true),
-    EditableMode::MultipleLinesSingleEditor,
-  );
+  let tabs = use_editor_tabs();
   let editable_nl = use_editable(
     || EditableConfig::new("".into()).with_allow_tabs(true),
     EditableMode::MultipleLinesSingleEditor,
   );
 
-  let results = use_signal(|| TableData { headers: vec![], rows: vec![] });
-
   let pg_config = use_resource(move || async move {
     if let Some(agent) = AGENT.get() {
       let guard = agent.db_client.config.lock().await;
@@ -52,13 +106,7 @@ pub fn init_state() -> AppState {
         match agent.db_client.try_connect().await {
           Ok(_) => {
             let tables = agent.db_client.list_tables().await.unwrap_or_default();
-            PostgresStatus::Connected {
-              config: format!(
-                "postgresql://{}:{}@{}/{}",
-                conf.user, conf.password, conf.host, conf.dbname
-              ),
-              tables,
-            }
+            PostgresStatus::Connected { config: conf.redacted_url(), tables }
           }
           Err(e) => PostgresStatus::ConnectionFailed(e.to_string()),
         }
@@ -82,16 +130,74 @@ pub fn init_state() -> AppState {
     }
   });
 
-  let conversation = use_signal(Conversation::new);
+  let schema = use_resource(move || async move {
+    // wait for the connection status so the model is loaded once the client is set up
+    let _ = pg_config.read();
+    let agent = AGENT.get()?;
+    match agent.db_client.schema_model().await {
+      Ok(model) => Some(model),
+      Err(e) => {
+        tracing::debug!("Schema model not available: {e:?}");
+        None
+      }
+    }
+  });
+
+  // resume the most recent session
+  let session = use_signal(|| {
+    SessionStore::default()
+      .list()
+      .unwrap_or_else(|e| {
+        tracing::error!("Unable to list chat sessions: {e:?}");
+        vec![]
+      })
+      .into_iter()
+      .next()
+      .unwrap_or_else(Session::untitled)
+  });
+  let conversation = use_signal(|| session.peek().conversation.clone());
+  // every agent step rewrites the conversation, so it is saved once it has been quiet a moment
+  let mut changes = use_signal(|| 0_u64);
+  use_effect(move || {
+    let _ = conversation.read();
+    let change = *changes.peek() + 1;
+    changes.set(change);
+    spawn(async move {
+      tokio::time::sleep(SESSION_SAVE_DELAY).await;
+      if *changes.peek() == change {
+        save_session(session, conversation);
+      }
+    });
+  });
+
+  let llm_busy = use_signal(|| false);
+
+  let history = use_signal(|| {
+    HistoryStore::load(&HistoryStore::default_path()).unwrap_or_else(|e| {
+      tracing::error!("Unable to load query history: {e:?}");
+      HistoryStore::default()
+    })
+  });
+
+  let snippets = use_signal(SnippetLibrary::default);
+  let tx_status = use_signal(TxStatus::default);
+  let stop_on_error =
+    use_hook(|| Setting::try_load().map(|s| s.execution.stop_on_error).unwrap_or(true));
 
   AppState {
     focus_sql,
     focus_text,
-    editable_sql,
+    tabs,
     editable_nl,
-    results,
     pg_config,
     llm_config,
+    schema,
     conversation,
+    session,
+    llm_busy,
+    history,
+    snippets,
+    tx_status,
+    stop_on_error,
   }
 }
@@ -0,0 +1,164 @@
+use crate::schema::SchemaModel;
+use crate::sql::completion::{CompletionKind, Completions, complete};
+use freya::prelude::*;
+use std::sync::Arc;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompletionPopup {
+  pub completions: Completions,
+  pub selected: usize,
+}
+
+pub type SchemaResource = Resource<Option<Arc<SchemaModel>>>;
+
+/// Recomputes completions for the word under the cursor; hides the popup when there are none.
+pub fn refresh_completions(
+  editable: UseEditable,
+  schema: SchemaResource,
+  mut popup: Signal<Option<CompletionPopup>>,
+) {
+  let model = schema.read_unchecked().clone().flatten().unwrap_or_default();
+  let (text, cursor) = {
+    let editor = editable.editor().read();
+    (editor.to_string(), editor.cursor_pos())
+  };
+  let completions = complete(&text, cursor, &model);
+  if completions.items.is_empty() {
+    popup.set(None);
+  } else {
+    popup.set(Some(CompletionPopup { completions, selected: 0 }));
+  }
+}
+
+/// Replaces the word under the cursor with the selected completion.
+pub fn accept_completion(mut editable: UseEditable, mut popup: Signal<Option<CompletionPopup>>) {
+  let Some(state) = popup.write().take() else {
+    return;
+  };
+  let Some(item) = state.completions.items.get(state.selected) else {
+    return;
+  };
+  let text = editable.editor().read().to_string();
+  let head: String = text.chars().take(state.completions.start).collect();
+  let tail: String = text.chars().skip(state.completions.end).collect();
+  let mut editor = editable.editor_mut().write();
+  editor.set(&format!("{head}{}{tail}", item.insert));
+  editor.set_cursor_pos(state.completions.start + item.insert.chars().count());
+}
+
+/// Handles navigation keys while the popup is open and Ctrl+Space to open it. Returns `true`
+/// when the key was consumed and must not reach the editor.
+pub fn handle_completion_key(
+  data: &KeyboardData,
+  editable: UseEditable,
+  mut schema: SchemaResource,
+  mut popup: Signal<Option<CompletionPopup>>,
+) -> bool {
+  if data.modifiers.contains(Modifiers::CONTROL) && data.key == Key::Character(" ".into()) {
+    // explicit request: also retry loading the schema if it was not available yet
+    if matches!(*schema.read_unchecked(), Some(None)) {
+      schema.restart();
+    }
+    refresh_completions(editable, schema, popup);
+    return true;
+  }
+
+  let Some(len) = popup.read().as_ref().map(|p| p.completions.items.len()) else {
+    return false;
+  };
+  match data.key {
+    Key::ArrowDown => {
+      if let Some(p) = popup.write().as_mut() {
+        p.selected = (p.selected + 1) % len;
+      }
+      true
+    }
+    Key::ArrowUp => {
+      if let Some(p) = popup.write().as_mut() {
+        p.selected = (p.selected + len - 1) % len;
+      }
+      true
+    }
+    Key::Enter | Key::Tab => {
+      accept_completion(editable, popup);
+      true
+    }
+    Key::Escape => {
+      popup.set(None);
+      true
+    }
+    _ => false,
+  }
+}
+
+/// Whether a key that reached the editor should (re)open completions.
+pub fn triggers_completion(key: &Key, popup_open: bool) -> bool {
+  match key {
+    Key::Character(c) => c.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '.'),
+    Key::Backspace => popup_open,
+    _ => false,
+  }
+}
+
+fn kind_badge(kind: CompletionKind) -> (&'static str, &'static str) {
+  match kind {
+    CompletionKind::Join => ("J", "rgb(150,80,0)"),
+    CompletionKind::Column => ("C", "rgb(0,120,60)"),
+    CompletionKind::Table => ("T", "rgb(0,70,190)"),
+    CompletionKind::Schema => ("S", "rgb(120,60,150)"),
+    CompletionKind::Function => ("F", "rgb(180,0,120)"),
+    CompletionKind::Keyword => ("K", "rgb(100,100,100)"),
+  }
+}
+
+pub fn completion_popup(popup: Signal<Option<CompletionPopup>>, editable: UseEditable) -> Element {
+  let Some(state) = popup.read().clone() else {
+    return rsx!();
+  };
+
+  rsx!(
+    rect {
+      position: "absolute",
+      position_top: "40",
+      position_right: "15",
+      layer: "-50",
+      width: "320",
+      max_height: "220",
+      padding: "4",
+      corner_radius: "6",
+      background: "white",
+      border: "1 solid rgb(200,200,200)",
+      shadow: "0 4 10 0 rgb(0,0,0,0.2)",
+      ScrollView {
+        for (i, item) in state.completions.items.iter().enumerate() {
+          rect {
+            key: "{i}",
+            width: "100%",
+            direction: "horizontal",
+            spacing: "6",
+            padding: "2 4",
+            corner_radius: "4",
+            background: if i == state.selected { "rgb(225,225,245)" } else { "transparent" },
+            onclick: {
+              let mut popup = popup;
+              move |_| {
+                if let Some(p) = popup.write().as_mut() {
+                  p.selected = i;
+                }
+                accept_completion(editable, popup);
+              }
+            },
+            label {
+              font_size: "11",
+              font_weight: "bold",
+              color: kind_badge(item.kind).1,
+              "{kind_badge(item.kind).0}"
+            }
+            label { font_size: "12", max_lines: "1", "{item.insert}" }
+            label { font_size: "10", color: "rgb(130,130,130)", max_lines: "1", "{item.detail}" }
+          }
+        }
+      }
+    }
+  )
+}
@@ -0,0 +1,229 @@
+use crate::agent::AGENT;
+use crate::config::{ConfigDocument, PostgresConfig};
+use crate::conversation::Conversation;
+use crate::db_client::TxStatus;
+use crate::secrets::SecretSource;
+use crate::ui::app_state::PostgresStatus;
+use crate::ui::overlay_modal::overlay;
+use crate::ui::profile_form::{
+  ProfileFields, field, profile_list, status_line, use_profile_editor,
+};
+use crate::ui::transaction::refresh_tx_status;
+use anyhow::anyhow;
+use freya::prelude::*;
+use std::collections::BTreeMap;
+
+/// Text fields of the profile being edited.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct ProfileForm {
+  name: String,
+  url: String,
+  service: String,
+  host: String,
+  port: String,
+  user: String,
+  password: String,
+  dbname: String,
+  /// Kept from the saved profile; the form only edits a literal password
+  password_from: Option<SecretSource>,
+}
+
+impl ProfileFields for ProfileForm {
+  type Config = PostgresConfig;
+
+  fn new(name: &str, config: &PostgresConfig) -> Self {
+    Self {
+      name: name.into(),
+      url: config.url.clone().unwrap_or_default(),
+      service: config.service.clone().unwrap_or_default(),
+      host: config.host.clone(),
+      // 0 means unset
+      port: if config.port == 0 { String::new() } else { config.port.to_string() },
+      user: config.user.clone(),
+      password: config.password.clone(),
+      dbname: config.dbname.clone(),
+      password_from: config.password_from.clone(),
+    }
+  }
+
+  fn name(&self) -> &str {
+    &self.name
+  }
+
+  fn name_mut(&mut self) -> &mut String {
+    &mut self.name
+  }
+
+  fn to_config(&self) -> anyhow::Result<(String, PostgresConfig)> {
+    let name = self.name.trim();
+    if name.is_empty() {
+      return Err(anyhow!("A profile name is required"));
+    }
+    let port = match self.port.trim() {
+      "" => 0,
+      port => port.parse().map_err(|_| anyhow!("Invalid port '{port}'"))?,
+    };
+    let optional = |text: &str| Some(text.trim().to_string()).filter(|text| !text.is_empty());
+    let config = PostgresConfig {
+      url: optional(&self.url),
+      service: optional(&self.service),
+      host: self.host.trim().into(),
+      port,
+      user: self.user.trim().into(),
+      password: self.password.clone(),
+      dbname: self.dbname.trim().into(),
+      password_from: self.password_from.clone(),
+    };
+    Ok((name.into(), config))
+  }
+  fn profiles(doc: &ConfigDocument) -> BTreeMap<String, PostgresConfig> {
+    doc.postgres_profiles()
+  }
+
+  fn active(doc: &ConfigDocument) -> String {
+    doc.active_postgres()
+  }
+
+  fn set_profile(doc: &mut ConfigDocument, name: &str, config: &PostgresConfig) {
+    doc.set_postgres_profile(name, config);
+  }
+
+  fn remove_profile(doc: &mut ConfigDocument, name: &str) -> anyhow::Result<()> {
+    doc.remove_postgres_profile(name)
+  }
+}
+
+/// Dialog to create, edit, test, duplicate and delete the Postgres profiles of config.toml and
+/// to connect to one of them.
+pub fn connection_manager(
+  mut show_manager: Signal<bool>,
+  mut pg_status: Resource<PostgresStatus>,
+  tx_status: Signal<TxStatus>,
+  mut conversation: Signal<Conversation>,
+  llm_busy: Signal<bool>,
+) -> Element {
+  let editor = use_profile_editor::<ProfileForm>(show_manager);
+  let form = editor.form;
+  let mut status = editor.status;
+  // switching with an open transaction needs a second click
+  let mut confirm_switch = use_signal(|| false);
+
+  use_effect(move || {
+    if show_manager() {
+      confirm_switch.set(false);
+    }
+  });
+
+  let test = move |_| {
+    let Some((_, config)) = editor.checked_config() else {
+      return;
+    };
+    status.set("Connecting...".into());
+    spawn(async move {
+      let Some(agent) = AGENT.get() else {
+        return;
+      };
+      match agent.db_client.test_connection(&config).await {
+        Ok(version) => status.set(format!("Connected, server version {version}")),
+        Err(e) => status.set(format!("Connection failed: {e:#}")),
+      }
+    });
+  };
+
+  let switch = move |_| {
+    let Some((name, config)) = editor.checked_config() else {
+      return;
+    };
+    // the running generation would put the cleared schema back
+    if *llm_busy.peek() {
+      return status.set("Wait for the LLM to finish before switching".into());
+    }
+    if *tx_status.peek() != TxStatus::Idle && !confirm_switch() {
+      confirm_switch.set(true);
+      status.set(
+        "A transaction is open and will be rolled back. Press Connect again to switch.".into(),
+      );
+      return;
+    }
+    confirm_switch.set(false);
+    status.set(format!("Connecting to '{name}'..."));
+    spawn(async move {
+      let Some(agent) = AGENT.get() else {
+        return;
+      };
+      if let Err(e) = agent.switch_postgres_profile(&name, config.clone()).await {
+        status.set(format!("Connection failed: {e:#}"));
+        return;
+      }
+      // columns learned from the previous database don't describe this one
+      conversation.write().known_tables.clear();
+      refresh_tx_status(tx_status).await;
+      pg_status.restart();
+      let saved = editor.edit_document(|doc| {
+        doc.set_postgres_profile(&name, &config);
+        doc.set_active_postgres(&name);
+        Ok(())
+      });
+      match saved {
+        Ok(_) => status.set(format!("Connected to '{name}'")),
+        Err(e) => status.set(format!("Connected to '{name}', but config.toml was not saved: {e}")),
+      }
+    });
+  };
+
+  let current = form.read().clone();
+  let password_hint = match &current.password_from {
+    Some(source) => format!("Empty: the password comes from {source}"),
+    None => "Empty: the password comes from ~/.pgpass, if it has one".to_string(),
+  };
+
+  rsx! {
+    if show_manager() {
+      {overlay(move || show_manager.set(false), rsx! {
+        rect {
+          width: "650",
+          height: "470",
+          padding: "12",
+          background: "white",
+          corner_radius: "8",
+          direction: "horizontal",
+          spacing: "12",
+          { profile_list(editor, "Postgres profiles", move || confirm_switch.set(false)) }
+          rect {
+            width: "fill",
+            height: "100%",
+            spacing: "8",
+            { field(form, "Name", current.name.clone(), false, |f, v| f.name = v) }
+            { field(form, "URL", current.url.clone(), false, |f, v| f.url = v) }
+            { field(form, "Service", current.service.clone(), false, |f, v| f.service = v) }
+            label {
+              font_size: "10",
+              color: "rgb(100,100,100)",
+              "Empty fields come from the URL, then the service, then PGHOST, PGUSER, ..."
+            }
+            { field(form, "Host", current.host.clone(), false, |f, v| f.host = v) }
+            { field(form, "Port", current.port.clone(), false, |f, v| f.port = v) }
+            { field(form, "User", current.user.clone(), false, |f, v| f.user = v) }
+            { field(form, "Password", current.password.clone(), true, |f, v| f.password = v) }
+            if current.password.is_empty() {
+              label { font_size: "10", color: "rgb(100,100,100)", "{password_hint}" }
+            }
+            { field(form, "Database", current.dbname.clone(), false, |f, v| f.dbname = v) }
+            { status_line(status) }
+            rect {
+              width: "100%",
+              direction: "horizontal",
+              main_align: "end",
+              spacing: "8",
+              Button { onclick: move |_| editor.delete(), label { "Delete" } }
+              Button { onclick: test, label { "Test" } }
+              Button { onclick: move |_| editor.save(), label { "Save" } }
+              Button { onclick: switch, label { "Connect" } }
+              Button { onclick: move |_| show_manager.set(false), label { "Close" } }
+            }
+          }
+        }
+      })}
+    }
+  }
+}
@@ -1,9 +1,25 @@
+use crate::conversation::ANSWER_ROLE;
+use crate::sql::highlight::highlight;
 use crate::ui::app_state::AppState;
+use crate::ui::completion_popup::{
+  CompletionPopup, completion_popup, handle_completion_key, refresh_completions,
+  triggers_completion,
+};
+use crate::ui::handlers::summarizable;
+use crate::ui::session_switcher::session_switcher;
+use crate::ui::tabs::{FileAction, tabs_bar};
 use freya::prelude::*;
 
-pub fn sql_editor_view(state: &mut AppState) -> Element {
+pub fn sql_editor_view(state: &mut AppState, file_dialog: Signal<Option<FileAction>>) -> Element {
   let mut focus_sql = state.focus_sql;
-  let mut editable_sql = state.editable_sql;
+  let mut editable_sql = state.active_editable();
+  let tabs = state.tabs;
+  let spans = use_memo(move || {
+    let sql = tabs.read().active_editable().editor().read().to_string();
+    highlight(&sql)
+  });
+  let schema = state.schema;
+  let mut popup = use_signal(|| None::<CompletionPopup>);
   rsx!(
     rect {
       width: "calc(75%-10)",
@@ -13,6 +29,7 @@ pub fn sql_editor_view(state: &mut AppState) -> Element {
       corner_radius: "6",
       border: "0.3 inner black",
       label { "SQL:" }
+      { tabs_bar(state.tabs, file_dialog) }
       ScrollView {
         paragraph {
           width: "100%",
@@ -21,11 +38,12 @@ pub fn sql_editor_view(state: &mut AppState) -> Element {
           cursor_index: "{editable_sql.editor().read().cursor_pos()}",
           cursor_mode: "editable",
           cursor_color: "black",
-          highlights: state.editable_sql.highlights_attr(0),
-          cursor_reference: state.editable_sql.cursor_attr(),
+          highlights: editable_sql.highlights_attr(0),
+          cursor_reference: editable_sql.cursor_attr(),
           a11y_id: state.focus_sql.attribute(),
           onclick: move |_: Event<MouseData>| {
             focus_sql.request_focus();
+            popup.set(None);
             editable_sql.process_event(&EditableEvent::Click);
           },
           onmousemove: move |e: Event<MouseData>| {
@@ -35,23 +53,45 @@ pub fn sql_editor_view(state: &mut AppState) -> Element {
             editable_sql.process_event(&EditableEvent::MouseDown(e.data, 0));
           },
           onkeydown: move |e: Event<KeyboardData>| {
+            if handle_completion_key(&e.data, editable_sql, schema, popup) {
+              return;
+            }
+            let key = e.data.key.clone();
             editable_sql.process_event(&EditableEvent::KeyDown(e.data));
+            if triggers_completion(&key, popup.read().is_some()) {
+              refresh_completions(editable_sql, schema, popup);
+            } else {
+              popup.set(None);
+            }
           },
           onglobalkeyup: move |e: Event<KeyboardData>| {
             editable_sql.process_event(&EditableEvent::KeyUp(e.data));
           },
-          text { "{editable_sql.editor()}" }
+          for (i, (style, span)) in spans.read().iter().enumerate() {
+            text {
+              key: "{i}",
+              color: style.color(),
+              font_weight: style.font_weight(),
+              font_style: style.font_style(),
+              "{span}"
+            }
+          }
         }
       }
+      { completion_popup(popup, editable_sql) }
     }
   )
 }
 
-pub fn ai_chat_view(state: &mut AppState) -> Element {
+pub fn ai_chat_view(state: &mut AppState, summarize_result: Callback<()>) -> Element {
   let mut focus_text = state.focus_text;
   let mut editable_nl = state.editable_nl;
 
-  let conv = state.conversation.read();
+  let mut conversation = state.conversation;
+  let llm_busy = *state.llm_busy.read();
+  let conv = conversation.read();
+  // only the result of the current query answers its question
+  let can_summarize = !llm_busy && summarizable(&conv, &state.active_results().read()).is_some();
   let mut scroll_controller = use_scroll_controller(|| ScrollConfig {
     default_vertical_position: ScrollPosition::End,
     ..Default::default()
@@ -68,9 +108,10 @@ pub fn ai_chat_view(state: &mut AppState) -> Element {
       border: "0.3 inner black",
       padding: "15 3",
       background: "rgb(233, 233, 233)",
-      //
+      content: "flex",
+      { session_switcher(state.session, conversation, state.llm_busy) }
         rect {
-          height: "75%",
+          height: "flex(1)",
           spacing: "7",
           ScrollView {
             scroll_controller: scroll_controller,
@@ -85,6 +126,8 @@ pub fn ai_chat_view(state: &mut AppState) -> Element {
                   "rgb(240,240,255)"
                 } else if msg.role == "assistant" {
                   "rgb(240,255,240)"
+                } else if msg.role == ANSWER_ROLE {
+                  "rgb(255,250,225)"
                 } else {
                   "rgb(255,240,240)"
                 }).to_string(),
@@ -111,11 +154,29 @@ pub fn ai_chat_view(state: &mut AppState) -> Element {
         background: "rgb(250,250,250)",
         corner_radius: "6",
         border: "0.3 inner black",
-        label {
-          font_size: "12",
-          font_weight: "light",
-          "Ask LLM:"
-        },
+        rect {
+          width: "100%",
+          direction: "horizontal",
+          content: "flex",
+          cross_align: "center",
+          label {
+            width: "flex(1)",
+            font_size: "12",
+            font_weight: "light",
+            "Ask LLM:"
+          }
+          if can_summarize {
+            Button { onclick: summarize_result, label { "Summarize result" } }
+          }
+          if llm_busy {
+            label { font_size: "11", color: "rgb(120,120,120)", "Waiting for the LLM…" }
+          } else if !conv.messages.is_empty() {
+            Button {
+              onclick: move |_| conversation.write().reset(),
+              label { "New question" }
+            }
+          }
+        }
         paragraph {
           width: "100%",
           height: "100%",
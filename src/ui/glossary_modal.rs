@@ -0,0 +1,106 @@
+use crate::agent::AGENT;
+use crate::glossary::{GlossaryEntry, GlossaryKind};
+use crate::ui::overlay_modal::overlay;
+use freya::prelude::*;
+use tracing::error;
+
+/// Modal to save the (corrected) query in the SQL editor as a glossary entry.
+/// The term defaults to the last natural language question.
+pub fn glossary_modal(
+  mut show_modal: Signal<bool>,
+  editable_sql: UseEditable,
+  mut term: Signal<String>,
+) -> Element {
+  let mut description = use_signal(String::new);
+  let mut kind = use_signal(|| GlossaryKind::Query);
+  let mut status = use_signal(String::new);
+
+  let save = move |_: ()| {
+    let entry = GlossaryEntry {
+      term: term.read().trim().to_string(),
+      aliases: vec![],
+      kind: *kind.read(),
+      sql: editable_sql.editor().read().to_string(),
+      description: description.read().trim().to_string(),
+      joins: vec![],
+    };
+    if entry.term.is_empty() || entry.sql.trim().is_empty() {
+      status.set("Both a term and SQL are required".into());
+      return;
+    }
+    spawn(async move {
+      let Some(agent) = AGENT.get() else {
+        status.set("Agent not initialized".into());
+        return;
+      };
+      match agent.glossary.write().await.upsert(entry) {
+        Ok(_) => {
+          status.set(String::new());
+          description.set(String::new());
+          show_modal.set(false);
+        }
+        Err(e) => {
+          error!("Unable to save glossary entry: {e:?}");
+          status.set(format!("{e}"));
+        }
+      }
+    });
+  };
+
+  rsx! {
+    if show_modal() {
+      {overlay(move || show_modal.set(false), rsx! {
+        rect {
+          width: "500",
+          padding: "12",
+          background: "white",
+          corner_radius: "8",
+          direction: "vertical",
+          spacing: "8",
+          label { font_weight: "bold", "Save query to glossary" }
+          label { font_size: "12", "Term" }
+          Input {
+            value: term.read().clone(),
+            onchange: move |v: String| term.set(v),
+          }
+          label { font_size: "12", "Description" }
+          Input {
+            value: description.read().clone(),
+            onchange: move |v: String| description.set(v),
+          }
+          rect {
+            direction: "horizontal",
+            spacing: "5",
+            for k in [GlossaryKind::Query, GlossaryKind::Metric, GlossaryKind::Filter, GlossaryKind::Join] {
+              Button {
+                key: "{k:?}",
+                onclick: move |_| kind.set(k),
+                label {
+                  font_weight: if *kind.read() == k { "bold" } else { "normal" },
+                  "{k:?}"
+                }
+              }
+            }
+          }
+          label {
+            font_size: "10",
+            max_lines: "4",
+            text_overflow: "ellipsis",
+            "{editable_sql.editor()}"
+          }
+          if !status.read().is_empty() {
+            label { color: "rgb(200,0,0)", "{status}" }
+          }
+          rect {
+            width: "100%",
+            direction: "horizontal",
+            main_align: "end",
+            spacing: "10",
+            Button { onclick: move |_| show_modal.set(false), label { "Cancel" } }
+            Button { onclick: save, label { "Save" } }
+          }
+        }
+      })}
+    }
+  }
+}
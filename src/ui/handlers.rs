@@ -1,115 +1,346 @@
 use crate::agent::AGENT;
-use crate::conversation::Conversation;
+use crate::conversation::{Conversation, ConversationObserver};
+use crate::db_client::{ResultSet, ServerNotice};
+use crate::history::{HistoryEntry, HistoryKind, HistoryStore};
+use crate::sql::splitter::{
+  Statement, normalize_statement, selected_statements, split_statements, statement_at,
+};
 use crate::ui::app_state::AppState;
-use crate::ui::results::TableData;
+use crate::ui::results::{QueryResults, ResultTab, TableData};
+use crate::ui::transaction::refresh_tx_status;
 use freya::prelude::*;
+use std::time::Instant;
 use tracing::error;
 
 pub struct AppHandlers {
+  /// Runs every statement of the active tab
   pub trigger_sql_query: Callback<()>,
+  pub run_statement: Callback<()>,
+  pub run_selection: Callback<()>,
+  pub explain: Callback<()>,
+  pub explain_analyze: Callback<()>,
   pub trigger_llm_query: Callback<()>,
+  /// Written answer to the last question from the active result
+  pub summarize_result: Callback<()>,
 }
 
-fn format_cell(row: &tokio_postgres::Row, i: usize) -> String {
-  let col = &row.columns()[i];
-  let t = col.type_();
+async fn record_history(
+  history: &mut Signal<HistoryStore>,
+  kind: HistoryKind,
+  sql: &str,
+  question: Option<&str>,
+  started: Instant,
+  outcome: Result<Option<usize>, String>,
+) {
+  let profile = match AGENT.get() {
+    Some(agent) => agent.active_profile.read().await.clone(),
+    None => None,
+  };
+  let (row_count, error) = match outcome {
+    Ok(rows) => (rows, None),
+    Err(e) => (None, Some(e)),
+  };
+  let entry = HistoryEntry {
+    kind,
+    timestamp: chrono::Local::now(),
+    profile,
+    sql: sql.to_string(),
+    question: question.map(str::to_string),
+    duration_ms: started.elapsed().as_millis() as u64,
+    row_count,
+    error,
+  };
+  if let Err(e) = history.write().record(entry) {
+    error!("Unable to record query history: {e:?}");
+  }
+}
 
-  match *t {
-    tokio_postgres::types::Type::INT4 => {
-      row.get::<usize, Option<i32>>(i).map(|v| v.to_string()).unwrap_or("NULL".into())
-    }
-    tokio_postgres::types::Type::VARCHAR | tokio_postgres::types::Type::TEXT => {
-      row.get::<usize, Option<String>>(i).unwrap_or("NULL".into())
-    }
-    tokio_postgres::types::Type::TIMESTAMP => row
-      .get::<usize, Option<chrono::NaiveDateTime>>(i)
-      .map(|v| v.to_string())
-      .unwrap_or("NULL".into()),
-    tokio_postgres::types::Type::DATE => {
-      row.get::<usize, Option<chrono::NaiveDate>>(i).map(|v| v.to_string()).unwrap_or("NULL".into())
+async fn execute_sql_query(
+  sql: &str,
+  history: &mut Signal<HistoryStore>,
+) -> (Result<Vec<ResultSet>, String>, Vec<ServerNotice>) {
+  let agent = AGENT.get().unwrap();
+  let started = Instant::now();
+  match agent.db_client.execute(sql).await {
+    Ok(execution) => {
+      let rows = execution.result_sets.last().and_then(|s| s.row_count).map(|n| n as usize);
+      record_history(history, HistoryKind::Executed, sql, None, started, Ok(rows)).await;
+      (Ok(execution.result_sets), execution.notices)
     }
-    _ => {
-      format!("<unhandled {t:?}>")
+    Err(e) => {
+      record_history(history, HistoryKind::Executed, sql, None, started, Err(format!("{e}"))).await;
+      // the notices sent before the error are part of its message
+      (Err(format!("{e}")), vec![])
     }
   }
 }
 
-fn rows_to_table(rows: Vec<tokio_postgres::Row>) -> TableData {
-  if rows.is_empty() {
-    return TableData { headers: vec!["0 rows returned".into()], rows: vec![] };
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RunMode {
+  /// The statement under the cursor
+  Statement,
+  /// Statements inside the selection, or the current statement when nothing is selected
+  Selection,
+  All,
+}
+
+fn statements_to_run(editable: &UseEditable, mode: RunMode) -> Vec<Statement> {
+  let editor = editable.editor().read();
+  let sql = editor.to_string();
+  let cursor = editor.cursor_pos();
+  match (mode, editor.get_selection()) {
+    (RunMode::All, _) => split_statements(&sql),
+    (RunMode::Selection, Some((start, end))) if start != end => {
+      selected_statements(&sql, start, end)
+    }
+    _ => statement_at(&sql, cursor).into_iter().collect(),
   }
+}
 
-  let headers = rows[0].columns().iter().map(|c| c.name().to_string()).collect::<Vec<_>>();
+fn result_title(index: usize, sql: &str) -> String {
+  let line = sql.split_whitespace().collect::<Vec<_>>().join(" ");
+  let short: String = line.chars().take(30).collect();
+  if short.len() < line.len() {
+    format!("{index}: {short}…")
+  } else {
+    format!("{index}: {short}")
+  }
+}
 
-  let data = rows
-    .iter()
-    .map(|row| {
-      row.columns().iter().enumerate().map(|(i, _)| format_cell(row, i)).collect::<Vec<_>>()
-    })
-    .collect::<Vec<_>>();
+/// Runs statements one by one, adding a result tab for each as it completes.
+async fn run_statements(
+  statements: Vec<Statement>,
+  results: &mut Signal<QueryResults>,
+  history: &mut Signal<HistoryStore>,
+  conversation: &mut Signal<Conversation>,
+  stop_on_error: bool,
+) {
+  results.set(QueryResults::default());
 
-  TableData { headers, rows: data }
+  for (i, statement) in statements.iter().enumerate() {
+    let title = result_title(i + 1, &statement.text);
+    let (outcome, notices) = execute_sql_query(&statement.text, history).await;
+    let failed = outcome.is_err();
+    conversation.write().record_query(HistoryKind::Executed, &statement.text);
+    // the result schema of the generated query lets follow-up questions refer to its columns
+    if let Some(set) = outcome.as_ref().ok().and_then(|sets| sets.iter().find(|s| s.returns_rows()))
+    {
+      conversation.write().record_result_columns(&statement.text, &set.columns);
+    }
+    let tabs = match outcome {
+      Ok(sets) => sets
+        .iter()
+        .enumerate()
+        .map(|(j, set)| ResultTab {
+          // numbered when the server returns several result sets for one statement
+          title: if sets.len() > 1 { format!("{title} ({})", j + 1) } else { title.clone() },
+          table: set.into(),
+          is_error: false,
+          status: set.status(),
+          sql: Some(statement.text.clone()),
+        })
+        .collect(),
+      Err(e) => vec![ResultTab {
+        title,
+        table: TableData::error(e),
+        is_error: true,
+        status: None,
+        sql: Some(statement.text.clone()),
+      }],
+    };
+    {
+      let mut results = results.write();
+      results.tabs.extend(tabs);
+      results.active = results.tabs.len().saturating_sub(1);
+      results.messages.extend(notices.iter().map(|n| n.to_string()));
+    }
+    if failed && stop_on_error {
+      if statements.len() > i + 1 {
+        let skipped = statements.len() - i - 1;
+        results.write().tabs.push(ResultTab {
+          title: format!("{skipped} skipped"),
+          table: TableData {
+            headers: vec!["Skipped after error".into()],
+            rows: statements[i + 1..].iter().map(|s| vec![s.text.clone()]).collect(),
+          },
+          is_error: false,
+          status: None,
+          sql: None,
+        });
+      }
+      break;
+    }
+  }
 }
 
-async fn execute_sql_query(sql: &str) -> TableData {
-  let agent = AGENT.get().unwrap();
-  match agent.db_client.query(sql).await {
-    Ok(rows) => rows_to_table(rows),
-    Err(e) => TableData { headers: vec!["Error".into()], rows: vec![vec![format!("{e}")]] },
+/// Lets the agent update the chat view while it works.
+impl ConversationObserver for Signal<Conversation> {
+  fn updated(&mut self, conversation: &Conversation) {
+    self.set(conversation.clone());
   }
 }
 
 async fn llm_to_sql_and_update(
   editable_sql: &mut UseEditable,
   text_query: &str,
-  results: &mut Signal<TableData>,
-  conversation: Signal<Conversation>,
+  results: &mut Signal<QueryResults>,
+  mut conversation: Signal<Conversation>,
+  history: &mut Signal<HistoryStore>,
 ) {
   let Some(agent) = AGENT.get() else {
     error!("Agent not initialized");
-    results.set(TableData {
-      headers: vec!["Error".into()],
-      rows: vec![vec!["Agent not initialized".to_string()]],
-    });
+    results.set(QueryResults::error("Agent not initialized"));
     return;
   };
-  match agent.text_to_sql(text_query, conversation).await {
-    Ok(sql) => editable_sql.editor_mut().write().set(&sql),
+  let started = Instant::now();
+  let mut working = conversation.peek().clone();
+  match agent.text_to_sql(text_query, &mut working, &mut conversation).await {
+    Ok(sql) => {
+      record_history(history, HistoryKind::Generated, &sql, Some(text_query), started, Ok(None))
+        .await;
+      editable_sql.editor_mut().write().set(&sql)
+    }
     Err(e) => {
       error!("Error while trying to fetch SQL from LLM");
-      results.set(TableData { headers: vec!["Error".into()], rows: vec![vec![format!("{e}")]] });
+      record_history(
+        history,
+        HistoryKind::Generated,
+        "",
+        Some(text_query),
+        started,
+        Err(format!("{e}")),
+      )
+      .await;
+      results.set(QueryResults::error(format!("{e}")));
     }
   }
 }
 
+/// The question of the current query and the active result tab, when that tab shows the result of
+/// the current query rather than of some other statement.
+pub fn summarizable(
+  conversation: &Conversation,
+  results: &QueryResults,
+) -> Option<(String, ResultTab)> {
+  let current = conversation.current_query.as_ref()?;
+  let tab = results.tabs.get(results.active).filter(|tab| !tab.is_error)?;
+  let same_query = normalize_statement(tab.sql.as_deref()?) == normalize_statement(&current.sql);
+  same_query.then(|| (current.question.clone(), tab.clone()))
+}
+
 pub fn init_handlers(state: &AppState) -> AppHandlers {
-  let editable_sql = state.editable_sql;
+  let tabs = state.tabs;
   let editable_nl = state.editable_nl;
-  let results = state.results;
   let conversation = state.conversation;
+  let history = state.history;
+  let tx_status = state.tx_status;
+  let stop_on_error = state.stop_on_error;
+  let mut llm_busy = state.llm_busy;
 
-  let trigger_sql_query = Callback::new(move |_: ()| {
-    let sql_query = editable_sql.editor().read().to_string();
+  let run_sql = move |mode: RunMode| {
+    let tab = tabs.read().active_tab().clone();
+    let statements = statements_to_run(&tab.editable, mode);
+    if statements.is_empty() {
+      return;
+    }
     spawn({
-      let mut results = results;
+      let mut results = tab.results;
+      let mut history = history;
+      let mut conversation = conversation;
       async move {
-        let table = execute_sql_query(&sql_query).await;
-        results.set(table);
+        run_statements(statements, &mut results, &mut history, &mut conversation, stop_on_error)
+          .await;
+        refresh_tx_status(tx_status).await;
       }
     });
-  });
+  };
+  let trigger_sql_query = Callback::new(move |_: ()| run_sql(RunMode::All));
+  let run_statement = Callback::new(move |_: ()| run_sql(RunMode::Statement));
+  let run_selection = Callback::new(move |_: ()| run_sql(RunMode::Selection));
+
+  let explain_sql = move |analyze: bool| {
+    let tab = tabs.read().active_tab().clone();
+    let Some(statement) = statements_to_run(&tab.editable, RunMode::Selection).into_iter().next()
+    else {
+      return;
+    };
+    spawn({
+      let mut results = tab.results;
+      async move {
+        let agent = AGENT.get().unwrap();
+        match agent.db_client.explain(&statement.text, analyze).await {
+          Ok(plan) => results.set(QueryResults::plan(statement.text, plan)),
+          Err(e) => results.set(QueryResults::error(format!("{e}"))),
+        }
+        refresh_tx_status(tx_status).await;
+      }
+    });
+  };
+  let explain = Callback::new(move |_: ()| explain_sql(false));
+  let explain_analyze = Callback::new(move |_: ()| explain_sql(true));
 
   let trigger_llm_query = Callback::new(move |_: ()| {
+    if *llm_busy.peek() {
+      return;
+    }
+    llm_busy.set(true);
     let text_query = editable_nl.editor().read().to_string();
     let conversation = conversation;
+    let tab = tabs.read().active_tab().clone();
     spawn({
-      let mut editable_sql = editable_sql;
-      let mut results = results;
+      let mut editable_sql = tab.editable;
+      let mut results = tab.results;
+      let mut history = history;
       async move {
-        llm_to_sql_and_update(&mut editable_sql, &text_query, &mut results, conversation).await;
+        llm_to_sql_and_update(
+          &mut editable_sql,
+          &text_query,
+          &mut results,
+          conversation,
+          &mut history,
+        )
+        .await;
+        llm_busy.set(false);
       }
     });
   });
 
-  AppHandlers { trigger_sql_query, trigger_llm_query }
+  let summarize_result = Callback::new(move |_: ()| {
+    if *llm_busy.peek() {
+      return;
+    }
+    let results = tabs.read().active_results();
+    let Some((question, tab)) = summarizable(&conversation.read(), &results.read()) else {
+      return;
+    };
+    let sql = tab.sql.clone().unwrap_or_default();
+    let Some(agent) = AGENT.get() else {
+      error!("Agent not initialized");
+      return;
+    };
+    let mut conversation = conversation;
+    llm_busy.set(true);
+    spawn(async move {
+      let answer =
+        match agent.summarize_result(&question, &sql, &tab.table.headers, &tab.table.rows).await {
+          Ok(answer) => answer,
+          Err(e) => {
+            error!("Unable to summarize result: {e:?}");
+            format!("Unable to summarize the result: {e}")
+          }
+        };
+      conversation.write().add_answer(&format!("{}\n{answer}", tab.title));
+      llm_busy.set(false);
+    });
+  });
+
+  AppHandlers {
+    trigger_sql_query,
+    run_statement,
+    run_selection,
+    explain,
+    explain_analyze,
+    trigger_llm_query,
+    summarize_result,
+  }
 }
@@ -0,0 +1,86 @@
+use crate::history::{HistoryKind, HistoryStore};
+use crate::ui::overlay_modal::overlay;
+use freya::prelude::*;
+
+/// Searchable list of past executed/generated queries. Clicking an entry restores its SQL
+/// into the editor, or only the question of a failed generation into the question box.
+pub fn history_panel(
+  mut show_panel: Signal<bool>,
+  history: Signal<HistoryStore>,
+  mut editable_sql: UseEditable,
+  mut editable_nl: UseEditable,
+) -> Element {
+  let mut search = use_signal(String::new);
+
+  rsx! {
+    if show_panel() {
+      {overlay(move || show_panel.set(false), rsx! {
+        rect {
+          width: "700",
+          height: "500",
+          padding: "12",
+          background: "white",
+          corner_radius: "8",
+          direction: "vertical",
+          spacing: "8",
+          label { font_weight: "bold", "History" }
+          Input {
+            value: search.read().clone(),
+            placeholder: "Search SQL, questions or profiles",
+            onchange: move |v: String| search.set(v),
+          }
+          ScrollView {
+            for (i, entry) in history.read().search(&search.read()).into_iter().take(200).enumerate() {
+              rect {
+                key: "{i}",
+                width: "100%",
+                padding: "6",
+                margin: "2 0",
+                corner_radius: "6",
+                background: if entry.error.is_some() { "rgb(255,240,240)" } else { "rgb(245,245,245)" },
+                onclick: {
+                  let sql = entry.sql.clone();
+                  let question = entry.question.clone();
+                  move |_| {
+                    if !sql.is_empty() {
+                      editable_sql.editor_mut().write().set(&sql);
+                    } else if let Some(question) = &question {
+                      editable_nl.editor_mut().write().set(question);
+                    }
+                    show_panel.set(false);
+                  }
+                },
+                label {
+                  font_size: "10",
+                  color: "rgb(100,100,100)",
+                  {
+                    format!(
+                      "{} · {} · {} · {} ms{}",
+                      entry.timestamp.format("%Y-%m-%d %H:%M:%S"),
+                      match entry.kind {
+                        HistoryKind::Executed => "executed",
+                        HistoryKind::Generated => "generated",
+                      },
+                      entry.profile.as_deref().unwrap_or("-"),
+                      entry.duration_ms,
+                      entry.row_count.map(|n| format!(" · {n} rows")).unwrap_or_default(),
+                    )
+                  }
+                }
+                if let Some(question) = &entry.question {
+                  label { font_size: "11", font_weight: "bold", "{question}" }
+                }
+                if !entry.sql.is_empty() {
+                  label { font_size: "11", max_lines: "3", text_overflow: "ellipsis", "{entry.sql}" }
+                }
+                if let Some(err) = &entry.error {
+                  label { font_size: "10", color: "rgb(200,0,0)", "{err}" }
+                }
+              }
+            }
+          }
+        }
+      })}
+    }
+  }
+}
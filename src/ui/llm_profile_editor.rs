@@ -0,0 +1,236 @@
+use crate::agent::AGENT;
+use crate::config::{ConfigDocument, LlmConfig};
+use crate::llm::list_models;
+use crate::secrets::resolve_in_background;
+use crate::ui::app_state::LlmStatus;
+use crate::ui::overlay_modal::overlay;
+use crate::ui::profile_form::{
+  ProfileFields, field, profile_list, status_line, use_profile_editor,
+};
+use anyhow::anyhow;
+use freya::prelude::*;
+use std::collections::BTreeMap;
+
+/// Smallest context window the editor accepts; the prompt alone takes several hundred tokens
+const MIN_CONTEXT_TOKENS: usize = 1024;
+
+/// Text fields of the LLM profile being edited. Settings the editor does not show are carried
+/// over from the saved profile.
+#[derive(Debug, Clone, PartialEq)]
+struct LlmForm {
+  name: String,
+  api_url: String,
+  api_key: String,
+  model: String,
+  temperature: String,
+  context_tokens: String,
+  base: LlmConfig,
+}
+
+impl Default for LlmForm {
+  fn default() -> Self {
+    Self::new("", &LlmConfig::new("http://localhost:11434/v1", ""))
+  }
+}
+
+impl ProfileFields for LlmForm {
+  type Config = LlmConfig;
+
+  fn new(name: &str, config: &LlmConfig) -> Self {
+    Self {
+      name: name.into(),
+      api_url: config.api_url.clone(),
+      api_key: config.api_key.clone().unwrap_or_default(),
+      model: config.model.clone(),
+      temperature: config.temperature.to_string(),
+      context_tokens: config.context_tokens.to_string(),
+      base: config.clone(),
+    }
+  }
+
+  fn name(&self) -> &str {
+    &self.name
+  }
+
+  fn name_mut(&mut self) -> &mut String {
+    &mut self.name
+  }
+
+  fn to_config(&self) -> anyhow::Result<(String, LlmConfig)> {
+    let name = self.name.trim();
+    if name.is_empty() {
+      return Err(anyhow!("A profile name is required"));
+    }
+    let temperature =
+      self.temperature.trim().parse().ok().filter(|t: &f64| (0.0..=2.0).contains(t)).ok_or_else(
+        || anyhow!("Temperature must be between 0 and 2, not '{}'", self.temperature),
+      )?;
+    let context_tokens = self
+      .context_tokens
+      .trim()
+      .parse()
+      .ok()
+      .filter(|&tokens| tokens >= MIN_CONTEXT_TOKENS)
+      .ok_or_else(|| {
+        anyhow!("Context size must be at least {MIN_CONTEXT_TOKENS}, not '{}'", self.context_tokens)
+      })?;
+    let api_key = Some(self.api_key.trim().to_string()).filter(|key| !key.is_empty());
+    let config = LlmConfig {
+      api_url: self.api_url.trim().into(),
+      api_key,
+      model: self.model.trim().into(),
+      temperature,
+      context_tokens,
+      ..self.base.clone()
+    };
+    Ok((name.into(), config))
+  }
+
+  fn profiles(doc: &ConfigDocument) -> BTreeMap<String, LlmConfig> {
+    doc.llm_profiles()
+  }
+
+  fn active(doc: &ConfigDocument) -> String {
+    doc.active_llm()
+  }
+
+  fn set_profile(doc: &mut ConfigDocument, name: &str, config: &LlmConfig) {
+    doc.set_llm_profile(name, config);
+  }
+
+  fn remove_profile(doc: &mut ConfigDocument, name: &str) -> anyhow::Result<()> {
+    doc.remove_llm_profile(name)
+  }
+}
+
+/// Dialog to edit the LLM profiles of config.toml, list the models of a server and make a
+/// profile the one the agent uses.
+pub fn llm_profile_editor(
+  mut show_editor: Signal<bool>,
+  mut llm_status: Resource<LlmStatus>,
+) -> Element {
+  let editor = use_profile_editor::<LlmForm>(show_editor);
+  let mut form = editor.form;
+  let mut status = editor.status;
+  let mut models = use_signal(Vec::<String>::new);
+
+  use_effect(move || {
+    if show_editor() {
+      models.set(vec![]);
+    }
+  });
+
+  let test = move |_| {
+    let Some((_, config)) = editor.checked_config() else {
+      return;
+    };
+    status.set("Listing models...".into());
+    spawn(async move {
+      let config = match resolve_in_background(move || config.resolve_secrets()).await {
+        Ok(config) => config,
+        Err(e) => return status.set(format!("{e:#}")),
+      };
+      match list_models(&reqwest::Client::new(), &config).await {
+        Ok(found) => {
+          let known = found.contains(&config.model);
+          status.set(match (found.len(), known) {
+            (0, _) => "Connected, but the server lists no models".into(),
+            (n, true) => format!("Connected, {n} models"),
+            (n, false) => format!("Connected, {n} models; '{}' is not one of them", config.model),
+          });
+          models.set(found);
+        }
+        Err(e) => {
+          models.set(vec![]);
+          status.set(format!("Connection failed: {e}"));
+        }
+      }
+    });
+  };
+
+  let activate = move |_| {
+    let Some((name, config)) = editor.checked_config() else {
+      return;
+    };
+    let saved = editor.edit_document(|doc| {
+      doc.set_llm_profile(&name, &config);
+      doc.set_active_llm(&name);
+      Ok(())
+    });
+    if let Err(e) = saved {
+      return status.set(format!("{e}"));
+    }
+    spawn(async move {
+      let config = match resolve_in_background(move || config.resolve_secrets()).await {
+        Ok(config) => config,
+        Err(e) => return status.set(format!("Saved, but the API key is unavailable: {e:#}")),
+      };
+      if let Some(agent) = AGENT.get() {
+        *agent.llm_client.write().await = Some(config);
+      }
+      llm_status.restart();
+      status.set(format!("Using '{name}'"));
+    });
+  };
+
+  let current = form.read().clone();
+
+  rsx! {
+    if show_editor() {
+      {overlay(move || show_editor.set(false), rsx! {
+        rect {
+          width: "700",
+          height: "430",
+          padding: "12",
+          background: "white",
+          corner_radius: "8",
+          direction: "horizontal",
+          spacing: "12",
+          { profile_list(editor, "LLM profiles", move || models.set(vec![])) }
+          rect {
+            width: "fill",
+            height: "100%",
+            spacing: "8",
+            { field(form, "Name", current.name.clone(), false, |f, v| f.name = v) }
+            { field(form, "API URL", current.api_url.clone(), false, |f, v| f.api_url = v) }
+            { field(form, "API key", current.api_key.clone(), true, |f, v| f.api_key = v) }
+            { field(form, "Model", current.model.clone(), false, |f, v| f.model = v) }
+            if !models.read().is_empty() {
+              rect {
+                direction: "horizontal",
+                cross_align: "center",
+                spacing: "8",
+                label { width: "90", font_size: "12", "Server models" }
+                Dropdown {
+                  value: current.model.clone(),
+                  for model in models.read().iter().cloned() {
+                    DropdownItem {
+                      key: "{model}",
+                      value: model.clone(),
+                      onpress: move |_| form.write().model = model.clone(),
+                      label { "{model}" }
+                    }
+                  }
+                }
+              }
+            }
+            { field(form, "Temperature", current.temperature.clone(), false, |f, v| f.temperature = v) }
+            { field(form, "Context size", current.context_tokens.clone(), false, |f, v| f.context_tokens = v) }
+            { status_line(status) }
+            rect {
+              width: "100%",
+              direction: "horizontal",
+              main_align: "end",
+              spacing: "8",
+              Button { onclick: move |_| editor.delete(), label { "Delete" } }
+              Button { onclick: test, label { "Test connection" } }
+              Button { onclick: move |_| editor.save(), label { "Save" } }
+              Button { onclick: activate, label { "Use" } }
+              Button { onclick: move |_| show_editor.set(false), label { "Close" } }
+            }
+          }
+        }
+      })}
+    }
+  }
+}
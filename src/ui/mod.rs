@@ -1,8 +1,20 @@
 pub mod actions;
 pub mod app;
 pub mod app_state;
+pub mod completion_popup;
+pub mod connection_manager;
 pub mod connections;
 pub mod editors;
+pub mod glossary_modal;
 pub mod handlers;
+pub mod history_panel;
+pub mod llm_profile_editor;
 pub mod overlay_modal;
+pub mod plan_view;
+pub mod profile_form;
 pub mod results;
+pub mod session_switcher;
+pub mod snippets_sidebar;
+pub mod tabs;
+pub mod trace_panel;
+pub mod transaction;
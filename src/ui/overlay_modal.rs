@@ -1,52 +1,75 @@
 use freya::prelude::*;
 
+/// Dims the window and shows `content` centered above it. Clicking the dimmed area calls
+/// `on_dismiss`.
+pub fn overlay(on_dismiss: impl FnMut() + 'static, content: Element) -> Element {
+  layers(on_dismiss, "0.5", "center", content)
+}
+
+/// Like `overlay`, with `content` docked to the left edge and a lighter dim.
+pub fn side_overlay(on_dismiss: impl FnMut() + 'static, content: Element) -> Element {
+  layers(on_dismiss, "0.3", "start", content)
+}
+
+fn layers(
+  mut on_dismiss: impl FnMut() + 'static,
+  opacity: &str,
+  align: &str,
+  content: Element,
+) -> Element {
+  rsx! {
+    rect {
+      width: "100%",
+      height: "100%",
+      position: "absolute",
+      layer: "-100",
+      rect {
+        background: "rgb(0,0,0)",
+        opacity: "{opacity}",
+        width: "100%",
+        height: "100%",
+        position: "absolute",
+        layer: "-101",
+        onclick: move |_| on_dismiss(),
+      }
+      rect {
+        width: "100%",
+        height: "100%",
+        position: "absolute",
+        layer: "-150",
+        main_align: "{align}",
+        cross_align: "{align}",
+        {content}
+      }
+    }
+  }
+}
+
 pub fn modal(mut show_modal: Signal<bool>, tables: Signal<Vec<String>>) -> Element {
   rsx! {
       if show_modal() {
-        rect {
-          width: "100%",
-          height: "100%",
-          position: "absolute",
-          layer: "-100",
+        {overlay(move || show_modal.set(false), rsx! {
           rect {
-            background: "rgb(0,0,0)",
-            opacity: "0.5",
-            width: "100%",
-            height: "100%",
-            position: "absolute",
-            layer: "-101",
-            onclick: move |_| show_modal.set(false),
-          }
-          rect {
-            width: "100%",
-            height: "100%",
-            position: "absolute",
-            layer: "-150",
-            main_align: "center",
-            cross_align: "center",
-
+            width: "600",
+            height: "400",
             rect {
-              width: "600",
-              height: "400",
-              rect {
-                width: "100%",
-                padding: "12",
-                opacity: "1",
-                background: "white",
-                corner_radius: "8",
-                direction: "vertical",
-                spacing: "8",
-                label { "Tables: {tables.read().len()}" }
-                ScrollView {
-                  for table in tables.read().iter() {
-                    // For future: make this expandable accordion
-                    label { "{table}" }
-                  }
+              width: "100%",
+              padding: "12",
+              opacity: "1",
+              background: "white",
+              corner_radius: "8",
+              direction: "vertical",
+              spacing: "8",
+              label { "Tables: {tables.read().len()}" }
+              ScrollView {
+                for table in tables.read().iter() {
+                  // For future: make this expandable accordion
+                  label { "{table}" }
                 }
               }
             }
           }
-        }
+        })}
       }
   }
 }
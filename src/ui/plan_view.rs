@@ -0,0 +1,230 @@
+use crate::advisor::PlanAdvice;
+use crate::agent::AGENT;
+use crate::explain::{PlanNode, PlanRow};
+use crate::ui::results::QueryResults;
+use freya::prelude::*;
+use std::collections::HashSet;
+use tracing::error;
+
+/// Background for a node by its share of the total time (or cost).
+fn share_background(share: f64) -> &'static str {
+  if share >= 0.5 {
+    "rgb(255,205,205)"
+  } else if share >= 0.2 {
+    "rgb(255,235,205)"
+  } else {
+    "transparent"
+  }
+}
+
+fn node_details(node: &PlanNode) -> Vec<String> {
+  let mut details = vec![format!("cost {:.2}..{:.2}", node.startup_cost, node.total_cost)];
+  if let (Some(startup), Some(total)) = (node.actual_startup_time, node.actual_total_time) {
+    let loops = node.actual_loops.unwrap_or(1.0);
+    let loops = if loops > 1.0 { format!(" × {loops} loops") } else { String::new() };
+    details.push(format!("time {startup:.3}..{total:.3} ms{loops}"));
+  }
+  match (node.actual_rows, node.rows_estimate_error()) {
+    (Some(actual), Some(error)) if error.abs() >= 2.0 => {
+      let direction = if error > 0.0 { "under" } else { "over" };
+      details.push(format!(
+        "rows {} est / {actual} actual ({:.0}× {direction})",
+        node.plan_rows,
+        error.abs()
+      ));
+    }
+    (Some(actual), _) => details.push(format!("rows {} est / {actual} actual", node.plan_rows)),
+    (None, _) => details.push(format!("rows {} est", node.plan_rows)),
+  }
+  if node.shared_hit_blocks.is_some() || node.shared_read_blocks.is_some() {
+    details.push(format!(
+      "buffers hit {} read {}",
+      node.shared_hit_blocks.unwrap_or(0),
+      node.shared_read_blocks.unwrap_or(0)
+    ));
+  }
+  details
+}
+
+/// Nodes that are not inside a collapsed subtree.
+fn visible_rows<'a>(rows: Vec<PlanRow<'a>>, collapsed: &HashSet<usize>) -> Vec<PlanRow<'a>> {
+  let mut visible = Vec::new();
+  let mut hidden_below: Option<usize> = None;
+  for row in rows {
+    if let Some(depth) = hidden_below {
+      if row.depth > depth {
+        continue;
+      }
+      hidden_below = None;
+    }
+    if collapsed.contains(&row.id) {
+      hidden_below = Some(row.depth);
+    }
+    visible.push(row);
+  }
+  visible
+}
+
+/// Collapsible plan tree of the last Explain; the most expensive nodes are highlighted.
+pub fn plan_view(mut results: Signal<QueryResults>) -> Element {
+  let current = results.read();
+  let Some(view) = current.plan.as_ref() else {
+    return rsx!();
+  };
+  let plan = &view.plan;
+  let summary = match (plan.planning_time, plan.execution_time) {
+    (Some(planning), Some(execution)) => {
+      format!("Planning {planning:.3} ms, execution {execution:.3} ms")
+    }
+    _ => "Estimated plan (run Explain Analyze for actual times)".to_string(),
+  };
+  let rows = visible_rows(plan.rows(), &view.collapsed);
+
+  let ask_ai = move |_| {
+    let Some((sql, plan)) = results.read().plan.as_ref().map(|v| (v.sql.clone(), v.plan.clone()))
+    else {
+      return;
+    };
+    if let Some(view) = results.write().plan.as_mut() {
+      view.advising = true;
+    }
+    spawn(async move {
+      let advice = match AGENT.get() {
+        Some(agent) => agent.explain_plan(&sql, &plan).await.map_err(|e| {
+          error!("Plan advice failed: {e:?}");
+          format!("{e}")
+        }),
+        None => Err("Agent not initialized".to_string()),
+      };
+      if let Some(view) = results.write().plan.as_mut() {
+        view.advising = false;
+        view.advice = Some(advice);
+      }
+    });
+  };
+
+  rsx!(
+    rect {
+      width: "100%",
+      height: "100%",
+      spacing: "4",
+      rect {
+        direction: "horizontal",
+        spacing: "10",
+        cross_align: "center",
+        label { font_size: "12", font_weight: "bold", "{summary}" }
+        Button {
+          onclick: ask_ai,
+          label { if view.advising { "Asking AI…" } else { "Explain with AI" } }
+        }
+        Button { onclick: move |_| results.write().plan = None, label { "Close plan" } }
+      }
+      match &view.advice {
+        Some(Ok(advice)) => advice_view(advice),
+        Some(Err(e)) => rsx!(label { font_size: "12", color: "rgb(200,0,0)", "{e}" }),
+        None => rsx!(),
+      }
+      ScrollView {
+        for row in rows.iter() {
+          rect {
+            key: "{row.id}",
+            width: "100%",
+            direction: "horizontal",
+            spacing: "6",
+            padding: format!("2 4 2 {}", row.depth * 18 + 4),
+            corner_radius: "4",
+            background: share_background(row.share),
+            label {
+              width: "14",
+              font_size: "12",
+              onclick: {
+                let id = row.id;
+                move |_| {
+                  if let Some(view) = results.write().plan.as_mut() {
+                    if !view.collapsed.remove(&id) {
+                      view.collapsed.insert(id);
+                    }
+                  }
+                }
+              },
+              if row.node.children.is_empty() {
+                " "
+              } else if view.collapsed.contains(&row.id) {
+                "▸"
+              } else {
+                "▾"
+              }
+            }
+            label { font_size: "12", font_weight: "bold", "{row.node.title()}" }
+            label {
+              font_size: "11",
+              color: "rgb(100,100,100)",
+              { node_details(row.node).join(" · ") }
+            }
+            label {
+              font_size: "11",
+              color: "rgb(160,0,0)",
+              { format!("{:.0}%", row.share * 100.0) }
+            }
+          }
+        }
+      }
+    }
+  )
+}
+
+fn advice_view(advice: &PlanAdvice) -> Element {
+  rsx!(
+    rect {
+      width: "100%",
+      padding: "6",
+      spacing: "4",
+      corner_radius: "6",
+      background: "rgb(240,245,255)",
+      label { font_size: "12", "{advice.explanation}" }
+      for (i, verified) in advice.suggestions.iter().enumerate() {
+        rect {
+          key: "{i}",
+          spacing: "2",
+          label {
+            font_size: "12",
+            font_weight: "bold",
+            { format!(
+              "{:?}: cost {:.2} -> {:.2} ({:.1}× cheaper)",
+              verified.suggestion.kind,
+              verified.cost_before,
+              verified.cost_after,
+              verified.speedup()
+            ) }
+          }
+          label { font_size: "12", font_family: "monospace", "{verified.suggestion.sql}" }
+          label { font_size: "11", color: "rgb(100,100,100)", "{verified.suggestion.rationale}" }
+        }
+      }
+      for (i, suggestion) in advice.unverified.iter().enumerate() {
+        rect {
+          key: "unverified-{i}",
+          spacing: "2",
+          label {
+            font_size: "12",
+            font_weight: "bold",
+            color: "rgb(180,120,0)",
+            "Index (unverified, install hypopg to re-plan with it)"
+          }
+          label { font_size: "12", font_family: "monospace", "{suggestion.sql}" }
+          label { font_size: "11", color: "rgb(100,100,100)", "{suggestion.rationale}" }
+        }
+      }
+      if advice.suggestions.is_empty() && advice.unverified.is_empty() {
+        label { font_size: "12", "No suggestion lowered the estimated cost." }
+      }
+      if !advice.rejected.is_empty() {
+        label {
+          font_size: "11",
+          color: "rgb(120,120,120)",
+          { format!("{} suggestion(s) discarded after re-planning", advice.rejected.len()) }
+        }
+      }
+    }
+  )
+}
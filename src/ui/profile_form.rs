@@ -0,0 +1,211 @@
+use crate::config::{ConfigDocument, Setting};
+use anyhow::anyhow;
+use freya::prelude::*;
+use std::collections::BTreeMap;
+use tracing::error;
+
+/// Text fields of a profile of config.toml, and how that kind of profile is stored.
+pub trait ProfileFields: Clone + Default + PartialEq + 'static {
+  type Config: Clone + 'static;
+
+  fn new(name: &str, config: &Self::Config) -> Self;
+  fn name(&self) -> &str;
+  fn name_mut(&mut self) -> &mut String;
+  /// The profile name and config, or why the fields don't make one
+  fn to_config(&self) -> anyhow::Result<(String, Self::Config)>;
+
+  fn profiles(doc: &ConfigDocument) -> BTreeMap<String, Self::Config>;
+  fn active(doc: &ConfigDocument) -> String;
+  fn set_profile(doc: &mut ConfigDocument, name: &str, config: &Self::Config);
+  fn remove_profile(doc: &mut ConfigDocument, name: &str) -> anyhow::Result<()>;
+}
+
+/// config.toml, the profile being edited and the status line of a profile dialog.
+pub struct ProfileEditor<F: ProfileFields> {
+  pub document: Signal<Option<ConfigDocument>>,
+  pub form: Signal<F>,
+  pub status: Signal<String>,
+}
+
+impl<F: ProfileFields> Clone for ProfileEditor<F> {
+  fn clone(&self) -> Self {
+    *self
+  }
+}
+
+impl<F: ProfileFields> Copy for ProfileEditor<F> {}
+
+/// Reloads config.toml, with the active profile in the form, whenever `show` turns on.
+pub fn use_profile_editor<F: ProfileFields>(show: Signal<bool>) -> ProfileEditor<F> {
+  let editor = ProfileEditor {
+    document: use_signal(|| None),
+    form: use_signal(F::default),
+    status: use_signal(String::new),
+  };
+  use_effect(move || {
+    if show() {
+      editor.load();
+    }
+  });
+  editor
+}
+
+impl<F: ProfileFields> ProfileEditor<F> {
+  fn load(mut self) {
+    match ConfigDocument::load(&Setting::config_path()) {
+      Ok(doc) => {
+        let active = F::active(&doc);
+        if let Some(config) = F::profiles(&doc).get(&active) {
+          self.form.set(F::new(&active, config));
+        }
+        // profiles that don't parse are not listed; say why
+        self.status.set(doc.profile_errors().join("\n"));
+        self.document.set(Some(doc));
+      }
+      Err(e) => {
+        error!("Unable to open config.toml: {e:?}");
+        self.status.set(format!("{e}"));
+      }
+    }
+  }
+
+  /// Applies `change` to config.toml and saves it.
+  pub fn edit_document(
+    mut self,
+    change: impl FnOnce(&mut ConfigDocument) -> anyhow::Result<()>,
+  ) -> anyhow::Result<()> {
+    let mut doc =
+      self.document.peek().clone().ok_or_else(|| anyhow!("config.toml is not loaded"))?;
+    change(&mut doc)?;
+    doc.save()?;
+    self.document.set(Some(doc));
+    Ok(())
+  }
+
+  /// The profile in the form; when the fields don't make one, the status line says why.
+  pub fn checked_config(mut self) -> Option<(String, F::Config)> {
+    match self.form.peek().to_config() {
+      Ok(profile) => Some(profile),
+      Err(e) => {
+        self.status.set(format!("{e}"));
+        None
+      }
+    }
+  }
+
+  pub fn save(mut self) {
+    let Some((name, config)) = self.checked_config() else {
+      return;
+    };
+    match self.edit_document(|doc| {
+      F::set_profile(doc, &name, &config);
+      Ok(())
+    }) {
+      Ok(_) => self.status.set(format!("Saved '{name}'")),
+      Err(e) => self.status.set(format!("{e}")),
+    }
+  }
+
+  pub fn delete(mut self) {
+    let name = self.form.peek().name().trim().to_string();
+    match self.edit_document(|doc| F::remove_profile(doc, &name)) {
+      Ok(_) => {
+        self.form.set(F::default());
+        self.status.set(format!("Deleted '{name}'"));
+      }
+      Err(e) => self.status.set(format!("{e}")),
+    }
+  }
+}
+
+/// The saved profiles, the active one in bold, with New and Duplicate below. `on_open` runs
+/// before a listed profile is opened in the form.
+pub fn profile_list<F: ProfileFields>(
+  editor: ProfileEditor<F>,
+  title: &str,
+  on_open: impl FnMut() + Clone + 'static,
+) -> Element {
+  let mut form = editor.form;
+  let (profiles, active) = match &*editor.document.read() {
+    Some(doc) => (F::profiles(doc), F::active(doc)),
+    None => Default::default(),
+  };
+  let current_name = form.read().name().to_string();
+
+  rsx! {
+    rect {
+      width: "180",
+      height: "100%",
+      spacing: "6",
+      label { font_weight: "bold", "{title}" }
+      ScrollView {
+        height: "fill",
+        for (name, config) in profiles {
+          rect {
+            key: "{name}",
+            width: "100%",
+            padding: "6",
+            margin: "2 0",
+            corner_radius: "6",
+            background: if name == current_name { "rgb(225,235,255)" } else { "rgb(245,245,245)" },
+            onclick: {
+              let name = name.clone();
+              let mut on_open = on_open.clone();
+              move |_| {
+                on_open();
+                form.set(F::new(&name, &config));
+              }
+            },
+            label {
+              font_size: "12",
+              font_weight: if name == active { "bold" } else { "normal" },
+              if name == active { "● {name}" } else { "{name}" }
+            }
+          }
+        }
+      }
+      rect {
+        direction: "horizontal",
+        spacing: "5",
+        Button { onclick: move |_| form.set(F::default()), label { "New" } }
+        Button {
+          onclick: move |_| form.write().name_mut().push_str("_copy"),
+          label { "Duplicate" }
+        }
+      }
+    }
+  }
+}
+
+/// A labelled input for one field of the form; `secret` fields are masked.
+pub fn field<F: ProfileFields>(
+  mut form: Signal<F>,
+  label: &'static str,
+  value: String,
+  secret: bool,
+  set: fn(&mut F, String),
+) -> Element {
+  let mode = if secret { InputMode::new_password() } else { InputMode::Shown };
+  rsx! {
+    rect {
+      direction: "horizontal",
+      cross_align: "center",
+      spacing: "8",
+      label { width: "90", font_size: "12", "{label}" }
+      Input {
+        value,
+        mode,
+        onchange: move |v: String| set(&mut form.write(), v),
+      }
+    }
+  }
+}
+
+/// The outcome of the last action, if there is one.
+pub fn status_line(status: Signal<String>) -> Element {
+  rsx! {
+    if !status.read().is_empty() {
+      label { font_size: "11", max_lines: "3", "{status}" }
+    }
+  }
+}
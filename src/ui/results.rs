@@ -1,37 +1,209 @@
+use crate::advisor::PlanAdvice;
+use crate::db_client::ResultSet;
+use crate::explain::Plan;
+use crate::ui::plan_view::plan_view;
 use freya::prelude::*;
+use std::collections::HashSet;
+#[derive(Debug, Clone, Default)]
 pub struct TableData {
   pub headers: Vec<String>,
   pub rows: Vec<Vec<String>>,
 }
 
-pub fn results_table(results: &Signal<TableData>) -> Element {
+impl TableData {
+  pub fn error(message: impl Into<String>) -> Self {
+    TableData { headers: vec!["Error".into()], rows: vec![vec![message.into()]] }
+  }
+}
+
+impl From<&ResultSet> for TableData {
+  fn from(set: &ResultSet) -> Self {
+    if !set.returns_rows() {
+      let status = set.status().unwrap_or_default();
+      return TableData { headers: vec!["Result".into()], rows: vec![vec![status]] };
+    }
+    TableData {
+      headers: set.columns.clone(),
+      rows: set
+        .rows
+        .iter()
+        .map(|row| row.iter().map(|v| v.clone().unwrap_or_else(|| "NULL".into())).collect())
+        .collect(),
+    }
+  }
+}
+
+/// Result of one executed statement.
+#[derive(Debug, Clone)]
+pub struct ResultTab {
+  pub title: String,
+  pub table: TableData,
+  pub is_error: bool,
+  /// Outcome shown above the grid, e.g. `3 rows affected`
+  pub status: Option<String>,
+  /// Statement that produced the result
+  pub sql: Option<String>,
+}
+
+/// Results of the last execution in an editor tab, one result tab per result set.
+#[derive(Debug, Clone, Default)]
+pub struct QueryResults {
+  pub tabs: Vec<ResultTab>,
+  pub active: usize,
+  /// Server notices and warnings, shown next to the grid
+  pub messages: Vec<String>,
+  /// Set by Explain; shown instead of the result tabs
+  pub plan: Option<PlanView>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PlanView {
+  /// The explained statement
+  pub sql: String,
+  pub plan: Plan,
+  /// Pre-order ids of collapsed nodes
+  pub collapsed: HashSet<usize>,
+  /// AI explanation and verified suggestions, once requested
+  pub advice: Option<Result<PlanAdvice, String>>,
+  pub advising: bool,
+}
+
+impl QueryResults {
+  pub fn plan(sql: impl Into<String>, plan: Plan) -> Self {
+    Self {
+      plan: Some(PlanView {
+        sql: sql.into(),
+        plan,
+        collapsed: HashSet::new(),
+        advice: None,
+        advising: false,
+      }),
+      ..Default::default()
+    }
+  }
+
+  pub fn single(title: impl Into<String>, table: TableData) -> Self {
+    Self {
+      tabs: vec![ResultTab {
+        title: title.into(),
+        table,
+        is_error: false,
+        status: None,
+        sql: None,
+      }],
+      ..Default::default()
+    }
+  }
+
+  pub fn error(message: impl Into<String>) -> Self {
+    Self {
+      tabs: vec![ResultTab {
+        title: "Error".into(),
+        table: TableData::error(message),
+        is_error: true,
+        status: None,
+        sql: None,
+      }],
+      ..Default::default()
+    }
+  }
+
+  pub fn active_table(&self) -> Option<&TableData> {
+    self.tabs.get(self.active).map(|t| &t.table)
+  }
+}
+
+pub fn results_table(results: &Signal<QueryResults>) -> Element {
+  let mut results = *results;
+  let empty = TableData::default();
+  let current = results.read();
+  if current.plan.is_some() {
+    return rsx!(
+      rect { width: "100%", height: "50%", padding: "5", { plan_view(results) } }
+    );
+  }
+  let table = current.active_table().unwrap_or(&empty);
+
   rsx!(
     rect {
       width: "100%",
       height: "50%",
       padding: "5",
       spacing: "5",
-      Table {
-        columns: results.read().headers.len().max(1),
-        TableHead {
-          TableRow {
-            for (i, col) in results.read().headers.iter().enumerate() {
-              TableCell {
-                key: "{i}",
-                label { text_align: "left", font_size: "16", font_weight: "bold", "{col}" }
+      if current.tabs.len() > 1 {
+        rect {
+          direction: "horizontal",
+          spacing: "4",
+          for (i, tab) in current.tabs.iter().enumerate() {
+            rect {
+              key: "{i}",
+              padding: "3 6",
+              corner_radius: "6",
+              background: match (i == current.active, tab.is_error) {
+                (true, true) => "rgb(255,215,215)",
+                (true, false) => "rgb(225,225,245)",
+                (false, true) => "rgb(255,240,240)",
+                (false, false) => "rgb(245,245,245)",
+              },
+              onclick: move |_| results.write().active = i,
+              label { font_size: "11", max_lines: "1", "{tab.title}" }
+            }
+          }
+        }
+      }
+      if let Some(status) = current.tabs.get(current.active).and_then(|t| t.status.clone()) {
+        label { font_size: "11", color: "rgb(100,100,100)", "{status}" }
+      }
+      rect {
+        width: "100%",
+        direction: "horizontal",
+        content: "flex",
+        spacing: "8",
+        rect {
+          width: if current.messages.is_empty() { "100%" } else { "flex(3)" },
+          { result_grid(table) }
+        }
+        if !current.messages.is_empty() {
+          rect {
+            width: "flex(1)",
+            padding: "4",
+            corner_radius: "6",
+            background: "rgb(250,248,235)",
+            label { font_size: "12", font_weight: "bold", "Messages" }
+            ScrollView {
+              for (i, message) in current.messages.iter().enumerate() {
+                label { key: "{i}", font_size: "11", "{message}" }
               }
             }
           }
         }
-        TableBody {
-          for (idx, row) in results.read().rows.iter().enumerate() {
-            TableRow {
-              key: "{idx}",
-              for (col_idx, cell) in row.iter().enumerate() {
-                TableCell {
-                  key: "{idx}-{col_idx}",
-                  label { text_align: "left", font_size: "12", "{cell}" }
-                }
+      }
+    }
+  )
+}
+
+fn result_grid(table: &TableData) -> Element {
+  rsx!(
+    Table {
+      columns: table.headers.len().max(1),
+      TableHead {
+        TableRow {
+          for (i, col) in table.headers.iter().enumerate() {
+            TableCell {
+              key: "{i}",
+              label { text_align: "left", font_size: "16", font_weight: "bold", "{col}" }
+            }
+          }
+        }
+      }
+      TableBody {
+        for (idx, row) in table.rows.iter().enumerate() {
+          TableRow {
+            key: "{idx}",
+            for (col_idx, cell) in row.iter().enumerate() {
+              TableCell {
+                key: "{idx}-{col_idx}",
+                label { text_align: "left", font_size: "12", "{cell}" }
               }
             }
           }
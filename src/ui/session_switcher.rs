@@ -0,0 +1,127 @@
+use crate::conversation::Conversation;
+use crate::sessions::{Session, SessionStore};
+use crate::ui::app_state::save_session;
+use freya::prelude::*;
+use tracing::error;
+
+/// Current chat session with rename, new, export and a list to switch to another one.
+pub fn session_switcher(
+  mut session: Signal<Session>,
+  mut conversation: Signal<Conversation>,
+  llm_busy: Signal<bool>,
+) -> Element {
+  let mut show_list = use_signal(|| false);
+  let mut name = use_signal(|| session.peek().name.clone());
+  let mut status = use_signal(String::new);
+
+  let mut open = move |next: Session| {
+    // the running generation would write the old conversation into the new session
+    if *llm_busy.read() {
+      status.set("Wait for the LLM to finish before switching sessions".into());
+      return;
+    }
+    // the conversation is saved a moment after it changes; save it now before leaving it
+    save_session(session, conversation);
+    name.set(next.name.clone());
+    conversation.set(next.conversation.clone());
+    session.set(next);
+    show_list.set(false);
+    status.set(String::new());
+  };
+
+  let rename = move |_| {
+    let new_name = name.read().trim().to_string();
+    if new_name.is_empty() || new_name == session.read().name {
+      return;
+    }
+    // the file is named by id, so renaming only rewrites it
+    let mut renamed = session.read().clone();
+    renamed.name = new_name;
+    renamed.conversation = conversation.read().clone();
+    match SessionStore::default().save(&mut renamed) {
+      Ok(_) => {
+        session.set(renamed);
+        status.set(String::new());
+      }
+      Err(e) => status.set(format!("{e}")),
+    }
+  };
+
+  let export = move |_| {
+    let mut current = session.read().clone();
+    current.conversation = conversation.read().clone();
+    match SessionStore::default().export_markdown(&current) {
+      Ok(path) => status.set(format!("Exported to {}", path.display())),
+      Err(e) => status.set(format!("{e}")),
+    }
+  };
+
+  let sessions = if *show_list.read() {
+    SessionStore::default().list().unwrap_or_else(|e| {
+      error!("Unable to list chat sessions: {e:?}");
+      vec![]
+    })
+  } else {
+    vec![]
+  };
+
+  rsx!(
+    rect {
+      width: "100%",
+      spacing: "3",
+      padding: "0 0 5 0",
+      rect {
+        width: "100%",
+        direction: "horizontal",
+        content: "flex",
+        spacing: "3",
+        cross_align: "center",
+        rect {
+          width: "flex(1)",
+          Input {
+            value: name.read().clone(),
+            placeholder: "Session name",
+            onchange: move |v: String| name.set(v),
+          }
+        }
+        Button { onclick: rename, label { "Rename" } }
+      }
+      rect {
+        width: "100%",
+        direction: "horizontal",
+        spacing: "3",
+        Button {
+          onclick: move |_| {
+            let shown = *show_list.read();
+            show_list.set(!shown);
+          },
+          label { if *show_list.read() { "Sessions ▴" } else { "Sessions ▾" } }
+        }
+        Button { onclick: move |_| open(Session::untitled()), label { "New" } }
+        Button { onclick: export, label { "Export" } }
+      }
+      if !status.read().is_empty() {
+        label { font_size: "10", "{status}" }
+      }
+      for (i, other) in sessions.into_iter().enumerate() {
+        rect {
+          key: "{i}",
+          width: "100%",
+          padding: "3 5",
+          corner_radius: "4",
+          background: if other.id == session.read().id { "rgb(225,225,245)" } else { "white" },
+          onclick: {
+            let other = other.clone();
+            move |_| open(other.clone())
+          },
+          label { font_size: "11", font_weight: "bold", "{other.name}" }
+          label {
+            font_size: "10",
+            color: "rgb(120,120,120)",
+            { format!("{} · {} messages", other.updated.format("%Y-%m-%d %H:%M"), other.conversation.messages.len()) }
+          }
+        }
+      }
+    }
+  )
+}
@@ -0,0 +1,176 @@
+use crate::snippets::{SavedQuery, SnippetLibrary, render};
+use crate::ui::overlay_modal::side_overlay;
+use freya::prelude::*;
+use std::collections::HashMap;
+use tracing::error;
+
+/// Left drawer listing saved queries of the active profile. Queries with `{{variable}}`
+/// placeholders ask for values before they are inserted at the editor cursor. Run inserts the
+/// query selected and executes only the selection.
+pub fn snippets_sidebar(
+  mut show_sidebar: Signal<bool>,
+  mut library: Signal<SnippetLibrary>,
+  mut editable_sql: UseEditable,
+  run_selection: Callback<()>,
+) -> Element {
+  let mut search = use_signal(String::new);
+  let mut selected = use_signal(|| None::<SavedQuery>);
+  let mut values = use_signal(HashMap::<String, String>::new);
+  let mut new_name = use_signal(String::new);
+  let mut new_tags = use_signal(String::new);
+  let mut status = use_signal(String::new);
+
+  let mut apply = move |run: bool| {
+    let Some(query) = selected.read().clone() else {
+      return;
+    };
+    match render(&query.sql, &values.read()) {
+      Ok(sql) => {
+        {
+          let mut editor = editable_sql.editor_mut().write();
+          let at = editor.cursor_pos();
+          let len = editor.insert(&sql, at);
+          editor.set_selection((at, at + len));
+          editor.set_cursor_pos(at + len);
+        }
+        status.set(String::new());
+        selected.set(None);
+        show_sidebar.set(false);
+        if run {
+          run_selection.call(());
+        }
+      }
+      Err(e) => status.set(format!("{e}")),
+    }
+  };
+
+  let save_current = move |_: ()| {
+    let name = new_name.read().trim().to_string();
+    let sql = editable_sql.editor().read().to_string();
+    if name.is_empty() || sql.trim().is_empty() {
+      status.set("A name and SQL in the editor are required".into());
+      return;
+    }
+    let tags = new_tags
+      .read()
+      .split(',')
+      .map(|t| t.trim().trim_start_matches('#').to_string())
+      .filter(|t| !t.is_empty())
+      .collect();
+    let query = SavedQuery { name, tags, description: String::new(), sql };
+    match library.write().upsert(query) {
+      Ok(_) => {
+        new_name.set(String::new());
+        new_tags.set(String::new());
+        status.set(String::new());
+      }
+      Err(e) => {
+        error!("Unable to save query: {e:?}");
+        status.set(format!("{e}"));
+      }
+    }
+  };
+
+  rsx! {
+    if show_sidebar() {
+      {side_overlay(move || show_sidebar.set(false), rsx! {
+        rect {
+          width: "300",
+          height: "100%",
+          padding: "12",
+          spacing: "8",
+          background: "white",
+          direction: "vertical",
+          label { font_weight: "bold", "Saved queries" }
+          Input {
+            value: search.read().clone(),
+            placeholder: "Search by name or #tag",
+            onchange: move |v: String| search.set(v),
+          }
+
+          if let Some(query) = selected.read().clone() {
+            rect {
+              spacing: "5",
+              padding: "6",
+              corner_radius: "6",
+              background: "rgb(245,245,255)",
+              label { font_weight: "bold", "{query.name}" }
+              for name in query.placeholders() {
+                rect {
+                  key: "{name}",
+                  spacing: "2",
+                  label { font_size: "11", "{name}" }
+                  Input {
+                    value: values.read().get(&name).cloned().unwrap_or_default(),
+                    onchange: {
+                      let name = name.clone();
+                      move |v: String| {
+                        values.write().insert(name.clone(), v);
+                      }
+                    },
+                  }
+                }
+              }
+              rect {
+                direction: "horizontal",
+                spacing: "5",
+                Button { onclick: move |_| selected.set(None), label { "Back" } }
+                Button { onclick: move |_| apply(false), label { "Insert" } }
+                Button { onclick: move |_| apply(true), label { "Run" } }
+              }
+            }
+          } else {
+            ScrollView {
+              height: "calc(100% - 190)",
+              for query in library.read().search(&search.read()) {
+                rect {
+                  key: "{query.name}",
+                  width: "100%",
+                  padding: "6",
+                  margin: "2 0",
+                  corner_radius: "6",
+                  background: "rgb(245,245,245)",
+                  onclick: {
+                    let query = query.clone();
+                    move |_| {
+                      values.set(HashMap::new());
+                      selected.set(Some(query.clone()));
+                    }
+                  },
+                  label { font_size: "12", font_weight: "bold", "{query.name}" }
+                  if !query.tags.is_empty() {
+                    label {
+                      font_size: "10",
+                      color: "rgb(90,90,160)",
+                      { query.tags.iter().map(|t| format!("#{t}")).collect::<Vec<_>>().join(" ") }
+                    }
+                  }
+                  label { font_size: "10", max_lines: "2", text_overflow: "ellipsis", "{query.sql}" }
+                }
+              }
+            }
+          }
+
+          rect {
+            spacing: "5",
+            label { font_size: "11", "Save editor SQL as:" }
+            Input {
+              value: new_name.read().clone(),
+              placeholder: "Name",
+              onchange: move |v: String| new_name.set(v),
+            }
+            Input {
+              value: new_tags.read().clone(),
+              placeholder: "tags, comma separated",
+              onchange: move |v: String| new_tags.set(v),
+            }
+            Button { onclick: save_current, label { "Save" } }
+          }
+          if !status.read().is_empty() {
+            label { color: "rgb(200,0,0)", "{status}" }
+          }
+        }
+      })}
+    }
+  }
+}
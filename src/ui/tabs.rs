@@ -0,0 +1,320 @@
+use crate::ui::overlay_modal::overlay;
+use crate::ui::results::QueryResults;
+use crate::workspace::{SavedTab, Workspace};
+use freya::prelude::*;
+use std::path::PathBuf;
+use tracing::error;
+
+/// One SQL editor tab. `editable` and `results` are signal handles, so copies of them stay
+/// attached to this tab.
+#[derive(Clone)]
+pub struct EditorTab {
+  pub id: usize,
+  pub editable: UseEditable,
+  pub results: Signal<QueryResults>,
+  /// Backing `.sql` file
+  pub path: Option<PathBuf>,
+  /// Contents at the last open/save; the tab is dirty while the editor differs from it
+  pub saved_content: String,
+}
+
+impl EditorTab {
+  pub fn title(&self) -> String {
+    self
+      .path
+      .as_ref()
+      .and_then(|p| p.file_name())
+      .map(|n| n.to_string_lossy().to_string())
+      .unwrap_or_else(|| format!("Untitled {}", self.id))
+  }
+
+  pub fn content(&self) -> String {
+    self.editable.editor().read().to_string()
+  }
+
+  pub fn is_dirty(&self) -> bool {
+    self.content() != self.saved_content
+  }
+}
+
+pub struct EditorTabs {
+  pub tabs: Vec<EditorTab>,
+  pub active: usize,
+  next_id: usize,
+  clipboard: UseClipboard,
+  platform: UsePlatform,
+}
+
+impl EditorTabs {
+  pub fn active_tab(&self) -> &EditorTab {
+    &self.tabs[self.active]
+  }
+
+  pub fn active_editable(&self) -> UseEditable {
+    self.active_tab().editable
+  }
+
+  pub fn active_results(&self) -> Signal<QueryResults> {
+    self.active_tab().results
+  }
+
+  /// Opens a new tab and makes it active. Must run inside the app scope (render or event
+  /// handler), as the tab's signals are owned by it.
+  pub fn open(&mut self, path: Option<PathBuf>, content: &str, dirty: bool) {
+    let editable = UseEditable::new_in_hook(
+      self.clipboard,
+      self.platform,
+      EditableConfig::new(content.into()).with_allow_tabs(true),
+      EditableMode::MultipleLinesSingleEditor,
+    );
+    let results = Signal::new(QueryResults::default());
+    let saved_content = if dirty { String::new() } else { content.to_string() };
+    self.next_id += 1;
+    self.tabs.push(EditorTab { id: self.next_id, editable, results, path, saved_content });
+    self.active = self.tabs.len() - 1;
+  }
+
+  /// Closes a tab, always keeping at least one empty tab open.
+  pub fn close(&mut self, index: usize) {
+    if index >= self.tabs.len() {
+      return;
+    }
+    self.tabs.remove(index);
+    if self.tabs.is_empty() {
+      self.open(None, "", false);
+    }
+    if self.active >= index && self.active > 0 {
+      self.active -= 1;
+    }
+  }
+
+  pub fn mark_saved(&mut self, path: PathBuf) {
+    let tab = &mut self.tabs[self.active];
+    tab.saved_content = tab.content();
+    tab.path = Some(path);
+  }
+
+  pub fn to_workspace(&self) -> Workspace {
+    Workspace {
+      tabs: self
+        .tabs
+        .iter()
+        .map(|t| SavedTab { path: t.path.clone(), content: t.content(), dirty: t.is_dirty() })
+        .collect(),
+      active: self.active,
+    }
+  }
+
+  pub fn persist(&self) {
+    if let Err(e) = self.to_workspace().save(&Workspace::default_path()) {
+      error!("Unable to save workspace: {e:?}");
+    }
+  }
+}
+
+/// Restores the tabs of the last session, or a single empty tab.
+pub fn use_editor_tabs() -> Signal<EditorTabs> {
+  let clipboard = use_clipboard();
+  let platform = use_platform();
+
+  use_hook(|| {
+    let workspace = Workspace::load(&Workspace::default_path()).unwrap_or_else(|e| {
+      error!("Unable to restore workspace: {e:?}");
+      Workspace::default()
+    });
+
+    let mut tabs = EditorTabs { tabs: vec![], active: 0, next_id: 0, clipboard, platform };
+    for saved in workspace.tabs.iter() {
+      // Dirty tabs keep their unsaved edits but still compare against the file on disk
+      tabs.open(saved.path.clone(), &saved.content, saved.dirty);
+      if saved.dirty {
+        if let Some(on_disk) = saved.path.as_deref().and_then(|p| std::fs::read_to_string(p).ok()) {
+          tabs.tabs.last_mut().unwrap().saved_content = on_disk;
+        }
+      }
+    }
+    if tabs.tabs.is_empty() {
+      tabs.open(None, "", false);
+    }
+    tabs.active = workspace.active.min(tabs.tabs.len() - 1);
+    Signal::new(tabs)
+  })
+}
+
+pub fn tabs_bar(
+  mut tabs: Signal<EditorTabs>,
+  mut file_dialog: Signal<Option<FileAction>>,
+) -> Element {
+  let mut save_active = move |_: ()| {
+    let tab = tabs.read().active_tab().clone();
+    match tab.path.clone() {
+      Some(path) => match crate::workspace::write_sql_file(&path, &tab.content()) {
+        Ok(_) => {
+          tabs.write().mark_saved(path);
+          tabs.read().persist();
+        }
+        Err(e) => error!("{e:?}"),
+      },
+      None => file_dialog.set(Some(FileAction::SaveAs)),
+    }
+  };
+
+  rsx!(
+    rect {
+      width: "100%",
+      direction: "horizontal",
+      spacing: "4",
+      cross_align: "center",
+      for (i, tab) in tabs.read().tabs.iter().enumerate() {
+        rect {
+          key: "{tab.id}",
+          direction: "horizontal",
+          padding: "3 6",
+          spacing: "6",
+          corner_radius: "6",
+          background: if i == tabs.read().active { "rgb(225,225,245)" } else { "rgb(245,245,245)" },
+          onclick: move |_| {
+            tabs.write().active = i;
+            tabs.read().persist();
+          },
+          label {
+            font_size: "12",
+            { if tab.is_dirty() { format!("{} •", tab.title()) } else { tab.title() } }
+          }
+          label {
+            font_size: "12",
+            color: "rgb(120,120,120)",
+            onclick: {
+              let (id, dirty) = (tab.id, tab.is_dirty());
+              move |e: Event<MouseData>| {
+                e.stop_propagation();
+                if dirty {
+                  file_dialog.set(Some(FileAction::Discard(id)));
+                } else {
+                  tabs.write().close(i);
+                  tabs.read().persist();
+                }
+              }
+            },
+            "×"
+          }
+        }
+      }
+      Button {
+        onclick: move |_| {
+          tabs.write().open(None, "", false);
+          tabs.read().persist();
+        },
+        label { "+" }
+      }
+      rect { width: "flex(1)" }
+      Button { onclick: move |_| file_dialog.set(Some(FileAction::Open)), label { "Open" } }
+      Button { onclick: move |_| save_active(()), label { "Save" } }
+      Button { onclick: move |_| file_dialog.set(Some(FileAction::SaveAs)), label { "Save as" } }
+    }
+  )
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FileAction {
+  Open,
+  SaveAs,
+  /// Close the tab with this id, which has unsaved changes
+  Discard(usize),
+}
+
+/// Asks for a file path to open into a new tab or to save the active tab to, or confirms that
+/// the unsaved changes of a tab being closed can be discarded.
+pub fn file_dialog(
+  mut action: Signal<Option<FileAction>>,
+  mut tabs: Signal<EditorTabs>,
+) -> Element {
+  let mut path = use_signal(String::new);
+  let mut status = use_signal(String::new);
+
+  let mut confirm = move |_: ()| {
+    let target = PathBuf::from(path.read().trim());
+    if target.as_os_str().is_empty() {
+      return;
+    }
+    let result = match *action.read() {
+      Some(FileAction::Open) => crate::workspace::read_sql_file(&target).map(|content| {
+        tabs.write().open(Some(target.clone()), &content, false);
+      }),
+      Some(FileAction::SaveAs) => {
+        let content = tabs.read().active_tab().content();
+        crate::workspace::write_sql_file(&target, &content)
+          .map(|_| tabs.write().mark_saved(target.clone()))
+      }
+      Some(FileAction::Discard(_)) | None => Ok(()),
+    };
+    match result {
+      Ok(_) => {
+        tabs.read().persist();
+        status.set(String::new());
+        action.set(None);
+      }
+      Err(e) => status.set(format!("{e}")),
+    }
+  };
+
+  let mut discard = move |id: usize| {
+    let index = tabs.read().tabs.iter().position(|tab| tab.id == id);
+    if let Some(index) = index {
+      tabs.write().close(index);
+      tabs.read().persist();
+    }
+    action.set(None);
+  };
+
+  let title = match *action.read() {
+    Some(FileAction::Open) => "Open SQL file".to_string(),
+    Some(FileAction::SaveAs) => "Save SQL as".to_string(),
+    Some(FileAction::Discard(id)) => {
+      let name = tabs.read().tabs.iter().find(|tab| tab.id == id).map(|tab| tab.title());
+      format!("Discard the unsaved changes to {}?", name.unwrap_or_default())
+    }
+    None => String::new(),
+  };
+  let discarding = match *action.read() {
+    Some(FileAction::Discard(id)) => Some(id),
+    _ => None,
+  };
+
+  rsx! {
+    if action.read().is_some() {
+      {overlay(move || action.set(None), rsx! {
+        rect {
+          width: "500",
+          padding: "12",
+          background: "white",
+          corner_radius: "8",
+          spacing: "8",
+          label { font_weight: "bold", "{title}" }
+          if discarding.is_none() {
+            Input {
+              value: path.read().clone(),
+              placeholder: "/path/to/query.sql",
+              onchange: move |v: String| path.set(v),
+            }
+          }
+          if !status.read().is_empty() {
+            label { color: "rgb(200,0,0)", "{status}" }
+          }
+          rect {
+            width: "100%",
+            direction: "horizontal",
+            main_align: "end",
+            spacing: "10",
+            Button { onclick: move |_| action.set(None), label { "Cancel" } }
+            if let Some(id) = discarding {
+              Button { onclick: move |_| discard(id), label { "Discard" } }
+            } else {
+              Button { onclick: move |_| confirm(()), label { "OK" } }
+            }
+          }
+        }
+      })}
+    }
+  }
+}
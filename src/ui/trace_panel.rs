@@ -0,0 +1,116 @@
+use crate::conversation::Conversation;
+use crate::ui::overlay_modal::overlay;
+use freya::prelude::*;
+
+/// Step-by-step view of what the agent did for each question of the conversation, newest
+/// question selected first.
+pub fn trace_panel(mut show_panel: Signal<bool>, conversation: Signal<Conversation>) -> Element {
+  // index into `traces`; `None` follows the newest one
+  let mut selected = use_signal(|| None::<usize>);
+  let conv = conversation.read();
+  let count = conv.traces.len();
+  let current = selected().filter(|i| *i < count).or(count.checked_sub(1));
+
+  rsx! {
+    if show_panel() {
+      {overlay(move || show_panel.set(false), rsx! {
+        rect {
+          width: "900",
+          height: "550",
+          padding: "12",
+          background: "white",
+          corner_radius: "8",
+          direction: "horizontal",
+          spacing: "12",
+          rect {
+            width: "250",
+            height: "100%",
+            spacing: "6",
+            label { font_weight: "bold", "Agent trace" }
+            if count == 0 {
+              label { font_size: "11", "No questions asked yet" }
+            }
+            ScrollView {
+              for (i, trace) in conv.traces.iter().enumerate().rev() {
+                rect {
+                  key: "{i}",
+                  width: "100%",
+                  padding: "6",
+                  margin: "2 0",
+                  corner_radius: "6",
+                  background: if Some(i) == current {
+                    "rgb(225,235,255)"
+                  } else if trace.final_sql().is_none() {
+                    "rgb(255,240,240)"
+                  } else {
+                    "rgb(245,245,245)"
+                  },
+                  onclick: move |_| selected.set(Some(i)),
+                  label { font_size: "11", max_lines: "2", text_overflow: "ellipsis", "{trace.question}" }
+                  label {
+                    font_size: "10",
+                    color: "rgb(100,100,100)",
+                    {
+                      format!(
+                        "{} · {} steps · {} ms · ~{} tokens",
+                        trace.started.format("%H:%M:%S"),
+                        trace.steps.len(),
+                        trace.total_latency_ms(),
+                        trace.total_tokens(),
+                      )
+                    }
+                  }
+                }
+              }
+            }
+          }
+          rect {
+            width: "fill",
+            height: "100%",
+            ScrollView {
+              if let Some(trace) = current.and_then(|i| conv.traces.get(i)) {
+                for (i, step) in trace.steps.iter().enumerate() {
+                  rect {
+                    key: "{i}",
+                    width: "100%",
+                    padding: "6",
+                    margin: "2 0",
+                    corner_radius: "6",
+                    background: if step.event.is_failure() { "rgb(255,240,240)" } else { "rgb(245,245,245)" },
+                    label {
+                      font_size: "11",
+                      font_weight: "bold",
+                      {
+                        format!(
+                          "{}. +{} ms  {}",
+                          i + 1,
+                          (step.timestamp - trace.started).num_milliseconds(),
+                          step.event.title(),
+                        )
+                      }
+                    }
+                    label {
+                      font_size: "10",
+                      color: "rgb(100,100,100)",
+                      {
+                        format!("took {} ms · ~{} tokens", step.latency_ms, step.tokens)
+                      }
+                    }
+                    if !step.event.detail().is_empty() {
+                      label {
+                        font_size: "10",
+                        max_lines: "8",
+                        text_overflow: "ellipsis",
+                        "{step.event.detail()}"
+                      }
+                    }
+                  }
+                }
+              }
+            }
+          }
+        }
+      })}
+    }
+  }
+}
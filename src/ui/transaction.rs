@@ -0,0 +1,156 @@
+use crate::agent::AGENT;
+use crate::db_client::TxStatus;
+use crate::ui::overlay_modal::overlay;
+use freya::prelude::*;
+use tracing::error;
+
+/// Reloads the transaction status after anything that may have changed it.
+pub async fn refresh_tx_status(mut tx_status: Signal<TxStatus>) {
+  if let Some(agent) = AGENT.get() {
+    tx_status.set(agent.db_client.tx_status().await);
+  }
+}
+
+fn status_label(status: TxStatus) -> (&'static str, &'static str) {
+  match status {
+    TxStatus::Idle => ("Auto-commit", "rgb(100,100,100)"),
+    TxStatus::InTransaction => ("In transaction", "rgb(180,120,0)"),
+    TxStatus::Failed => ("Transaction failed", "rgb(200,0,0)"),
+  }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum TxAction {
+  Begin,
+  Commit,
+  Rollback,
+}
+
+/// Disables the window's close button while a transaction is open, so that closing goes through
+/// [`close_dialog`]. Freya closes the window on a close request without asking, so a close sent
+/// by the window manager (e.g. Alt+F4) still ends the session and the server rolls back.
+pub fn use_close_guard(tx_status: Signal<TxStatus>) {
+  let platform = use_platform();
+  use_effect(move || {
+    let open = *tx_status.read() != TxStatus::Idle;
+    platform.with_window(move |window| {
+      // Freya doesn't re-export the button flags, so they are derived from the current ones
+      let current = window.enabled_buttons();
+      let all = current.union(current.complement());
+      let buttons = if open {
+        all
+          .iter_names()
+          .filter(|(name, _)| *name != "CLOSE")
+          .fold(all.difference(all), |buttons, (_, flag)| buttons.union(flag))
+      } else {
+        all
+      };
+      window.set_enabled_buttons(buttons);
+    });
+  });
+}
+
+/// Transaction status with Begin / Commit / Rollback controls, and Quit while a transaction is
+/// open.
+pub fn transaction_bar(tx_status: Signal<TxStatus>, mut show_close: Signal<bool>) -> Element {
+  let mut message = use_signal(String::new);
+  let status = *tx_status.read();
+  let (text, color) = status_label(status);
+
+  let run = move |action: TxAction| {
+    spawn(async move {
+      let Some(agent) = AGENT.get() else {
+        return;
+      };
+      let result = match action {
+        TxAction::Begin => agent.db_client.begin().await,
+        TxAction::Commit => agent.db_client.commit().await,
+        TxAction::Rollback => agent.db_client.rollback().await,
+      };
+      match result {
+        Ok(_) => message.set(String::new()),
+        Err(e) => {
+          error!("Transaction {action:?} failed: {e:?}");
+          message.set(format!("{e}"));
+        }
+      }
+      refresh_tx_status(tx_status).await;
+    });
+  };
+
+  rsx!(
+    rect {
+      direction: "horizontal",
+      spacing: "8",
+      cross_align: "center",
+      label { font_size: "12", font_weight: "bold", color: color, "{text}" }
+      if status == TxStatus::Idle {
+        Button { onclick: move |_| run(TxAction::Begin), label { "Begin" } }
+      } else {
+        if status == TxStatus::InTransaction {
+          Button { onclick: move |_| run(TxAction::Commit), label { "Commit" } }
+        }
+        Button { onclick: move |_| run(TxAction::Rollback), label { "Rollback" } }
+        Button { onclick: move |_| show_close.set(true), label { "Quit" } }
+      }
+      if !message.read().is_empty() {
+        label { font_size: "11", color: "rgb(200,0,0)", "{message}" }
+      }
+    }
+  )
+}
+
+/// Asks whether to commit or roll back the open transaction before closing the app.
+pub fn close_dialog(mut show_close: Signal<bool>, tx_status: Signal<TxStatus>) -> Element {
+  let platform = use_platform();
+  let mut message = use_signal(String::new);
+
+  let close = move |action: TxAction| {
+    spawn(async move {
+      if let Some(agent) = AGENT.get() {
+        let result = match action {
+          TxAction::Commit => agent.db_client.commit().await,
+          _ => agent.db_client.rollback().await,
+        };
+        if let Err(e) = result {
+          error!("Transaction {action:?} before closing failed: {e:?}");
+          message.set(format!("{e}"));
+          refresh_tx_status(tx_status).await;
+          return;
+        }
+      }
+      platform.exit();
+    });
+  };
+  let can_commit = *tx_status.read() == TxStatus::InTransaction;
+
+  rsx! {
+    if show_close() {
+      {overlay(move || show_close.set(false), rsx! {
+        rect {
+          width: "420",
+          padding: "12",
+          background: "white",
+          corner_radius: "8",
+          spacing: "10",
+          label { font_weight: "bold", "A transaction is open" }
+          label { font_size: "12", "Commit or roll back its changes before closing?" }
+          if !message.read().is_empty() {
+            label { font_size: "11", color: "rgb(200,0,0)", "{message}" }
+          }
+          rect {
+            width: "100%",
+            direction: "horizontal",
+            main_align: "end",
+            spacing: "8",
+            if can_commit {
+              Button { onclick: move |_| close(TxAction::Commit), label { "Commit and quit" } }
+            }
+            Button { onclick: move |_| close(TxAction::Rollback), label { "Roll back and quit" } }
+            Button { onclick: move |_| show_close.set(false), label { "Cancel" } }
+          }
+        }
+      })}
+    }
+  }
+}
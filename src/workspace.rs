@@ -0,0 +1,87 @@
+use crate::config::Setting;
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// An open editor tab as persisted between runs.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SavedTab {
+  /// Backing `.sql` file, if the tab was opened from or saved to one
+  #[serde(default)]
+  pub path: Option<PathBuf>,
+  /// Editor contents, kept so unsaved work survives a restart
+  #[serde(default)]
+  pub content: String,
+  /// Whether `content` differs from the backing file
+  #[serde(default)]
+  pub dirty: bool,
+}
+
+/// Open tabs, stored in `workspace.json` next to `config.toml`.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct Workspace {
+  pub tabs: Vec<SavedTab>,
+  #[serde(default)]
+  pub active: usize,
+}
+
+impl Workspace {
+  pub fn default_path() -> PathBuf {
+    Setting::data_path("workspace.json")
+  }
+
+  pub fn load(path: &Path) -> anyhow::Result<Self> {
+    if !path.exists() {
+      return Ok(Self::default());
+    }
+    let raw = std::fs::read_to_string(path)
+      .with_context(|| format!("failed to read workspace {}", path.display()))?;
+    let mut workspace: Workspace = serde_json::from_str(&raw)?;
+
+    // A file that changed on disk wins over a stale clean copy
+    for tab in workspace.tabs.iter_mut().filter(|t| !t.dirty) {
+      if let Some(content) = tab.path.as_deref().and_then(|p| std::fs::read_to_string(p).ok()) {
+        tab.content = content;
+      }
+    }
+    Ok(workspace)
+  }
+
+  pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+    let body = serde_json::to_string_pretty(self)?;
+    std::fs::write(path, body)
+      .with_context(|| format!("failed to write workspace {}", path.display()))
+  }
+}
+
+pub fn read_sql_file(path: &Path) -> anyhow::Result<String> {
+  std::fs::read_to_string(path).with_context(|| format!("failed to open {}", path.display()))
+}
+
+pub fn write_sql_file(path: &Path, content: &str) -> anyhow::Result<()> {
+  std::fs::write(path, content).with_context(|| format!("failed to save {}", path.display()))
+}
+
+#[test]
+fn test_workspace_roundtrip_refreshes_clean_tabs() {
+  let dir = std::env::temp_dir().join(format!("pg-admin-workspace-{}", std::process::id()));
+  std::fs::create_dir_all(&dir).unwrap();
+  let sql_path = dir.join("report.sql");
+  write_sql_file(&sql_path, "SELECT 1;").unwrap();
+
+  let workspace = Workspace {
+    tabs: vec![
+      SavedTab { path: Some(sql_path.clone()), content: "SELECT 1;".into(), dirty: false },
+      SavedTab { path: None, content: "SELECT now();".into(), dirty: true },
+    ],
+    active: 1,
+  };
+  let ws_path = dir.join("workspace.json");
+  workspace.save(&ws_path).unwrap();
+  assert_eq!(Workspace::load(&ws_path).unwrap(), workspace);
+
+  write_sql_file(&sql_path, "SELECT 2;").unwrap();
+  assert_eq!(Workspace::load(&ws_path).unwrap().tabs[0].content, "SELECT 2;");
+
+  let _ = std::fs::remove_dir_all(&dir);
+}
@@ -1,4 +1,3 @@
-
 use freya::prelude::*;
 use freya_testing::launch::launch_test;
 use freya_testing::prelude::*;
@@ -24,18 +23,13 @@ async fn postgres_config_view_shows_value() {
       let conf = PostgresConfig {
         dbname: "postgres".to_string(),
         host: "postgres".to_string(),
-        password: "postgres".to_string(),
+        password: "s3cret".to_string(),
         user: "postgres".to_string(),
         port: 5432,
+        ..Default::default()
       };
       let tables = vec![];
-      PostgresStatus::Connected {
-        config: format!(
-          "postgresql://{}:{}@{}/{}",
-          conf.user, conf.password, conf.host, conf.dbname
-        ),
-        tables,
-      }
+      PostgresStatus::Connected { config: conf.redacted_url(), tables }
     });
     postgres_config_view(&r, show_modal, t_signal)
   }
@@ -45,7 +39,9 @@ async fn postgres_config_view_shows_value() {
 
   let root = utils.root();
   let rect = root.get(0);
-  assert!(rect.get(0).get(0).get(0).get(0).text().unwrap().contains("postgres"));
+  let text = rect.get(0).get(0).get(0).get(0).text().unwrap();
+  assert!(text.contains("postgres"));
+  assert!(!text.contains("s3cret"));
 }
 
 #[tokio::test]